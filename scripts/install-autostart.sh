@@ -124,36 +124,12 @@ elif [ "$PLATFORM" = "linux" ]; then
     if command -v systemctl >/dev/null; then
         echo -e "${GREEN}==> Setting up Linux systemd user service...${NC}"
         
-        SYSTEMD_DIR="$HOME/.config/systemd/user"
-        mkdir -p "$SYSTEMD_DIR"
-        
-        # Create systemd service file
-        SYSTEMD_FILE="$SYSTEMD_DIR/isup.service"
-        
-        cat > "$SYSTEMD_FILE" << EOL
-[Unit]
-Description=isup monitoring service
-After=network.target
-
-[Service]
-ExecStart=$INSTALL_DIR/$BINARY_NAME daemon
-Restart=on-failure
-RestartSec=5s
-
-[Install]
-WantedBy=default.target
-EOL
-        
-        # Reload systemd
-        systemctl --user daemon-reload
-        
-        # Enable and start the service
-        systemctl --user enable isup.service
-        systemctl --user start isup.service
+        # isup writes, enables and starts its own user unit
+        "$INSTALL_DIR/$BINARY_NAME" service install
         
         echo -e "${GREEN}==> ✅ systemd user service installed and started!${NC}"
-        echo -e "${GREEN}==> check status with: systemctl --user status isup.service${NC}"
-        echo -e "${GREEN}==> Logs are available at: $LOG_DIR${NC}"
+        echo -e "${GREEN}==> check status with: isup service status${NC}"
+        echo -e "${GREEN}==> Logs are available with: journalctl --user -u isup${NC}"
     
     # Fallback to desktop entry if systemd is not available
     else
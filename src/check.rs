@@ -0,0 +1,579 @@
+use anyhow::{Context, Result};
+use colored::*;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use terminal_size::terminal_size;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::utils::get_status_description;
+
+// How a one-shot check classifies a site
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SiteState {
+    Up,
+    // 403: the server answered but refuses automated access
+    Restricted,
+    // Any other non-success status that isn't a server error
+    Reachable,
+    Down,
+    // DNS failure or 404
+    NotFound,
+}
+
+impl SiteState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SiteState::Up => "up",
+            SiteState::Restricted => "restricted",
+            SiteState::Reachable => "reachable",
+            SiteState::Down => "down",
+            SiteState::NotFound => "not_found",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub url: String,
+    pub state: SiteState,
+    pub status_code: Option<u16>,
+    pub description: String,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub checked_at: i64,
+}
+
+pub const CHECK_COLUMNS: &[&str] = &[
+    "url",
+    "state",
+    "status_code",
+    "description",
+    "latency_ms",
+    "checked_at",
+    "error",
+];
+
+impl CheckResult {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "url": self.url,
+            "state": self.state.as_str(),
+            "status_code": self.status_code,
+            "description": self.description,
+            "latency_ms": self.latency_ms,
+            "checked_at": self.checked_at,
+            "error": self.error,
+        })
+    }
+}
+
+// Ensure URL has a scheme. A bare host is an https site; any other scheme
+// (push://, cmd://, process://, ...) picks a kind of monitor and is kept.
+pub fn normalize_url(url: &str) -> String {
+    if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+// Check a single site and classify the outcome
+pub async fn check_url(client: &Client, url: &str) -> CheckResult {
+    check_request(url, client.get(url)).await
+}
+
+// Same as check_url, for a request that is already set up, e.g. with its own timeout
+pub async fn check_request(url: &str, request: reqwest::RequestBuilder) -> CheckResult {
+    let started = Instant::now();
+    let response = request.send().await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let checked_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    match response {
+        Ok(response) => {
+            let status_code = response.status().as_u16();
+            CheckResult {
+                url: url.to_string(),
+                state: classify_status(status_code),
+                status_code: Some(status_code),
+                description: get_status_description(status_code),
+                error: None,
+                latency_ms,
+                checked_at,
+            }
+        }
+        Err(e) => {
+            let error_string = e.to_string();
+            let (state, explanation) = classify_error(&error_string);
+            CheckResult {
+                url: url.to_string(),
+                state,
+                status_code: None,
+                description: explanation
+                    .map(str::to_string)
+                    .unwrap_or_else(|| error_string.clone()),
+                error: Some(error_string),
+                latency_ms,
+                checked_at,
+            }
+        }
+    }
+}
+
+// Check many sites at once, with at most `concurrency` requests in flight.
+// `on_result` sees each result as it completes; the returned results keep input order.
+pub async fn check_urls(
+    client: &Client,
+    urls: &[String],
+    concurrency: usize,
+    mut on_result: impl FnMut(&CheckResult),
+) -> Vec<CheckResult> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, url) in urls.iter().enumerate() {
+        let client = client.clone();
+        let url = url.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, check_url(&client, &url).await)
+        });
+    }
+
+    let mut results: Vec<Option<CheckResult>> = vec![None; urls.len()];
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((index, result)) = joined {
+            on_result(&result);
+            results[index] = Some(result);
+        }
+    }
+
+    results.into_iter().flatten().collect()
+}
+
+// Read URLs from a file, or from stdin when the path is "-".
+// Blank lines and lines starting with '#' are skipped.
+pub fn read_url_list(path: &str) -> Result<Vec<String>> {
+    let reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(BufReader::new(std::io::stdin()))
+    } else {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open URL list {}", path))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let mut urls = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            urls.push(line.to_string());
+        }
+    }
+    Ok(urls)
+}
+
+pub fn classify_status(status_code: u16) -> SiteState {
+    match status_code {
+        200..=299 => SiteState::Up,
+        404 => SiteState::NotFound,
+        403 => SiteState::Restricted,
+        500.. => SiteState::Down,
+        _ => SiteState::Reachable,
+    }
+}
+
+// Analyze the error to determine if it's a connection issue or DNS resolution problem
+pub fn classify_error(error_string: &str) -> (SiteState, Option<&'static str>) {
+    if error_string.contains("dns error") || error_string.contains("failed to lookup address") {
+        (SiteState::NotFound, Some("Domain could not be resolved - The domain name doesn't exist or DNS resolution failed"))
+    } else if error_string.contains("connection refused") {
+        (
+            SiteState::Down,
+            Some("Connection refused - The server actively rejected the connection"),
+        )
+    } else if error_string.contains("timeout") {
+        (
+            SiteState::Down,
+            Some("Connection timed out - The server did not respond within the timeout period"),
+        )
+    } else {
+        (SiteState::Down, None)
+    }
+}
+
+// Print a result the way the interactive checker always has
+pub fn print_result(result: &CheckResult) {
+    let url = &result.url;
+
+    if let Some(status_code) = result.status_code {
+        let description = &result.description;
+        match result.state {
+            SiteState::Up => {
+                println!("✅ {} is {}!", url.cyan(), "UP".green().bold());
+                println!("status code: {}", status_code.to_string().green());
+            }
+            SiteState::NotFound => {
+                println!("❓ {} {}!", url.cyan(), "DOES NOT EXIST".red().bold());
+                println!("Status code: {}", status_code.to_string().red());
+                println!("Description: {}", description.red());
+            }
+            SiteState::Restricted => {
+                println!(
+                    "⚠️ {} is {}! but restricts automated access",
+                    url.cyan(),
+                    "up".yellow().bold()
+                );
+                println!("Status code: {}", status_code.to_string().red());
+                println!("Description: {}", description.red());
+            }
+            SiteState::Down => {
+                println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
+                println!("Status code: {}", status_code.to_string().red());
+                println!("Description: {}", description.red());
+            }
+            SiteState::Reachable => {
+                println!(
+                    "⚠️ {} is {} but returned status code: {}",
+                    url.cyan(),
+                    "REACHABLE".yellow().bold(),
+                    status_code.to_string().yellow()
+                );
+                println!("Description: {}", description.yellow());
+            }
+        }
+        return;
+    }
+
+    if result.state == SiteState::NotFound {
+        println!("❓ {} {}!", url.cyan(), "DOES NOT EXIST".red().bold());
+    } else {
+        println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
+    }
+
+    // Unrecognised errors are shown verbatim
+    if result.error.as_deref() == Some(result.description.as_str()) {
+        println!("{}", result.description.red());
+    } else {
+        println!("{}", format!("Error: {}", result.description).red());
+    }
+}
+
+// Summary table printed after checking several sites
+pub fn print_summary(results: &[CheckResult]) {
+    let term_width = terminal_size().map(|(w, _)| w.0 as usize).unwrap_or(80);
+    let url_width = (term_width * 50 / 100).min(40);
+
+    println!("{}", "─".repeat(term_width.min(80)));
+    println!(
+        " {:<width$} │ {:<11} │ {:<6} │ {:<8}",
+        "URL".bold(),
+        "STATE".bold(),
+        "CODE".bold(),
+        "LATENCY".bold(),
+        width = url_width
+    );
+    println!("{}", "─".repeat(term_width.min(80)));
+
+    for result in results {
+        let state = match result.state {
+            SiteState::Up => "● UP".green().bold(),
+            SiteState::Restricted => "● RESTRICTED".yellow().bold(),
+            SiteState::Reachable => "● REACHABLE".yellow().bold(),
+            SiteState::Down => "● DOWN".red().bold(),
+            SiteState::NotFound => "○ NOT FOUND".red().bold(),
+        };
+        let code = result
+            .status_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        println!(
+            " {:<width$} │ {:<11} │ {:<6} │ {:<8}",
+            result.url.cyan(),
+            state,
+            code,
+            format!("{}ms", result.latency_ms),
+            width = url_width
+        );
+    }
+    println!("{}", "─".repeat(term_width.min(80)));
+
+    let count = |state: SiteState| {
+        results
+            .iter()
+            .filter(|result| result.state == state)
+            .count()
+    };
+    println!(
+        " {} up, {} down, {} not found, {} other",
+        count(SiteState::Up).to_string().green(),
+        count(SiteState::Down).to_string().red(),
+        count(SiteState::NotFound).to_string().red(),
+        (count(SiteState::Restricted) + count(SiteState::Reachable))
+            .to_string()
+            .yellow()
+    );
+}
+
+// Process exit codes for one-shot checks
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_DOWN: i32 = 1;
+pub const EXIT_NOT_FOUND: i32 = 2;
+pub const EXIT_USAGE: i32 = 3;
+// Something went wrong inside isup itself, e.g. the database couldn't be opened
+pub const EXIT_ERROR: i32 = 4;
+
+// A status code pattern given to --fail-on-status, e.g. "4xx", "503" or "5xx"
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatusPattern {
+    Exact(u16),
+    Class(u16),
+}
+
+impl StatusPattern {
+    pub fn matches(&self, status_code: u16) -> bool {
+        match self {
+            StatusPattern::Exact(code) => *code == status_code,
+            StatusPattern::Class(class) => status_code / 100 == *class,
+        }
+    }
+}
+
+impl std::str::FromStr for StatusPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let invalid = || format!("invalid status pattern '{}', expected e.g. 404 or 4xx", s);
+
+        if let Some(class) = s.strip_suffix("xx") {
+            match class.parse::<u16>() {
+                Ok(class @ 1..=5) => Ok(StatusPattern::Class(class)),
+                _ => Err(invalid()),
+            }
+        } else {
+            match s.parse::<u16>() {
+                Ok(code @ 100..=599) => Ok(StatusPattern::Exact(code)),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+impl CheckResult {
+    // Whether this result should fail the run, given the --fail-on-status patterns
+    pub fn is_failure(&self, fail_on: &[StatusPattern]) -> bool {
+        match self.state {
+            SiteState::Down | SiteState::NotFound => true,
+            _ => self
+                .status_code
+                .is_some_and(|code| fail_on.iter().any(|pattern| pattern.matches(code))),
+        }
+    }
+}
+
+// 0 when everything is up, 2 if any site doesn't exist, otherwise 1 if any check failed
+pub fn exit_code(results: &[CheckResult], fail_on: &[StatusPattern]) -> i32 {
+    if results
+        .iter()
+        .any(|result| result.state == SiteState::NotFound)
+    {
+        EXIT_NOT_FOUND
+    } else if results.iter().any(|result| result.is_failure(fail_on)) {
+        EXIT_DOWN
+    } else {
+        EXIT_OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(state: SiteState, status_code: Option<u16>) -> CheckResult {
+        CheckResult {
+            url: "https://example.com".to_string(),
+            state,
+            status_code,
+            description: String::new(),
+            error: None,
+            latency_ms: 0,
+            checked_at: 0,
+        }
+    }
+
+    #[test]
+    fn status_patterns_parse() {
+        assert_eq!("404".parse(), Ok(StatusPattern::Exact(404)));
+        assert_eq!(" 5XX ".parse(), Ok(StatusPattern::Class(5)));
+        assert_eq!("1xx".parse(), Ok(StatusPattern::Class(1)));
+        for invalid in ["", "99", "600", "6xx", "0xx", "xx", "4x", "abc", "-404"] {
+            assert!(
+                invalid.parse::<StatusPattern>().is_err(),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn status_patterns_match() {
+        assert!(StatusPattern::Exact(404).matches(404));
+        assert!(!StatusPattern::Exact(404).matches(403));
+        assert!(StatusPattern::Class(4).matches(499));
+        assert!(!StatusPattern::Class(4).matches(500));
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code(&[], &[]), EXIT_OK);
+        assert_eq!(exit_code(&[result(SiteState::Up, Some(200))], &[]), EXIT_OK);
+        assert_eq!(
+            exit_code(&[result(SiteState::Restricted, Some(403))], &[]),
+            EXIT_OK
+        );
+        assert_eq!(
+            exit_code(
+                &[
+                    result(SiteState::Up, Some(200)),
+                    result(SiteState::Down, None)
+                ],
+                &[]
+            ),
+            EXIT_DOWN
+        );
+        // Not found wins over down
+        assert_eq!(
+            exit_code(
+                &[
+                    result(SiteState::Down, Some(500)),
+                    result(SiteState::NotFound, Some(404))
+                ],
+                &[]
+            ),
+            EXIT_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn fail_on_status_turns_results_into_failures() {
+        let fail_on = [StatusPattern::Class(4)];
+        assert_eq!(
+            exit_code(&[result(SiteState::Restricted, Some(403))], &fail_on),
+            EXIT_DOWN
+        );
+        assert_eq!(
+            exit_code(&[result(SiteState::Reachable, Some(302))], &fail_on),
+            EXIT_OK
+        );
+        assert_eq!(
+            exit_code(
+                &[result(SiteState::Up, Some(200))],
+                &[StatusPattern::Exact(200)]
+            ),
+            EXIT_DOWN
+        );
+    }
+
+    #[test]
+    fn url_lists_skip_blanks_and_comments() {
+        let path = std::env::temp_dir().join(format!("isup-urls-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "# sites\nexample.com\n\n  https://a.test/  \n#gone.test\n",
+        )
+        .unwrap();
+        let urls = read_url_list(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(urls, ["example.com", "https://a.test/"]);
+
+        assert!(read_url_list("/nonexistent/isup/urls.txt").is_err());
+    }
+
+    // Serve "/<delay ms>/<status>" on a local port, tracking the peak number of open requests
+    async fn serve_delayed(peak: Arc<std::sync::atomic::AtomicUsize>) -> String {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let open = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (open, peak) = (open.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let now = open.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/");
+                    let mut parts = path.trim_start_matches('/').split('/');
+                    let delay: u64 = parts.next().unwrap().parse().unwrap();
+                    let status: u16 = parts.next().unwrap().parse().unwrap();
+                    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+
+                    open.fetch_sub(1, Ordering::SeqCst);
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        status
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn check_urls_keeps_input_order_and_limits_concurrency() {
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let base = serve_delayed(peak.clone()).await;
+        // The slowest request comes first, so completion order differs from input order
+        let urls: Vec<String> = ["300/200", "10/404", "50/500", "10/200", "80/403", "10/200"]
+            .iter()
+            .map(|path| format!("{}/{}", base, path))
+            .collect();
+        let client = Client::builder().no_proxy().build().unwrap();
+
+        let mut completed = Vec::new();
+        let results = check_urls(&client, &urls, 2, |result| {
+            completed.push(result.url.clone())
+        })
+        .await;
+
+        let urls_out: Vec<&str> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(urls_out, urls);
+        assert_ne!(completed, urls);
+        let codes: Vec<Option<u16>> = results.iter().map(|r| r.status_code).collect();
+        assert_eq!(
+            codes,
+            [
+                Some(200),
+                Some(404),
+                Some(500),
+                Some(200),
+                Some(403),
+                Some(200)
+            ]
+        );
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
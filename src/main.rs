@@ -1,15 +1,34 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::*;
-use reqwest::blocking::Client;
-use reqwest::StatusCode;
+use reqwest::Client;
+use serde_json::json;
+use std::path::PathBuf;
 use std::time::Duration;
 
+#[macro_use]
+mod output;
+use output::{emit_record, emit_records, is_structured, OutputFormat};
+mod check;
+use check::{
+    check_urls, exit_code, normalize_url, print_result, print_summary, read_url_list,
+    StatusPattern, CHECK_COLUMNS, EXIT_ERROR, EXIT_OK, EXIT_USAGE,
+};
 mod utils;
-use utils::get_status_description;
+use utils::parse_duration;
+mod wait;
+use wait::{parse_interval, wait_for, WaitOptions};
 mod monitor;
-use monitor::commands::{add_site, list_sites, remove_site, status_sites};
-use monitor::service::{run_monitor_service, stop_monitoring_service, is_daemon_running};
+use monitor::commands::{
+    add_command_monitor, add_push_monitor, add_site, build_statuspage, check_now, list_sites,
+    pause_site, ping_push_monitor, reload_daemon, remove_site, show_logs, status_sites,
+    LogsOptions,
+};
+use monitor::config::LogLevel;
+use monitor::log::{LogFile, LogFormat};
+use monitor::service::{is_daemon_running, run_monitor_service, stop_monitoring_service};
+use monitor::systemd::{install_service, service_status, uninstall_service};
+use monitor::watch::watch_sites;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
@@ -21,21 +40,74 @@ struct Args {
     #[clap(required = false)]
     urls: Vec<String>,
 
+    /// Read URLs from a file, one per line ("-" for stdin)
+    #[clap(short, long)]
+    file: Option<String>,
+
+    /// Maximum number of sites checked at the same time
+    #[clap(short, long, default_value = "16")]
+    concurrency: usize,
+
     /// Timeout in seconds
     #[clap(short, long, default_value = "10")]
     timeout: u64,
-}
 
+    /// Print nothing; report results through the exit code only
+    #[clap(short, long, global = true)]
+    quiet: bool,
+
+    /// Treat these status codes as failures (e.g. 4xx, 404, 5xx); repeatable or comma separated
+    #[clap(long, value_delimiter = ',')]
+    fail_on_status: Vec<StatusPattern>,
+
+    /// Output format: text, json, ndjson or csv
+    #[clap(short, long, global = true, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
 #[derive(Subcommand, Debug)]
 enum Commands {
     Add {
-        url: String,
+        #[clap(required_unless_present_any = ["push", "command"])]
+        url: Option<String>,
 
         #[clap(short, long, default_value = "16.9")]
         interval: f64,
 
         #[clap(short, long, default_value = "device")]
         notify: String,
+
+        /// Request timeout in seconds (defaults to the timeout in ~/.isup/config)
+        #[clap(short, long)]
+        timeout: Option<f64>,
+
+        /// Add a push monitor with this name, which expects pings instead of checking a URL
+        #[clap(long, conflicts_with_all = ["url", "timeout", "command"], requires = "expect_every")]
+        push: Option<String>,
+
+        /// How often the push monitor expects a ping, e.g. 1h
+        #[clap(long, value_parser = parse_duration, requires = "push")]
+        expect_every: Option<Duration>,
+
+        /// How late a ping may be before the push monitor goes down
+        #[clap(long, value_parser = parse_duration, default_value = "5m", requires = "push")]
+        grace: Duration,
+
+        /// Add a command monitor that runs this shell command: exit 0 is up, 1 degraded, 2 or more down
+        #[clap(long, conflicts_with = "url")]
+        command: Option<String>,
+
+        /// Name of the command monitor (defaults to the command's program name)
+        #[clap(long, requires = "command")]
+        name: Option<String>,
+
+        /// Environment variable for the command, as KEY=VALUE; may be repeated
+        #[clap(long = "env", value_name = "KEY=VALUE", requires = "command")]
+        env: Vec<String>,
+    },
+
+    /// Ping a push monitor, e.g. at the end of a cron job
+    Ping {
+        name: String,
     },
 
     List,
@@ -46,128 +118,369 @@ enum Commands {
 
     Status,
 
-    Daemon,
+    /// Stop checking a site without removing it
+    Pause {
+        url: String,
+    },
+
+    /// Resume checking a paused site
+    Resume {
+        url: String,
+    },
+
+    /// Tell the running daemon to reload its sites and config
+    Reload,
+
+    /// Have the daemon check a monitored site right now
+    CheckNow {
+        #[clap(required_unless_present = "all")]
+        url: Option<String>,
+
+        /// Check every monitored site
+        #[clap(short, long, conflicts_with = "url")]
+        all: bool,
+    },
+
+    /// Live dashboard of all monitored sites
+    Watch {
+        /// How often the dashboard refreshes from the database
+        #[clap(short, long, value_parser = parse_duration, default_value = "2s")]
+        refresh: Duration,
+    },
+
+    /// Run the monitoring daemon (normally started for you by `isup add`)
+    Daemon {
+        /// Also write log lines to stdout, e.g. under a service manager
+        #[clap(long)]
+        foreground: bool,
+
+        /// Lowest level to log (error, warn, info or debug); overrides log_level in ~/.isup/config
+        #[clap(long)]
+        log_level: Option<LogLevel>,
+
+        /// Log line format: text or json
+        #[clap(long, value_enum, default_value = "text")]
+        log_format: LogFormat,
+    },
+
+    /// Block until a URL is up (or down, with --until-down)
+    Wait {
+        url: String,
+
+        /// Give up after this long (e.g. 30s, 5m)
+        #[clap(long, value_parser = parse_duration, default_value = "5m")]
+        timeout: Duration,
+
+        /// Time between checks (at least 100ms)
+        #[clap(short, long, value_parser = parse_interval, default_value = "2s")]
+        interval: Duration,
+
+        /// Timeout for each individual request
+        #[clap(long, value_parser = parse_duration, default_value = "10s")]
+        request_timeout: Duration,
+
+        /// Wait for the URL to go down instead
+        #[clap(long)]
+        until_down: bool,
+
+        /// Treat these status codes as unhealthy (e.g. 4xx, 503)
+        #[clap(long, value_delimiter = ',')]
+        fail_on_status: Vec<StatusPattern>,
+    },
 
     StopMs,
+
+    /// Show the daemon's logs
+    Logs {
+        /// Keep printing new entries as they are logged
+        #[clap(short, long)]
+        follow: bool,
+
+        /// Only show entries about this site
+        #[clap(short, long)]
+        site: Option<String>,
+
+        /// Only show entries from the last e.g. 30m, 1h or 2d
+        #[clap(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+
+        /// Only show entries at this level or more severe (error, warn, info or debug)
+        #[clap(long)]
+        level: Option<LogLevel>,
+
+        /// Which log to read: checks (the daemon log) or notifications
+        #[clap(short, long, value_enum, default_value = "checks")]
+        kind: LogFile,
+
+        /// How many of the latest entries to show (default: 50, or all with --since)
+        #[clap(short = 'n', long)]
+        lines: Option<usize>,
+    },
+
+    /// Manage the systemd user service that runs the daemon (Linux)
+    Service {
+        #[clap(subcommand)]
+        action: ServiceAction,
+    },
+
+    /// Render a static HTML status page from the check history
+    Statuspage {
+        #[clap(subcommand)]
+        action: StatuspageAction,
+    },
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    
+#[derive(Subcommand, Debug)]
+enum StatuspageAction {
+    /// Write index.html and status.json into a directory
+    Build {
+        /// Directory to write the page to; created if missing
+        #[clap(long)]
+        out: PathBuf,
+
+        /// Page title (default: statuspage_title from ~/.isup/config)
+        #[clap(long)]
+        title: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceAction {
+    /// Write, enable and start the systemd user unit
+    Install,
+
+    /// Stop, disable and remove the systemd user unit
+    Uninstall,
+
+    /// Show whether the unit is installed, enabled and running
+    Status,
+}
+
+fn main() {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // Help and version requests aren't errors; everything else is a usage error
+            let code = match e.kind() {
+                clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion => {
+                    EXIT_OK
+                }
+                _ => EXIT_USAGE,
+            };
+            let _ = e.print();
+            std::process::exit(code);
+        }
+    };
+    output::set_format(args.output);
+    output::set_quiet(args.quiet);
+
+    match run(&args) {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+// Run the selected command and return the process exit code
+fn run(args: &Args) -> Result<i32> {
     match &args.command {
-        Some(Commands::Add { url, interval, notify }) => {
-            add_site(url, *interval, notify)?;
-            Ok(())
+        Some(Commands::Add {
+            url,
+            interval,
+            notify,
+            timeout,
+            push,
+            expect_every,
+            grace,
+            command,
+            name,
+            env,
+        }) => {
+            // clap makes sure there is a url, a --command or --push with --expect-every
+            match (push, command, url) {
+                (Some(name), _, _) => {
+                    add_push_monitor(name, expect_every.unwrap_or_default(), *grace)?
+                }
+                (None, Some(command), _) => {
+                    add_command_monitor(command, name.as_deref(), env, *interval, *timeout)?
+                }
+                (None, None, Some(url)) => add_site(url, *interval, notify, *timeout)?,
+                (None, None, None) => {}
+            }
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Ping { name }) => {
+            ping_push_monitor(name)?;
+            Ok(EXIT_OK)
         }
         Some(Commands::List) => {
             list_sites()?;
-            Ok(())
+            Ok(EXIT_OK)
         }
-        Some(Commands::Remove {
-             url }) => {
+        Some(Commands::Remove { url }) => {
             remove_site(url)?;
-            Ok(())
+            Ok(EXIT_OK)
         }
         Some(Commands::Status) => {
             status_sites()?;
-            Ok(())
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Pause { url }) => {
+            pause_site(url, true)?;
+            Ok(EXIT_OK)
         }
-        Some(Commands::Daemon) => {
+        Some(Commands::Resume { url }) => {
+            pause_site(url, false)?;
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Reload) => {
+            reload_daemon()?;
+            Ok(EXIT_OK)
+        }
+        Some(Commands::CheckNow { url, all }) => check_now(url.as_deref(), *all),
+        Some(Commands::Watch { refresh }) => {
+            watch_sites(*refresh)?;
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Daemon {
+            foreground,
+            log_level,
+            log_format,
+        }) => {
+            monitor::log::init(*log_format, *log_level, *foreground);
             run_monitor_service()?;
-            Ok(())
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Wait {
+            url,
+            timeout,
+            interval,
+            request_timeout,
+            until_down,
+            fail_on_status,
+        }) => {
+            let options = WaitOptions {
+                timeout: *timeout,
+                interval: *interval,
+                request_timeout: *request_timeout,
+                until_down: *until_down,
+                fail_on: fail_on_status.clone(),
+            };
+            wait_for(url, &options)
+        }
+        Some(Commands::Logs {
+            follow,
+            site,
+            since,
+            level,
+            kind,
+            lines,
+        }) => {
+            let options = LogsOptions {
+                kind: *kind,
+                site: site.clone(),
+                since: *since,
+                level: *level,
+                lines: lines.or(if since.is_some() { None } else { Some(50) }),
+                follow: *follow,
+            };
+            show_logs(&options)?;
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Service { action }) => {
+            match action {
+                ServiceAction::Install => install_service()?,
+                ServiceAction::Uninstall => uninstall_service()?,
+                ServiceAction::Status => service_status()?,
+            }
+            Ok(EXIT_OK)
+        }
+        Some(Commands::Statuspage { action }) => {
+            match action {
+                StatuspageAction::Build { out, title } => build_statuspage(out, title.as_deref())?,
+            }
+            Ok(EXIT_OK)
         }
         Some(Commands::StopMs) => {
-            if is_daemon_running() {
-                println!("stopping monitoring service...");
+            let was_running = is_daemon_running();
+            if was_running {
+                say!("stopping monitoring service...");
                 stop_monitoring_service()?;
             } else {
-                println!("⚠️ monitoring service is not running");
+                say!("⚠️ monitoring service is not running");
+            }
+            if is_structured() {
+                emit_record(
+                    json!({ "was_running": was_running, "stopped": was_running }),
+                    &["was_running", "stopped"],
+                );
             }
-            Ok(())
+            Ok(EXIT_OK)
         }
         None => {
+            let mut urls: Vec<String> = args.urls.iter().map(|url| normalize_url(url)).collect();
+            if let Some(path) = &args.file {
+                // An unreadable list is bad input, not a down site
+                match read_url_list(path) {
+                    Ok(list) => urls.extend(list.iter().map(|url| normalize_url(url))),
+                    Err(e) => {
+                        eprintln!("{} {:#}", "error:".red().bold(), e);
+                        return Ok(EXIT_USAGE);
+                    }
+                }
+            }
+
+            if urls.is_empty() {
+                eprintln!("{} no URLs to check", "error:".red().bold());
+                eprintln!("\nUsage: isup [OPTIONS] [URLS]... [COMMAND]\n\nFor more information, try '--help'.");
+                return Ok(EXIT_USAGE);
+            }
+
             // Original functionality for direct URL checking
             let client = Client::builder()
                 .user_agent("isup/2.0.3")
                 .timeout(Duration::from_secs(args.timeout))
                 .build()
                 .context("failed to create HTTP client")?;
-            
-            for url_input in &args.urls {
-                // Ensure URL has a scheme
-                let url = if !url_input.starts_with("http://") && !url_input.starts_with("https://") {
-                    format!("https://{}", url_input)
-                } else {
-                    url_input.clone()
-                };
-                
-                println!("checking if {} is up...", url.cyan());
-                
-                match check_site(&client, &url) {
-                    Ok(status) => {
-                        if status.is_success() {
-                            println!("✅ {} is {}!", url.cyan(), "UP".green().bold());
-                            println!("status code: {}", status.as_u16().to_string().green());
-                        } else {
-                            let status_code = status.as_u16();
-                            let description = get_status_description(status_code);
-                            
-                            // Categorize the response based on status code
-                            
-                            if status_code == 404 {
-                                println!("❓ {} {}!", url.cyan(), "DOES NOT EXIST".red().bold());
-                                println!("Status code: {}", status_code.to_string().red());
-                                println!("Description: {}", description.red());
-                            }
-                            else if status_code == 403 {
-                                println!("⚠️ {} is {}! but restricts automated access", url.cyan(), "up".yellow().bold());
-                                println!("Status code: {}", status_code.to_string().red());
-                                println!("Description: {}", description.red());
-                            }
-                            else if status_code >= 500 { 
-                                println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
-                                println!("Status code: {}", status_code.to_string().red());
-                                println!("Description: {}", description.red());
-                            } else {
-                                println!("⚠️ {} is {} but returned status code: {}", 
-                                    url.cyan(), 
-                                    "REACHABLE".yellow().bold(),
-                                    status_code.to_string().yellow());
-                                println!("Description: {}", description.yellow());
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        // Analyze the error to determine if it's a connection issue or DNS resolution problem
-                        let error_string = e.to_string();
-                        if error_string.contains("dns error") || error_string.contains("failed to lookup address") {
-                            println!("❓ {} {}!", url.cyan(), "DOES NOT EXIST".red().bold());
-                            println!("{}", "Error: Domain could not be resolved - The domain name doesn't exist or DNS resolution failed".red());
-                        } else if error_string.contains("connection refused") {
-                            println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
-                            println!("{}", "Error: Connection refused - The server actively rejected the connection".red());
-                        } else if error_string.contains("timeout") {
-                            println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
-                            println!("{}", "Error: Connection timed out - The server did not respond within the timeout period".red());
-                        } else {
-                            println!("❌ {} is {}!", url.cyan(), "DOWN".red().bold());
-                            println!("{}", error_string.red());
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+
+            let print_text = !is_structured() && !output::is_quiet();
+            if urls.len() == 1 {
+                say!("checking if {} is up...", urls[0].cyan());
+            } else {
+                say!("checking {} sites...", urls.len().to_string().cyan());
+                say!();
+            }
+
+            let mut printed = 0;
+            let results =
+                runtime.block_on(check_urls(&client, &urls, args.concurrency, |result| {
+                    if print_text {
+                        // Add a newline between URL checks for better readability
+                        if printed > 0 {
+                            println!();
                         }
+                        print_result(result);
+                        printed += 1;
                     }
-                }
-                
-                // Add a newline between URL checks for better readability
-                if args.urls.len() > 1 && url_input != args.urls.last().unwrap() {
-                    println!();
-                }
+                }));
+
+            if print_text && results.len() > 1 {
+                println!();
+                print_summary(&results);
+            }
+
+            if is_structured() {
+                let records: Vec<_> = results.iter().map(|result| result.to_json()).collect();
+                emit_records(&records, CHECK_COLUMNS);
             }
-            
-            Ok(())
+
+            Ok(exit_code(&results, &args.fail_on_status))
         }
     }
 }
-
-fn check_site(client: &Client, url: &str) -> Result<StatusCode> {
-    let response = client.get(url).send()?;
-    Ok(response.status())
-}
@@ -0,0 +1,647 @@
+use anyhow::{Context, Result};
+use hyper::body::HttpBody;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::check::normalize_url;
+use crate::monitor::commands::site_record;
+use crate::monitor::db::{
+    add_site_to_db, get_all_sites, get_incidents, get_site_by_id, get_site_by_push_token,
+    get_site_by_url, get_site_history, record_ping, remove_site_from_db, set_site_paused,
+    update_site_settings, validate_settings, Site,
+};
+use crate::monitor::http::json_response;
+use crate::monitor::probe;
+use crate::monitor::service::DaemonState;
+use crate::utils::{get_data_dir, parse_duration};
+
+// JSON API for managing monitors, served on api_addr. Every request needs an
+// `Authorization: Bearer <token>` header with the token from ~/.isup/api_token.
+//
+//     GET    /api/status               daemon status and every site
+//     GET    /api/sites                every site
+//     POST   /api/sites                add a site: {"url", "interval", "timeout"}
+//     GET    /api/sites/{id}           one site
+//     PATCH  /api/sites/{id}           change "interval", "timeout" or "paused"
+//     DELETE /api/sites/{id}           stop monitoring a site
+//     GET    /api/sites/{id}/history   recent checks, newest first (?since=1h&limit=100)
+//     GET    /api/sites/{id}/incidents outages, newest first (?since=30d)
+//     POST   /api/sites/{id}/check     check a site right now
+//
+// Push monitors are pinged at /ping/{token} with any method. The token in the
+// path is the push monitor's own, so these requests need no Authorization header.
+
+// Request bodies are small JSON objects
+const MAX_BODY_BYTES: usize = 64 * 1024;
+const DEFAULT_INTERVAL: f64 = 16.9;
+const MAX_HISTORY: usize = 1000;
+
+pub fn get_token_path() -> PathBuf {
+    get_data_dir().join("api_token")
+}
+
+// Read the API token, creating a random one the first time
+pub fn load_or_create_token() -> Result<String> {
+    let path = get_token_path();
+    if let Ok(token) = std::fs::read_to_string(&path) {
+        let token = token.trim().to_string();
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let token = random_token()?;
+    write_private(&path, &token).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(token)
+}
+
+#[cfg(unix)]
+pub fn random_token() -> Result<String> {
+    use std::io::Read;
+
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Each RandomState is seeded by the OS, which is good enough without /dev/urandom
+#[cfg(not(unix))]
+pub fn random_token() -> Result<String> {
+    use std::hash::{BuildHasher, Hasher};
+
+    Ok((0..4)
+        .map(|_| {
+            format!(
+                "{:016x}",
+                std::collections::hash_map::RandomState::new()
+                    .build_hasher()
+                    .finish()
+            )
+        })
+        .collect())
+}
+
+#[cfg(unix)]
+fn write_private(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    writeln!(file, "{}", contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    std::fs::write(path, format!("{}\n", contents))
+}
+
+// Compare without bailing out at the first differing byte
+fn token_matches(given: &str, expected: &str) -> bool {
+    !expected.is_empty()
+        && given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// A failed request: the status to answer with and a message for the "error" field
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, message.into())
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        ApiError(StatusCode::NOT_FOUND, message.into())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", error))
+    }
+}
+
+type ApiResult = std::result::Result<(StatusCode, Value), ApiError>;
+
+pub async fn handle(state: Arc<DaemonState>, request: Request<Body>) -> Response<Body> {
+    if let Some(token) = request.uri().path().strip_prefix("/ping/") {
+        return match ping(&state, token.trim_end_matches('/')).await {
+            Ok((status, body)) => json_response(status, &body),
+            Err(ApiError(status, message)) => json_response(status, &json!({ "error": message })),
+        };
+    }
+
+    let given = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !token_matches(given.trim(), &state.api_token()) {
+        let mut response = json_response(
+            StatusCode::UNAUTHORIZED,
+            &json!({ "error": "missing or invalid token" }),
+        );
+        response
+            .headers_mut()
+            .insert("www-authenticate", "Bearer".parse().expect("valid header"));
+        return response;
+    }
+
+    respond(&state, request).await
+}
+
+// Answer an API request that has already been let in. The dashboard serves
+// the same routes behind its own checks.
+pub async fn respond(state: &Arc<DaemonState>, request: Request<Body>) -> Response<Body> {
+    match route(state, request).await {
+        Ok((status, body)) => json_response(status, &body),
+        Err(ApiError(status, message)) => json_response(status, &json!({ "error": message })),
+    }
+}
+
+async fn route(state: &Arc<DaemonState>, request: Request<Body>) -> ApiResult {
+    let method = request.method().clone();
+    let path = request.uri().path().trim_matches('/').to_string();
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let segments: Vec<&str> = path.split('/').collect();
+    let state = state.clone();
+
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["api", "status"]) => status(&state).await,
+        (&Method::GET, ["api", "sites"]) => {
+            blocking(move || {
+                let now = unix_now();
+                let sites: Vec<Value> = get_all_sites()?
+                    .iter()
+                    .map(|site| record(site, now))
+                    .collect();
+                Ok((StatusCode::OK, Value::Array(sites)))
+            })
+            .await
+        }
+        (&Method::POST, ["api", "sites"]) => {
+            let body = read_json(request).await?;
+            blocking(move || add(&state, body)).await
+        }
+        (&Method::GET, ["api", "sites", id]) => {
+            let id = id.to_string();
+            blocking(move || Ok((StatusCode::OK, record(&find_site(&id)?, unix_now())))).await
+        }
+        (&Method::PATCH, ["api", "sites", id]) => {
+            let id = id.to_string();
+            let body = read_json(request).await?;
+            blocking(move || update(&state, find_site(&id)?, body)).await
+        }
+        (&Method::DELETE, ["api", "sites", id]) => {
+            let id = id.to_string();
+            blocking(move || {
+                let site = find_site(&id)?;
+                remove_site_from_db(&site.url)?;
+                state.request_resync();
+                Ok((
+                    StatusCode::OK,
+                    json!({ "id": site.id, "url": site.url, "removed": true }),
+                ))
+            })
+            .await
+        }
+        (&Method::GET, ["api", "sites", id, "history"]) => {
+            let id = id.to_string();
+            blocking(move || history(&find_site(&id)?, &query)).await
+        }
+        (&Method::GET, ["api", "sites", id, "incidents"]) => {
+            let id = id.to_string();
+            blocking(move || incidents(&find_site(&id)?, &query)).await
+        }
+        (&Method::POST, ["api", "sites", id, "check"]) => {
+            let id = id.to_string();
+            let site = blocking(move || find_site(&id)).await?;
+            let check = state
+                .check_now(&site)
+                .await
+                .map_err(|e| ApiError(StatusCode::CONFLICT, e.to_string()))?;
+            Ok((StatusCode::OK, check.to_json()))
+        }
+        _ => Err(ApiError::not_found(format!(
+            "no route for {} /{}",
+            method, path
+        ))),
+    }
+}
+
+// SQLite blocks, so handlers that touch the database run off the runtime's threads
+async fn blocking<T: Send + 'static>(
+    handler: impl FnOnce() -> std::result::Result<T, ApiError> + Send + 'static,
+) -> std::result::Result<T, ApiError> {
+    tokio::task::spawn_blocking(handler)
+        .await
+        .map_err(|e| ApiError::from(anyhow::Error::from(e)))?
+}
+
+// Record a ping for the push monitor with this token. A monitor that is down
+// or hasn't been checked yet is checked right away, so it recovers without
+// waiting for its next turn.
+async fn ping(state: &Arc<DaemonState>, token: &str) -> ApiResult {
+    let token = token.to_string();
+    let site = blocking(move || {
+        let site = match token.as_str() {
+            "" => None,
+            token => get_site_by_push_token(token)?,
+        }
+        .ok_or_else(|| ApiError::not_found("no push monitor with that token"))?;
+        record_ping(&site.url)?;
+        Ok(site)
+    })
+    .await?;
+    if site.is_up != Some(true) && !site.paused {
+        let _ = state.check_now(&site).await;
+    }
+
+    Ok((
+        StatusCode::OK,
+        json!({ "ok": true, "name": site.push_name() }),
+    ))
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// The same record `isup list --output json` prints, plus the id used in API paths
+fn record(site: &Site, now: i64) -> Value {
+    let mut record = site_record(site, now);
+    record["id"] = json!(site.id);
+    record
+}
+
+fn find_site(id: &str) -> std::result::Result<Site, ApiError> {
+    let id: i64 = id
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("invalid site id '{}'", id)))?;
+    get_site_by_id(id)?.ok_or_else(|| ApiError::not_found(format!("no site with id {}", id)))
+}
+
+async fn read_json(request: Request<Body>) -> std::result::Result<Value, ApiError> {
+    let mut body = request.into_body();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ApiError::bad_request(e.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(ApiError(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "request body is too large".to_string(),
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    match serde_json::from_slice(&bytes) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        Ok(_) => Err(ApiError::bad_request("expected a JSON object")),
+        Err(e) => Err(ApiError::bad_request(format!("invalid JSON: {}", e))),
+    }
+}
+
+// A positive number of seconds from the request body
+fn seconds(body: &Value, key: &str) -> std::result::Result<Option<f64>, ApiError> {
+    match body.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .filter(|seconds| *seconds > 0.0)
+            .map(Some)
+            .ok_or_else(|| {
+                ApiError::bad_request(format!(
+                    "{} must be a number of seconds greater than 0",
+                    key
+                ))
+            }),
+    }
+}
+
+async fn status(state: &Arc<DaemonState>) -> ApiResult {
+    Ok((StatusCode::OK, status_json(state).await?))
+}
+
+// Daemon status and every site, as GET /api/status returns them
+pub async fn status_json(state: &DaemonState) -> Result<Value> {
+    let mut daemon = state.status().await;
+    if let Some(daemon) = daemon.as_object_mut() {
+        daemon.remove("ok");
+    }
+
+    // Also streamed to the dashboard, so the database is read off the runtime's threads
+    let sites = tokio::task::spawn_blocking(|| -> Result<Vec<Value>> {
+        let now = unix_now();
+        Ok(get_all_sites()?
+            .iter()
+            .map(|site| record(site, now))
+            .collect())
+    })
+    .await??;
+    Ok(json!({ "daemon": daemon, "sites": sites }))
+}
+
+// Mirrors `isup add`
+fn add(state: &DaemonState, body: Value) -> ApiResult {
+    let url = body
+        .get("url")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| ApiError::bad_request("missing url"))?;
+    let url = normalize_url(url);
+    probe::validate(&url).map_err(|e| ApiError::bad_request(format!("{:#}", e)))?;
+
+    let interval = seconds(&body, "interval")?.unwrap_or(DEFAULT_INTERVAL);
+    let timeout = seconds(&body, "timeout")?;
+    validate_settings(interval, timeout).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    if get_site_by_url(&url)?.is_some() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("{} is already being monitored", url),
+        ));
+    }
+
+    let site = Site {
+        url: url.clone(),
+        interval,
+        notify_method: "device".to_string(),
+        timeout,
+        ..Default::default()
+    };
+    let id = add_site_to_db(&site)?;
+    state.request_resync();
+
+    let site =
+        get_site_by_id(id)?.ok_or_else(|| ApiError::not_found(format!("{} was not added", url)))?;
+    Ok((StatusCode::CREATED, record(&site, unix_now())))
+}
+
+fn update(state: &DaemonState, site: Site, body: Value) -> ApiResult {
+    let interval = seconds(&body, "interval")?.unwrap_or(site.interval);
+    // An explicit null goes back to the timeout from the config
+    let timeout = if body.get("timeout").is_some() {
+        seconds(&body, "timeout")?
+    } else {
+        site.timeout
+    };
+    validate_settings(interval, timeout).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let paused = match body.get("paused") {
+        None => None,
+        Some(paused) => Some(
+            paused
+                .as_bool()
+                .ok_or_else(|| ApiError::bad_request("paused must be true or false"))?,
+        ),
+    };
+
+    update_site_settings(&site.url, interval, timeout)?;
+    if let Some(paused) = paused {
+        set_site_paused(&site.url, paused)?;
+    }
+    state.request_resync();
+
+    let site = get_site_by_url(&site.url)?
+        .ok_or_else(|| ApiError::not_found(format!("{} was removed", site.url)))?;
+    Ok((StatusCode::OK, record(&site, unix_now())))
+}
+
+fn query_pairs(query: &str) -> std::result::Result<Vec<(String, String)>, ApiError> {
+    let query = reqwest::Url::parse(&format!("http://localhost/?{}", query))
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    Ok(query.query_pairs().into_owned().collect())
+}
+
+fn history(site: &Site, query: &str) -> ApiResult {
+    let mut since = Duration::from_secs(24 * 3600);
+    let mut limit = 100;
+
+    for (key, value) in query_pairs(query)? {
+        match key.as_str() {
+            "since" => since = parse_duration(&value).map_err(ApiError::bad_request)?,
+            "limit" => {
+                limit = value
+                    .parse::<usize>()
+                    .map_err(|_| ApiError::bad_request(format!("invalid limit '{}'", value)))?
+                    .min(MAX_HISTORY);
+            }
+            _ => {}
+        }
+    }
+
+    let since = unix_now() - since.as_secs() as i64;
+    let records: Vec<Value> = get_site_history(site.id.unwrap_or_default(), since, limit)?
+        .iter()
+        .map(|check| {
+            json!({
+                "checked_at": check.checked_at,
+                "is_up": check.is_up,
+                "status_code": check.status_code,
+                "latency_ms": check.latency_ms,
+                "status": check.status,
+            })
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        json!({ "id": site.id, "url": site.url, "checks": records }),
+    ))
+}
+
+fn incidents(site: &Site, query: &str) -> ApiResult {
+    let mut since = Duration::from_secs(30 * 86400);
+    for (key, value) in query_pairs(query)? {
+        if key == "since" {
+            since = parse_duration(&value).map_err(ApiError::bad_request)?;
+        }
+    }
+
+    let now = unix_now();
+    let records: Vec<Value> =
+        get_incidents(site.id.unwrap_or_default(), now - since.as_secs() as i64)?
+            .iter()
+            .map(|incident| incident.to_json(now))
+            .collect();
+
+    Ok((
+        StatusCode::OK,
+        json!({ "id": site.id, "url": site.url, "incidents": records }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::dashboard;
+
+    fn request(method: Method, path: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header("host", "127.0.0.1")
+            .header("x-isup-dashboard", "1")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn error(response: Response<Body>) -> (StatusCode, String) {
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        (
+            status,
+            body["error"].as_str().unwrap_or_default().to_string(),
+        )
+    }
+
+    const OUT_OF_RANGE: &[(&str, &str)] = &[
+        (r#"{"interval": 1e20}"#, "interval"),
+        (r#"{"interval": 1.7e308}"#, "interval"),
+        (r#"{"interval": 0.01}"#, "interval"),
+        (r#"{"interval": 0}"#, "interval"),
+        (r#"{"interval": -5}"#, "interval"),
+        (r#"{"interval": "60"}"#, "interval"),
+        (r#"{"timeout": 1e20}"#, "timeout"),
+        (r#"{"timeout": 0}"#, "timeout"),
+        (r#"{"interval": 60, "timeout": 3601}"#, "timeout"),
+    ];
+
+    // Both the API and the dashboard, which serves the same handlers, refuse
+    // settings the scheduler can't turn into a Duration, before anything is stored
+    #[tokio::test]
+    async fn out_of_range_settings_are_rejected_on_every_surface() {
+        let state = DaemonState::for_tests();
+        for (settings, field) in OUT_OF_RANGE {
+            let mut body: Value = serde_json::from_str(settings).unwrap();
+            body["url"] = json!("https://example.com");
+
+            let api =
+                error(respond(&state, request(Method::POST, "/api/sites", body.clone())).await)
+                    .await;
+            let dash = error(
+                dashboard::handle(
+                    state.clone(),
+                    request(Method::POST, "/api/sites", body.clone()),
+                )
+                .await,
+            )
+            .await;
+            for (status, message) in [api, dash] {
+                assert_eq!(status, StatusCode::BAD_REQUEST, "{}", settings);
+                assert!(message.starts_with(field), "{}: {}", settings, message);
+            }
+
+            let site = Site {
+                url: "https://example.com".to_string(),
+                interval: 60.0,
+                ..Default::default()
+            };
+            let Err(ApiError(status, message)) = update(&state, site, body) else {
+                panic!("{} was accepted", settings);
+            };
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", settings);
+            assert!(message.starts_with(field), "{}: {}", settings, message);
+        }
+    }
+
+    #[test]
+    fn settings_within_the_limits_are_accepted() {
+        use crate::monitor::db::{MAX_INTERVAL, MAX_TIMEOUT, MIN_INTERVAL};
+
+        for (interval, timeout) in [
+            (MIN_INTERVAL, None),
+            (MAX_INTERVAL, Some(MAX_TIMEOUT)),
+            (16.9, Some(0.5)),
+        ] {
+            assert!(
+                validate_settings(interval, timeout).is_ok(),
+                "{} {:?}",
+                interval,
+                timeout
+            );
+        }
+        assert!(validate_settings(f64::NAN, None).is_err());
+        assert!(validate_settings(60.0, Some(f64::INFINITY)).is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_and_bad_ids_are_client_errors() {
+        let state = DaemonState::for_tests();
+        let cases = [
+            (Method::GET, "/api/nope", StatusCode::NOT_FOUND),
+            (Method::PUT, "/api/sites", StatusCode::NOT_FOUND),
+            (Method::GET, "/api/sites/abc", StatusCode::BAD_REQUEST),
+            (Method::DELETE, "/api/sites/-", StatusCode::BAD_REQUEST),
+        ];
+        for (method, path, expected) in cases {
+            let Err(ApiError(status, _)) = route(&state, request(method, path, json!({}))).await
+            else {
+                panic!("{} was answered", path);
+            };
+            assert_eq!(status, expected, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn pings_are_recorded_against_the_monitor_with_that_token() {
+        let state = DaemonState::for_tests();
+        let url = "push://api-ping-test";
+        let token = random_token().unwrap();
+        let _ = remove_site_from_db(url);
+        add_site_to_db(&Site {
+            url: url.to_string(),
+            interval: 3600.0,
+            notify_method: "device".to_string(),
+            push_token: Some(token.clone()),
+            grace: Some(60.0),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let response = handle(
+            state.clone(),
+            request(Method::GET, &format!("/ping/{}/", token), json!({})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["name"], "api-ping-test");
+        assert!(get_site_by_url(url).unwrap().unwrap().last_ping.is_some());
+
+        // Pings need no API token, so unknown tokens must not reveal anything
+        for path in ["/ping/not-a-token", "/ping/", "/ping//"] {
+            let (status, message) =
+                error(handle(state.clone(), request(Method::POST, path, json!({}))).await).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", path);
+            assert_eq!(message, "no push monitor with that token");
+        }
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(token_matches("abc123", "abc123"));
+        assert!(!token_matches("abc124", "abc123"));
+        assert!(!token_matches("abc12", "abc123"));
+        assert!(!token_matches("", ""));
+    }
+}
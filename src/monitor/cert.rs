@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// When the certificate a site presents expires. The handshake accepts
+// invalid and expired certificates, since those are exactly the ones worth
+// reporting.
+pub async fn fetch_expiry(url: &str, timeout: Duration) -> Result<DateTime<Utc>> {
+    let url = reqwest::Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("{} has no host", url))?
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    tokio::task::spawn_blocking(move || {
+        let addr = (host.as_str(), port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("could not resolve {}", host))?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .build()?;
+        let tls = connector
+            .connect(&host, stream)
+            .map_err(|e| anyhow!("TLS handshake with {} failed: {}", host, e))?;
+        let certificate = tls
+            .peer_certificate()?
+            .ok_or_else(|| anyhow!("{} sent no certificate", host))?;
+
+        not_after(&certificate.to_der()?)
+    })
+    .await?
+}
+
+// The notAfter time of a DER-encoded X.509 certificate:
+//
+//     Certificate ::= SEQUENCE { tbsCertificate, ... }
+//     TBSCertificate ::= SEQUENCE { [0] version OPTIONAL, serialNumber, signature,
+//                                   issuer, validity, ... }
+//     Validity ::= SEQUENCE { notBefore Time, notAfter Time }
+fn not_after(der: &[u8]) -> Result<DateTime<Utc>> {
+    let (_, certificate, _) = read_tlv(der)?;
+    let (_, tbs, _) = read_tlv(certificate)?;
+
+    let mut rest = tbs;
+    let (tag, _, after) = read_tlv(rest)?;
+    // Skip the explicit version tag if present
+    if tag == 0xa0 {
+        rest = after;
+    }
+    // serialNumber, signature and issuer
+    for _ in 0..3 {
+        rest = read_tlv(rest)?.2;
+    }
+
+    let (_, validity, _) = read_tlv(rest)?;
+    let (_, _, validity) = read_tlv(validity)?;
+    let (tag, time, _) = read_tlv(validity)?;
+    let time = std::str::from_utf8(time).context("certificate time is not text")?;
+
+    let parsed = match tag {
+        // UTCTime: YYMMDDHHMMSSZ, with years 50-99 in the 1900s
+        0x17 => {
+            let year: i32 = time
+                .get(..2)
+                .and_then(|year| year.parse().ok())
+                .ok_or_else(|| anyhow!("bad UTCTime"))?;
+            let century = if year >= 50 { "19" } else { "20" };
+            NaiveDateTime::parse_from_str(&format!("{}{}", century, time), "%Y%m%d%H%M%SZ")
+        }
+        // GeneralizedTime: YYYYMMDDHHMMSSZ
+        0x18 => NaiveDateTime::parse_from_str(time, "%Y%m%d%H%M%SZ"),
+        other => return Err(anyhow!("unexpected certificate time tag {:#x}", other)),
+    }
+    .context("could not parse the certificate's expiry time")?;
+
+    Ok(parsed.and_utc())
+}
+
+// Split one DER element into its tag, its contents and the bytes after it
+fn read_tlv(input: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let malformed = || anyhow!("malformed certificate");
+
+    let (&tag, rest) = input.split_first().ok_or_else(malformed)?;
+    let (&first, mut rest) = rest.split_first().ok_or_else(malformed)?;
+
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        // Long form: the low bits say how many length bytes follow
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(malformed());
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+        rest = &rest[count..];
+        len
+    };
+
+    if rest.len() < len {
+        return Err(malformed());
+    }
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Self-signed P-256 certificates for CN=isup.test. The first expires
+    // 2030-12-31 23:59:59 (a UTCTime), the second 2051-06-30 12:00:00, which
+    // X.509 writes as a GeneralizedTime.
+    const CERT: &[u8] = include_bytes!("testdata/cert.der");
+    const CERT_2051: &[u8] = include_bytes!("testdata/cert-2051.der");
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn reads_not_after_from_real_certificates() {
+        assert_eq!(not_after(CERT).unwrap(), utc("2030-12-31T23:59:59Z"));
+        assert_eq!(not_after(CERT_2051).unwrap(), utc("2051-06-30T12:00:00Z"));
+    }
+
+    #[test]
+    fn truncated_certificates_are_errors() {
+        for len in 0..CERT.len() {
+            assert!(not_after(&CERT[..len]).is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn corrupted_certificates_never_panic() {
+        for at in 0..CERT.len() {
+            for byte in [0x00, 0x7f, 0x80, 0x81, 0x84, 0x85, 0xff] {
+                let mut der = CERT.to_vec();
+                der[at] = byte;
+                let _ = not_after(&der);
+            }
+        }
+    }
+
+    #[test]
+    fn long_form_lengths() {
+        // One and two length bytes
+        assert_eq!(
+            read_tlv(&[0x04, 0x81, 0x02, 1, 2, 3]).unwrap(),
+            (0x04, &[1, 2][..], &[3][..])
+        );
+        let mut long = vec![0x04, 0x82, 0x01, 0x00];
+        long.extend([7; 256]);
+        let (_, contents, rest) = read_tlv(&long).unwrap();
+        assert_eq!((contents.len(), rest.len()), (256, 0));
+
+        // Indefinite length, more than four length bytes, missing length
+        // bytes and lengths past the end of the input
+        assert!(read_tlv(&[0x30, 0x80, 0x00, 0x00]).is_err());
+        assert!(read_tlv(&[0x30, 0x85, 0, 0, 0, 0, 1, 0]).is_err());
+        assert!(read_tlv(&[0x30, 0x82, 0x01]).is_err());
+        assert!(read_tlv(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff, 0]).is_err());
+        assert!(read_tlv(&[0x30, 0x03, 1, 2]).is_err());
+        assert!(read_tlv(&[0x30]).is_err());
+        assert!(read_tlv(&[]).is_err());
+    }
+
+    #[test]
+    fn unexpected_time_values_are_errors() {
+        // Validity ::= SEQUENCE { notBefore, notAfter } wrapped in just enough of
+        // a certificate: no version, then serial, signature and issuer
+        fn certificate(time: &[u8]) -> Vec<u8> {
+            let mut validity = vec![0x17, 0x0d];
+            validity.extend(b"250101000000Z");
+            validity.extend(time);
+            let mut tbs = vec![
+                0x02,
+                0x01,
+                0x01,
+                0x30,
+                0x00,
+                0x30,
+                0x00,
+                0x30,
+                validity.len() as u8,
+            ];
+            tbs.extend(validity);
+            let mut der = vec![0x30, tbs.len() as u8 + 2, 0x30, tbs.len() as u8];
+            der.extend(tbs);
+            der
+        }
+
+        let mut good = vec![0x17, 0x0d];
+        good.extend(b"491231235959Z");
+        assert_eq!(
+            not_after(&certificate(&good)).unwrap(),
+            utc("2049-12-31T23:59:59Z")
+        );
+
+        assert!(not_after(&certificate(&[0x17, 0x02, b'9', b'9'])).is_err());
+        assert!(not_after(&certificate(&[0x17, 0x01, b'9'])).is_err());
+        assert!(not_after(&certificate(&[0x17, 0x02, 0xff, 0xfe])).is_err());
+        assert!(not_after(&certificate(&[0x18, 0x00])).is_err());
+        assert!(not_after(&certificate(&[0x04, 0x00])).is_err());
+    }
+}
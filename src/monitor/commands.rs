@@ -1,50 +1,67 @@
-use anyhow:: Result;
+use anyhow::Result;
 use colored::*;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use terminal_size::terminal_size;
 
-use crate::monitor::db::{add_site_to_db, get_all_sites, get_site_by_url, remove_site_from_db, Site};
-use crate::monitor::service::{start_background_service, is_daemon_running};
+use crate::output::{emit_record, emit_records, is_structured};
 
-pub fn  add_site(
-    url: &str,
-    interval: f64,
-    notify: &str
-) -> Result<()> {
+use crate::check::{normalize_url, EXIT_DOWN, EXIT_OK};
+use crate::monitor::api::random_token;
+use crate::monitor::config::{self, LogLevel};
+use crate::monitor::control::{expect_ok, is_unreachable, notify_sites_changed, send_command};
+use crate::monitor::db::{
+    add_site_to_db, get_all_sites, get_site_by_url, remove_site_from_db, Site,
+};
+use crate::monitor::db::{record_ping, set_site_paused, validate_settings, PUSH_SCHEME};
+use crate::monitor::log::{parse_line, LogEntry, LogFile};
+use crate::monitor::probe::{self, CMD_SCHEME};
+use crate::monitor::service::{
+    build_client, check_and_record, is_daemon_running, start_background_service, SiteCheck,
+};
+use crate::monitor::statuspage;
+use crate::output::OutputFormat;
+use crate::utils::format_duration;
+
+pub fn add_site(url: &str, interval: f64, notify: &str, timeout: Option<f64>) -> Result<()> {
     // Ensure the URL has a proper scheme; if missing, default to "https://".
-    let formatted_url = if !url.starts_with("http://") && !url.starts_with("https://") {
-        format!("https://{}", url)
-    } else {
-        url.to_string()
-    };
+    let formatted_url = normalize_url(url);
+    probe::validate(&formatted_url)?;
+
+    validate_settings(interval, timeout)?;
 
     // Check if the site is already being monitored.
     if get_site_by_url(&formatted_url)?.is_some() {
-        println!("{} is already being monitored", formatted_url.cyan());
+        say!("{} is already being monitored", formatted_url.cyan());
         return Ok(());
     }
 
     // Validate the notification method; currently only "device" notifications are supported.
     if notify != "device" {
-        println!("Note: Currently only device notifications are supported.");
+        say!("Note: Currently only device notifications are supported.");
     }
 
     // Construct the new site record.
     let site = Site {
-        id: None,
         url: formatted_url.clone(),
         interval,
         notify_method: "device".to_string(), // Always enforce device notifications.
-        is_up: None,
-        last_checked: None,
-        last_status: None,
-        downtime_started: None,
+        timeout,
+        ..Default::default()
     };
 
     // Add the new site to the database.
-    add_site_to_db(&site)?;
+    let id = add_site_to_db(&site)?;
+    notify_sites_changed();
+
+    if is_structured() {
+        emit_record(
+            json!({ "id": id, "url": formatted_url, "interval": interval, "action": "added" }),
+            &["id", "url", "interval", "action"],
+        );
+    }
 
-    println!(
+    say!(
         "{} {} to monitoring with {} second interval",
         "added".green().bold(),
         formatted_url.cyan(),
@@ -57,133 +74,435 @@ pub fn  add_site(
     Ok(())
 }
 
+// Push monitors are named push://<name> and command monitors cmd://<name>;
+// names go in URLs, so keep them plain
+fn monitor_url(scheme: &str, kind: &str, name: &str) -> Result<String> {
+    let name = name.strip_prefix(scheme).unwrap_or(name);
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(anyhow::anyhow!(
+            "invalid {} name '{}', use letters, digits, '-', '_' and '.'",
+            kind,
+            name
+        ));
+    }
+    Ok(format!("{}{}", scheme, name))
+}
+
+fn push_url(name: &str) -> Result<String> {
+    monitor_url(PUSH_SCHEME, "push monitor", name)
+}
+
+// Add a push monitor, which goes down when it isn't pinged every `every` plus `grace`
+pub fn add_push_monitor(name: &str, every: Duration, grace: Duration) -> Result<()> {
+    let url = push_url(name)?;
+    if every.is_zero() {
+        return Err(anyhow::anyhow!("--expect-every must be greater than 0"));
+    }
+    validate_settings(every.as_secs_f64(), None)?;
+
+    if get_site_by_url(&url)?.is_some() {
+        say!("{} is already being monitored", url.cyan());
+        return Ok(());
+    }
+
+    let token = random_token()?;
+    let site = Site {
+        url: url.clone(),
+        interval: every.as_secs_f64(),
+        notify_method: "device".to_string(),
+        push_token: Some(token.clone()),
+        grace: Some(grace.as_secs_f64()),
+        ..Default::default()
+    };
+    let id = add_site_to_db(&site)?;
+    notify_sites_changed();
+
+    let ping_url = config::current()
+        .api_addr
+        .map(|addr| format!("http://{}/ping/{}", addr, token));
+    if is_structured() {
+        emit_record(
+            json!({
+                "id": id,
+                "url": url,
+                "expect_every": every.as_secs_f64(),
+                "grace": grace.as_secs_f64(),
+                "token": token,
+                "ping_url": ping_url,
+                "action": "added",
+            }),
+            &[
+                "id",
+                "url",
+                "expect_every",
+                "grace",
+                "token",
+                "ping_url",
+                "action",
+            ],
+        );
+    }
+
+    say!(
+        "{} {} expecting a ping every {} (grace {})",
+        "added".green().bold(),
+        url.cyan(),
+        format_duration(every.as_secs() as i64).yellow(),
+        format_duration(grace.as_secs() as i64)
+    );
+    say!(
+        "  ping it with: isup ping {}",
+        name.trim_start_matches(PUSH_SCHEME)
+    );
+    match &ping_url {
+        Some(ping_url) => say!("  or over HTTP: curl -fsS -m 10 {}", ping_url),
+        None => say!(
+            "  or over HTTP at /ping/{} once api_addr is set in ~/.isup/config",
+            token
+        ),
+    }
+
+    ensure_monitoring_service_running()?;
+    Ok(())
+}
+
+// Record a ping for a push monitor, e.g. at the end of a cron job
+pub fn ping_push_monitor(name: &str) -> Result<()> {
+    let url = push_url(name)?;
+    let site =
+        get_site_by_url(&url)?.ok_or_else(|| anyhow::anyhow!("{} is not being monitored", url))?;
+    record_ping(&url)?;
+
+    // A down monitor recovers right away instead of at its next check
+    if site.is_up != Some(true) && !site.paused {
+        let _ = send_command(json!({ "cmd": "check", "url": url }));
+    }
+
+    say!("{} pinged {}", "✓".green(), url.cyan());
+    if is_structured() {
+        emit_record(json!({ "url": url, "pinged": true }), &["url", "pinged"]);
+    }
+    Ok(())
+}
+
+// Add a command monitor, which runs `command` with the shell every `interval`
+// and takes its health from the exit code
+pub fn add_command_monitor(
+    command: &str,
+    name: Option<&str>,
+    env: &[String],
+    interval: f64,
+    timeout: Option<f64>,
+) -> Result<()> {
+    if command.trim().is_empty() {
+        return Err(anyhow::anyhow!("--command must not be empty"));
+    }
+    validate_settings(interval, timeout)?;
+
+    // By default the monitor is named after the program, e.g. check_disk for
+    // "/usr/lib/nagios/plugins/check_disk -w 20%"
+    let program = command.split_whitespace().next().unwrap_or_default();
+    let default_name = program.rsplit('/').next().unwrap_or(program);
+    let url = monitor_url(CMD_SCHEME, "command monitor", name.unwrap_or(default_name))?;
+
+    let mut variables = serde_json::Map::new();
+    for pair in env {
+        let (key, value) = pair
+            .split_once('=')
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("invalid --env '{}', expected KEY=VALUE", pair))?;
+        variables.insert(key.to_string(), Value::String(value.to_string()));
+    }
+
+    if get_site_by_url(&url)?.is_some() {
+        say!("{} is already being monitored", url.cyan());
+        return Ok(());
+    }
+
+    let site = Site {
+        url: url.clone(),
+        interval,
+        notify_method: "device".to_string(),
+        timeout,
+        command: Some(command.to_string()),
+        env: (!variables.is_empty()).then(|| Value::Object(variables).to_string()),
+        ..Default::default()
+    };
+    let id = add_site_to_db(&site)?;
+    notify_sites_changed();
+
+    if is_structured() {
+        emit_record(
+            json!({ "id": id, "url": url, "command": command, "interval": interval, "action": "added" }),
+            &["id", "url", "command", "interval", "action"],
+        );
+    }
+
+    say!(
+        "{} {} running `{}` every {} seconds",
+        "added".green().bold(),
+        url.cyan(),
+        command,
+        interval.to_string().yellow()
+    );
+
+    ensure_monitoring_service_running()?;
+    Ok(())
+}
+
+// Field order for structured site records
+const SITE_COLUMNS: &[&str] = &[
+    "url",
+    "state",
+    "status_code",
+    "latency_ms",
+    "last_status",
+    "last_checked",
+    "downtime_started",
+    "downtime_seconds",
+    "interval",
+    "timeout",
+    "paused",
+];
+
+// Machine-readable view of a monitored site
+pub fn site_record(site: &Site, now: i64) -> Value {
+    let downtime_seconds = match (site.is_up, site.downtime_started) {
+        (Some(false), Some(start)) => Some(now - start),
+        _ => None,
+    };
+
+    json!({
+        "url": site.url,
+        "state": site.state(),
+        "status_code": site.last_status_code,
+        "latency_ms": site.last_latency_ms,
+        "last_status": site.last_status,
+        "last_checked": site.last_checked,
+        "downtime_started": site.downtime_started,
+        "downtime_seconds": downtime_seconds,
+        "interval": site.interval,
+        "timeout": site.timeout_secs(),
+        "paused": site.paused,
+        "last_ping": site.last_ping,
+    })
+}
+
+fn emit_sites(sites: &[Site]) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let records: Vec<Value> = sites.iter().map(|site| site_record(site, now)).collect();
+    emit_records(&records, SITE_COLUMNS);
+    Ok(())
+}
+
 pub fn list_sites() -> Result<()> {
     let sites = get_all_sites()?;
-    
+
+    if is_structured() {
+        return emit_sites(&sites);
+    }
+
     // Get terminal width for responsive layout
     let term_width = terminal_size().map(|(w, _)| w.0 as usize).unwrap_or(80);
-    
+
     if sites.is_empty() {
-        println!("{}", "  no sites are currently being monitored".yellow().italic());
+        say!(
+            "{}",
+            "  no sites are currently being monitored".yellow().italic()
+        );
         return Ok(());
     }
-    
+
     let daemon_running = is_daemon_running();
-    
+
     // Service status header with clean styling
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
     if !daemon_running {
-        println!(" {}  {}", "warning".yellow().bold(), 
-            "monitoring service is not running. no sites are being checked.".yellow());
-        println!(" {}  Run 'isup status' to check service status", "→".yellow());
+        say!(
+            " {}  {}",
+            "warning".yellow().bold(),
+            "monitoring service is not running. no sites are being checked.".yellow()
+        );
+        say!(
+            " {}  Run 'isup status' to check service status",
+            "→".yellow()
+        );
     } else {
-        println!(" {}  {}", "active".green().bold(), 
-            "monitoring service is running normally".green());
+        say!(
+            " {}  {}",
+            "active".green().bold(),
+            "monitoring service is running normally".green()
+        );
     }
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
 
     // Dynamic column widths based on terminal size
     let url_width = (term_width * 50 / 100).min(35);
 
     // Header with clean separators
-    println!(" {:<width$} │ {:<8} │ {:<10}", 
-        "URL".bold(), 
-        "STATUS".bold(), 
+    say!(
+        " {:<width$} │ {:<10} │ {:<10} │ {:<8}",
+        "URL".bold(),
+        "STATUS".bold(),
         "INTERVAL".bold(),
+        "TIMEOUT".bold(),
         width = url_width
     );
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
 
-    for (_i, site) in sites.into_iter().enumerate() {
+    for site in &sites {
         let status = match site.is_up {
+            _ if site.paused => "⏸ PAUSED".blue().bold(),
+            Some(true) if site.degraded => "▲ DEGRADED".yellow().bold(),
             Some(true) => "● UP".green().bold(),
             Some(false) => "● DOWN".red().bold(),
             None => "○ UNKNOWN".yellow().bold(),
         };
-    
-        println!(" {:<width$} │ {:<8} │ {:<10}", 
+
+        say!(
+            " {:<width$} │ {:<10} │ {:<10} │ {:<8}",
             site.url.cyan(),
             status,
             format!("{}s", site.interval),
+            format!("{}s", site.timeout_secs()),
             width = url_width
         );
     }
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
+
+    // A check that can outlast its interval delays the next one
+    for site in sites
+        .iter()
+        .filter(|site| site.push_name().is_none() && site.interval < site.timeout_secs())
+    {
+        say!(
+            " {}  {} checks every {}s but may take up to {}s to time out",
+            "warning".yellow().bold(),
+            site.url.cyan(),
+            site.interval,
+            site.timeout_secs()
+        );
+    }
     Ok(())
 }
 pub fn remove_site(url: &str) -> Result<()> {
     let term_width = terminal_size().map(|(w, _)| w.0 as usize).unwrap_or(80);
-    println!("{}", "─".repeat(term_width.min(80)));
-    
+    say!("{}", "─".repeat(term_width.min(80)));
+
     // Ensure URL has a scheme
-    let url = if !url.starts_with("http://") && !url.starts_with("https://") {
-        format!("https://{}", url)
-    } else {
-        url.to_string()
-    };
-    
+    let url = normalize_url(url);
+
     // Check if the site exists before attempting removal
     if get_site_by_url(&url)?.is_none() {
-        println!("{} is not being monitored", url.cyan());
-        println!("{}", "─".repeat(term_width.min(80)));
+        say!("{} is not being monitored", url.cyan());
+        say!("{}", "─".repeat(term_width.min(80)));
+        if is_structured() {
+            emit_record(json!({ "url": url, "removed": false }), &["url", "removed"]);
+        }
         return Ok(());
     }
-    
+
     // Remove from database
-    if remove_site_from_db(&url)? {
-        println!("{} {} from monitoring", "removed".green().bold(), url.cyan());
-        
+    let removed = remove_site_from_db(&url)?;
+    notify_sites_changed();
+    if is_structured() {
+        emit_record(
+            json!({ "url": url, "removed": removed }),
+            &["url", "removed"],
+        );
+    }
+
+    if removed {
+        say!(
+            "{} {} from monitoring",
+            "removed".green().bold(),
+            url.cyan()
+        );
+
         // Verify the site was actually removed
         if get_site_by_url(&url)?.is_none() {
-            println!("✅ site successfully removed from database.");
+            say!("✅ site successfully removed from database.");
         } else {
-                println!("⚠️ site may still be in the database. please try again.");
+            say!("⚠️ site may still be in the database. please try again.");
         }
     } else {
-        println!("⚠️ failed to remove {} from monitoring", url.cyan());
+        say!("⚠️ failed to remove {} from monitoring", url.cyan());
     }
-    
-    println!("{}", "─".repeat(term_width.min(80)));
+
+    say!("{}", "─".repeat(term_width.min(80)));
     Ok(())
 }
 
 pub fn status_sites() -> Result<()> {
     let sites = get_all_sites()?;
+
+    if is_structured() {
+        return emit_sites(&sites);
+    }
+
     let term_width = terminal_size().map(|(w, _)| w.0 as usize).unwrap_or(80);
-    
+
     if sites.is_empty() {
-        println!("{}", "  no sites are currently being monitored".yellow().italic());
+        say!(
+            "{}",
+            "  no sites are currently being monitored".yellow().italic()
+        );
         return Ok(());
     }
-    
+
     let daemon_running = is_daemon_running();
     let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
 
     // Modern header with status indicator
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
     if !daemon_running {
-        println!(" {}  {}", "⚠ warning".yellow().bold(), 
-            "monitoring service is not running".yellow());
+        say!(
+            " {}  {}",
+            "⚠ warning".yellow().bold(),
+            "monitoring service is not running".yellow()
+        );
     } else {
-        println!(" {}  {}", "✓ active".green().bold(), 
-            "monitoring service is running normally".green());
+        say!(
+            " {}  {}",
+            "✓ active".green().bold(),
+            "monitoring service is running normally".green()
+        );
+
+        // Ask the daemon itself for details when it's reachable
+        if let Ok(daemon) = send_command(json!({ "cmd": "status" })).and_then(expect_ok) {
+            let in_flight = daemon["in_flight"].as_array().map(|a| a.len()).unwrap_or(0);
+            say!(
+                "    pid {} · up {} · {} sites tracked · {} checks in flight",
+                daemon["pid"],
+                format_duration(daemon["uptime_secs"].as_i64().unwrap_or(0)),
+                daemon["tracked_sites"],
+                in_flight
+            );
+        }
     }
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
 
     // Dynamic column widths
     let url_width = (term_width * 40 / 100).min(35);
-    
-    println!(" {:<width$} │ {:<8} │ {:<15} │ {:<10}", 
-        "URL".bold(), 
-        "STATUS".bold(), 
-        "LAST CHECKED".bold(), 
+
+    say!(
+        " {:<width$} │ {:<10} │ {:<15} │ {:<10}",
+        "URL".bold(),
+        "STATUS".bold(),
+        "LAST CHECKED".bold(),
         "DOWNTIME".bold(),
         width = url_width
     );
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
 
-    for (_i, site) in sites.iter().enumerate() {
+    for site in sites.iter() {
         let status = match site.is_up {
+            _ if site.paused => "⏸ PAUSED".blue().bold(),
+            Some(true) if site.degraded => "▲ DEGRADED".yellow().bold(),
             Some(true) => "● UP".green().bold(),
             Some(false) => "● DOWN".red().bold(),
             None => "○ UNKNOWN".yellow().bold(),
@@ -198,10 +517,10 @@ pub fn status_sites() -> Result<()> {
                 } else {
                     format!("{} hours ago", ago / 3600)
                 }
-            },
+            }
             None => "Never".to_string(),
         };
-        
+
         let downtime = match (site.is_up, site.downtime_started) {
             (Some(false), Some(start)) => {
                 let duration = now - start;
@@ -214,41 +533,548 @@ pub fn status_sites() -> Result<()> {
                 } else {
                     format!("{} days", duration / 86400)
                 }
-            },
+            }
             _ => "None".to_string(),
         };
-        
-        println!(" {:<width$} │ {:<8} │ {:<15} │ {:<10}", 
+
+        say!(
+            " {:<width$} │ {:<10} │ {:<15} │ {:<10}",
             site.url.cyan(),
             status,
             last_checked.italic(),
-            if downtime == "None" { downtime } else { downtime.red().to_string() },
+            if downtime == "None" {
+                downtime
+            } else {
+                downtime.red().to_string()
+            },
             width = url_width
         );
     }
-    println!("{}", "─".repeat(term_width.min(80)));
+    say!("{}", "─".repeat(term_width.min(80)));
 
     // Offer to restart service if not running
     if !daemon_running && !sites.is_empty() {
-        println!("would you like to restart the monitoring service? [y/N]");
+        say!("would you like to restart the monitoring service? [y/N]");
         let mut input = String::new();
-        if std::io::stdin().read_line(&mut input).is_ok() {
-            if input.trim().to_lowercase() == "y" {
-                ensure_monitoring_service_running()?;
+        if std::io::stdin().read_line(&mut input).is_ok() && input.trim().to_lowercase() == "y" {
+            ensure_monitoring_service_running()?;
+        }
+    }
+
+    Ok(())
+}
+
+// Pause or resume monitoring of a site, through the daemon when it's running
+pub fn pause_site(url: &str, paused: bool) -> Result<()> {
+    let url = normalize_url(url);
+    let action = if paused { "paused" } else { "resumed" };
+
+    let found =
+        match send_command(json!({ "cmd": if paused { "pause" } else { "resume" }, "url": url })) {
+            Ok(response) => expect_ok(response).is_ok(),
+            // No daemon to tell, so just update the database
+            Err(_) => set_site_paused(&url, paused)?,
+        };
+
+    if found {
+        say!("{} {}", action.green().bold(), url.cyan());
+    } else {
+        say!("{} is not being monitored", url.cyan());
+    }
+
+    if is_structured() {
+        emit_record(
+            json!({ "url": url, "paused": paused, "found": found }),
+            &["url", "paused", "found"],
+        );
+    }
+
+    Ok(())
+}
+
+// Check one site (or all of them) immediately and return the exit code.
+// The daemon runs the check when it's up so its schedule, database and notifications stay in step.
+pub fn check_now(url: Option<&str>, all: bool) -> Result<i32> {
+    let url = url.map(normalize_url);
+    let request = match &url {
+        Some(url) if !all => json!({ "cmd": "check", "url": url }),
+        _ => json!({ "cmd": "check", "all": true }),
+    };
+
+    let checks = match send_command(request) {
+        Ok(response) => {
+            let response = expect_ok(response)?;
+            let results = response["results"].as_array().cloned().unwrap_or_default();
+            for skipped in results
+                .iter()
+                .filter_map(|result| result.get("error").and_then(Value::as_str))
+            {
+                say!("⚠️ {}", skipped);
+            }
+            results.iter().filter_map(SiteCheck::from_json).collect()
+        }
+        // Only check here if no daemon could have run the check too
+        Err(e) if is_unreachable(&e) => {
+            say!(
+                "{} monitoring service is not running, checking locally",
+                "ℹ".blue()
+            );
+            check_locally(url.as_deref(), all)?
+        }
+        Err(e) => return Err(e.context("the monitoring service didn't finish the check")),
+    };
+
+    if is_structured() {
+        let records: Vec<Value> = checks.iter().map(SiteCheck::to_json).collect();
+        emit_records(
+            &records,
+            &[
+                "url",
+                "is_up",
+                "degraded",
+                "status_code",
+                "status",
+                "latency_ms",
+                "state_changed",
+            ],
+        );
+    } else {
+        for check in &checks {
+            let details = format!(
+                "{}{}{}",
+                check
+                    .status_code
+                    .map(|code| format!("{} ", code))
+                    .unwrap_or_default(),
+                check.status_desc,
+                check
+                    .latency_ms
+                    .map(|ms| format!(", {}ms", ms))
+                    .unwrap_or_default()
+            );
+            if check.is_up && check.degraded {
+                say!(
+                    "⚠️ {} is {} ({})",
+                    check.url.cyan(),
+                    "DEGRADED".yellow().bold(),
+                    details
+                );
+            } else if check.is_up {
+                say!(
+                    "✅ {} is {} ({})",
+                    check.url.cyan(),
+                    "UP".green().bold(),
+                    details
+                );
+            } else {
+                say!(
+                    "❌ {} is {} ({})",
+                    check.url.cyan(),
+                    "DOWN".red().bold(),
+                    details
+                );
+            }
+            if check.state_changed {
+                say!("   {} state changed, notification sent", "↳".yellow());
             }
         }
     }
-    
+
+    Ok(if checks.iter().all(|check| check.is_up) {
+        EXIT_OK
+    } else {
+        EXIT_DOWN
+    })
+}
+
+// Same pipeline as the daemon, for when it isn't running
+fn check_locally(url: Option<&str>, all: bool) -> Result<Vec<SiteCheck>> {
+    let sites: Vec<Site> = match url {
+        Some(url) if !all => match get_site_by_url(url)? {
+            Some(site) => vec![site],
+            None => return Err(anyhow::anyhow!("{} is not being monitored", url)),
+        },
+        _ => get_all_sites()?
+            .into_iter()
+            .filter(|site| !site.paused)
+            .collect(),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let client = build_client(&config::current())?;
+
+    Ok(runtime.block_on(async {
+        let mut checks = Vec::new();
+        for site in &sites {
+            checks.push(check_and_record(&client, &site.url).await);
+        }
+        checks
+    }))
+}
+
+// Ask the running daemon to re-read its sites and ~/.isup/config
+pub fn reload_daemon() -> Result<()> {
+    match send_command(json!({ "cmd": "reload" })) {
+        Ok(response) => {
+            let response = expect_ok(response)?;
+            say!("{} monitoring service reloaded", "✓".green());
+
+            // Settings from ~/.isup/config that the daemon picked up
+            let changes: Vec<&str> = response
+                .get("changes")
+                .and_then(Value::as_array)
+                .map(|changes| changes.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            if changes.is_empty() {
+                say!("  config unchanged");
+            }
+            for change in changes {
+                say!("  {}", change);
+            }
+        }
+        Err(_) => say!("⚠️ monitoring service is not running"),
+    }
+    Ok(())
+}
+
+// Render the status page into `out`
+pub fn build_statuspage(out: &std::path::Path, title: Option<&str>) -> Result<()> {
+    let title = title
+        .map(str::to_string)
+        .unwrap_or_else(|| config::current().statuspage_title.clone());
+    let sites = statuspage::build(out, &title)?;
+
+    say!(
+        "{} status page for {} site{} written to {}",
+        "✓".green(),
+        sites,
+        if sites == 1 { "" } else { "s" },
+        out.display()
+    );
+    if is_structured() {
+        emit_record(
+            json!({ "out": out.display().to_string(), "sites": sites, "title": title }),
+            &["out", "sites", "title"],
+        );
+    }
     Ok(())
 }
 
+pub struct LogsOptions {
+    pub kind: LogFile,
+    pub site: Option<String>,
+    pub since: Option<std::time::Duration>,
+    // Most verbose level shown; None shows every level
+    pub level: Option<LogLevel>,
+    // How many of the latest entries to show; None shows all
+    pub lines: Option<usize>,
+    pub follow: bool,
+}
+
+// Print the daemon's check log or the notification log, oldest entry first,
+// then keep printing new entries with --follow
+pub fn show_logs(options: &LogsOptions) -> Result<()> {
+    if options.follow
+        && matches!(
+            crate::output::format(),
+            OutputFormat::Json | OutputFormat::Csv
+        )
+    {
+        return Err(anyhow::anyhow!("--follow needs text or ndjson output"));
+    }
+
+    let filter = LogFilter {
+        site: options.site.as_deref().map(normalize_url),
+        cutoff: options
+            .since
+            .and_then(|since| chrono::Duration::from_std(since).ok())
+            .map(|since| chrono::Local::now().fixed_offset() - since),
+        level: options.level,
+    };
+
+    let path = options.kind.path();
+    let paths = options.kind.existing_paths();
+    if paths.is_empty() && !options.follow {
+        say!("no logs yet at {}", path.display());
+        return Ok(());
+    }
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    for file in &paths {
+        let (read, end) = read_log_from(file, 0)?;
+        entries.extend(read.into_iter().filter(|entry| filter.matches(entry)));
+        if *file == path {
+            offset = end;
+        }
+    }
+    if let Some(lines) = options.lines {
+        entries.drain(..entries.len().saturating_sub(lines));
+    }
+    print_log_entries(&entries);
+
+    if !options.follow {
+        return Ok(());
+    }
+
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let (mut new, end) = read_appended(&path, &options.kind.rotated_path(1), offset)?;
+        offset = end;
+        new.retain(|entry| filter.matches(entry));
+        print_log_entries(&new);
+    }
+}
+
+// What `isup logs` shows: entries about one site, since a point in time, up to a level
+struct LogFilter {
+    site: Option<String>,
+    cutoff: Option<chrono::DateTime<chrono::FixedOffset>>,
+    level: Option<LogLevel>,
+}
+
+impl LogFilter {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.cutoff.is_none_or(|cutoff| entry.timestamp >= cutoff)
+            && self.level.is_none_or(|level| entry.level <= level)
+            && self
+                .site
+                .as_deref()
+                .is_none_or(|site| entry.url().is_some_and(|url| same_url(url, site)))
+    }
+}
+
+// Entries added to a followed log since `offset`, and the offset to read from next.
+// When the log was rotated meanwhile, the rest of the old file comes first.
+fn read_appended(
+    path: &std::path::Path,
+    rotated: &std::path::Path,
+    offset: u64,
+) -> Result<(Vec<LogEntry>, u64)> {
+    let len = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    let mut new = Vec::new();
+    let mut offset = offset;
+    if len < offset {
+        if let Ok((read, _)) = read_log_from(rotated, offset) {
+            new.extend(read);
+        }
+        offset = 0;
+    }
+    if len > offset {
+        let (read, end) = read_log_from(path, offset)?;
+        new.extend(read);
+        offset = end;
+    }
+    Ok((new, offset))
+}
+
+// Parse the complete lines of a log file from a byte offset on, returning the
+// entries and the offset just past the last complete line
+fn read_log_from(path: &std::path::Path, offset: u64) -> Result<(Vec<LogEntry>, u64)> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e.into()),
+    };
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    // A line still being written is picked up on the next read
+    let complete = bytes
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |end| end + 1);
+    let entries = String::from_utf8_lossy(&bytes[..complete])
+        .lines()
+        .filter_map(parse_line)
+        .collect();
+
+    Ok((entries, offset + complete as u64))
+}
+
+// Match a logged URL against the one given to --site, ignoring the scheme and a trailing slash
+fn same_url(a: &str, b: &str) -> bool {
+    let bare = |url: &str| {
+        let url = url.trim_end_matches('/');
+        url.split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .to_string()
+    };
+    bare(a) == bare(b)
+}
+
+fn print_log_entries(entries: &[LogEntry]) {
+    if is_structured() {
+        if !entries.is_empty() {
+            let records: Vec<Value> = entries.iter().map(LogEntry::to_json).collect();
+            emit_records(&records, &["ts", "level", "event", "url"]);
+        }
+        return;
+    }
+
+    for entry in entries {
+        let time = entry
+            .timestamp
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        let level = match entry.level {
+            LogLevel::Error => "ERROR".red().bold(),
+            LogLevel::Warn => "WARN ".yellow().bold(),
+            LogLevel::Info => "INFO ".green(),
+            LogLevel::Debug => "DEBUG".dimmed(),
+        };
+
+        let mut line = format!(
+            "{} {} {}",
+            time.to_string().dimmed(),
+            level,
+            entry.event.bold()
+        );
+        if let Some(url) = entry.url() {
+            line.push_str(&format!(" {}", url.cyan()));
+        }
+        for (key, value) in entry.fields.iter().filter(|(key, _)| key.as_str() != "url") {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            line.push_str(&format!(" {}{}", format!("{}=", key).dimmed(), value));
+        }
+        say!("{}", line);
+    }
+}
+
 // Helper function to ensure the service is running
 fn ensure_monitoring_service_running() -> Result<()> {
     if !is_daemon_running() {
-        println!("{} monitoring service is not running. starting it now...", "ℹ".blue());
+        say!(
+            "{} monitoring service is not running. starting it now...",
+            "ℹ".blue()
+        );
         start_background_service()?;
     } else {
-        println!("{} monitoring service is already running", "✓".green());
+        say!("{} monitoring service is already running", "✓".green());
     }
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry {
+        parse_line(line).unwrap()
+    }
+
+    #[test]
+    fn command_monitors_reject_malformed_env_before_touching_the_database() {
+        for env in ["NOEQUALS", "=value"] {
+            let err = add_command_monitor("true", Some("t"), &[env.to_string()], 60.0, None)
+                .unwrap_err()
+                .to_string();
+            assert_eq!(err, format!("invalid --env '{}', expected KEY=VALUE", env));
+        }
+        assert!(add_command_monitor("true", Some("t"), &[], 60.0, Some(0.0)).is_err());
+        assert!(add_command_monitor("  ", None, &[], 60.0, None).is_err());
+    }
+
+    #[test]
+    fn logged_urls_match_regardless_of_scheme_and_trailing_slash() {
+        assert!(same_url("https://example.com/", "http://example.com"));
+        assert!(same_url("https://example.com/a", "https://example.com/a/"));
+        assert!(!same_url("https://example.com/a", "https://example.com/b"));
+        assert!(!same_url("https://example.com", "https://example.org"));
+    }
+
+    #[test]
+    fn filters_combine_site_level_and_cutoff() {
+        let up = entry("2026-10-18T09:00:00+00:00 INFO check_result url=https://a.test up=true");
+        let down =
+            entry("2026-10-18T10:00:00+00:00 WARN check_result url=https://a.test/ up=false");
+        let other = entry("2026-10-18T10:00:00+00:00 ERROR record_failed url=https://b.test");
+        let no_url = entry("2026-10-18T10:00:00+00:00 INFO daemon_started pid=1");
+
+        let everything = LogFilter {
+            site: None,
+            cutoff: None,
+            level: None,
+        };
+        assert!([&up, &down, &other, &no_url]
+            .iter()
+            .all(|entry| everything.matches(entry)));
+
+        let site = LogFilter {
+            site: Some(normalize_url("a.test")),
+            ..everything
+        };
+        assert!(site.matches(&up) && site.matches(&down));
+        assert!(!site.matches(&other) && !site.matches(&no_url));
+
+        let warnings = LogFilter {
+            site: None,
+            cutoff: None,
+            level: Some(LogLevel::Warn),
+        };
+        assert!(!warnings.matches(&up) && !warnings.matches(&no_url));
+        assert!(warnings.matches(&down) && warnings.matches(&other));
+
+        let recent = LogFilter {
+            site: None,
+            cutoff: Some(
+                chrono::DateTime::parse_from_rfc3339("2026-10-18T09:30:00+00:00").unwrap(),
+            ),
+            level: None,
+        };
+        assert!(!recent.matches(&up) && recent.matches(&down));
+    }
+
+    #[test]
+    fn following_picks_up_the_end_of_a_rotated_log() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("isup-logs-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (path, rotated) = (dir.join("daemon.log"), dir.join("daemon.log.1"));
+        let append = |path: &std::path::Path, text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+        let events = |entries: &[LogEntry]| -> Vec<String> {
+            entries.iter().map(|entry| entry.event.clone()).collect()
+        };
+
+        append(&path, "2026-10-18T09:00:00+00:00 INFO first\n");
+        let (read, offset) = read_appended(&path, &rotated, 0).unwrap();
+        assert_eq!(events(&read), ["first"]);
+
+        // A half-written line waits for its newline
+        append(
+            &path,
+            "2026-10-18T09:00:01+00:00 INFO second\n2026-10-18T09:00:02+00:00 IN",
+        );
+        let (read, offset) = read_appended(&path, &rotated, offset).unwrap();
+        assert_eq!(events(&read), ["second"]);
+
+        // The daemon finishes the line, rotates, and starts a new file
+        append(&path, "FO third\n");
+        std::fs::rename(&path, &rotated).unwrap();
+        append(&path, "2026-10-18T09:00:03+00:00 INFO fourth\n");
+        let (read, offset) = read_appended(&path, &rotated, offset).unwrap();
+        assert_eq!(events(&read), ["third", "fourth"]);
+
+        let (read, _) = read_appended(&path, &rotated, offset).unwrap();
+        assert!(read.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
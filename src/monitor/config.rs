@@ -0,0 +1,561 @@
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::monitor::db::MAX_TIMEOUT;
+use crate::utils::get_data_dir;
+
+// Global daemon settings, read from ~/.isup/config.
+//
+// The file is a list of `key = value` lines; blank lines and lines starting
+// with '#' are ignored. Missing keys keep their defaults:
+//
+//     user_agent = isup/2.0.3
+//     timeout = 10
+//     notifiers = device, console
+//     log_level = info
+//     max_concurrent_checks = 16
+//     jitter = 0.1
+//     shutdown_grace = 10
+//     metrics_addr = 127.0.0.1:9188
+//     api_addr = 127.0.0.1:9187
+//     statuspage_addr = 127.0.0.1:9190
+//     statuspage_dir = /var/www/status
+//     statuspage_title = Status
+//     dashboard_addr = 127.0.0.1:9191
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+
+    // syslog priority, used as a <N> line prefix when logging to the journal
+    pub fn syslog_priority(&self) -> u8 {
+        match self {
+            LogLevel::Error => 3,
+            LogLevel::Warn => 4,
+            LogLevel::Info => 6,
+            LogLevel::Debug => 7,
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            other => Err(format!(
+                "invalid log level '{}', expected error, warn, info or debug",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub user_agent: String,
+    // Request timeout in seconds
+    pub timeout: f64,
+    // Notification channels, tried in order until one succeeds
+    pub notifiers: Vec<String>,
+    pub log_level: LogLevel,
+    // How many checks the daemon runs at the same time
+    pub max_concurrent_checks: usize,
+    // Fraction of a site's interval its checks may move either way
+    pub jitter: f64,
+    // Seconds the daemon waits for running checks when it stops
+    pub shutdown_grace: f64,
+    // Where to serve Prometheus metrics; off when None
+    pub metrics_addr: Option<SocketAddr>,
+    // Where to serve the management API; off when None
+    pub api_addr: Option<SocketAddr>,
+    // Where to serve the status page; off when None
+    pub statuspage_addr: Option<SocketAddr>,
+    // Where the daemon keeps the status page up to date; off when None
+    // unless statuspage_addr is set
+    pub statuspage_dir: Option<PathBuf>,
+    pub statuspage_title: String,
+    // Where to serve the web dashboard; off when None. Always a loopback
+    // address, since the dashboard has no login.
+    pub dashboard_addr: Option<SocketAddr>,
+}
+
+// Channels the notifier knows how to deliver to
+pub const NOTIFIER_CHANNELS: &[&str] = &["device", "console"];
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            user_agent: format!("isup/{}", env!("CARGO_PKG_VERSION")),
+            timeout: 10.0,
+            notifiers: vec!["device".to_string(), "console".to_string()],
+            log_level: LogLevel::Info,
+            max_concurrent_checks: 16,
+            jitter: 0.1,
+            shutdown_grace: 10.0,
+            metrics_addr: None,
+            api_addr: None,
+            statuspage_addr: None,
+            statuspage_dir: None,
+            statuspage_title: "Status".to_string(),
+            dashboard_addr: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Config::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `key = value`", number + 1))?;
+            let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+
+            config
+                .set(key, value)
+                .map_err(|e| anyhow!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "user_agent" => self.user_agent = value.to_string(),
+            "timeout" => {
+                self.timeout = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|timeout| *timeout > 0.0 && *timeout <= MAX_TIMEOUT)
+                    .ok_or_else(|| format!("invalid timeout '{}'", value))?;
+            }
+            "notifiers" => {
+                let channels: Vec<String> = value
+                    .split(',')
+                    .map(|channel| channel.trim().to_lowercase())
+                    .filter(|channel| !channel.is_empty())
+                    .collect();
+                if let Some(unknown) = channels
+                    .iter()
+                    .find(|c| !NOTIFIER_CHANNELS.contains(&c.as_str()))
+                {
+                    return Err(format!("unknown notifier '{}'", unknown));
+                }
+                self.notifiers = channels;
+            }
+            "log_level" => self.log_level = value.parse()?,
+            "max_concurrent_checks" => {
+                self.max_concurrent_checks = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or_else(|| format!("invalid max_concurrent_checks '{}'", value))?;
+            }
+            "jitter" => {
+                self.jitter = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|jitter| (0.0..=0.5).contains(jitter))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid jitter '{}', expected a fraction between 0 and 0.5",
+                            value
+                        )
+                    })?;
+            }
+            "shutdown_grace" => {
+                self.shutdown_grace = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|grace| (0.0..=MAX_TIMEOUT).contains(grace))
+                    .ok_or_else(|| format!("invalid shutdown_grace '{}'", value))?;
+            }
+            "metrics_addr" => self.metrics_addr = parse_addr(key, value)?,
+            "api_addr" => self.api_addr = parse_addr(key, value)?,
+            "statuspage_addr" => self.statuspage_addr = parse_addr(key, value)?,
+            "statuspage_dir" => {
+                self.statuspage_dir = match value {
+                    "" | "off" => None,
+                    dir => Some(expand_home(dir)),
+                };
+            }
+            "statuspage_title" => self.statuspage_title = value.to_string(),
+            "dashboard_addr" => {
+                let addr = parse_addr(key, value)?;
+                if addr.is_some_and(|addr| !addr.ip().is_loopback()) {
+                    return Err(format!(
+                        "dashboard_addr must be a loopback address like 127.0.0.1:9191, not '{}'",
+                        value
+                    ));
+                }
+                self.dashboard_addr = addr;
+            }
+            other => return Err(format!("unknown setting '{}'", other)),
+        }
+        Ok(())
+    }
+
+    // Human-readable list of settings that differ from `other`
+    pub fn diff(&self, other: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        if self.user_agent != other.user_agent {
+            changes.push(format!(
+                "user_agent: {:?} -> {:?}",
+                self.user_agent, other.user_agent
+            ));
+        }
+        if self.timeout != other.timeout {
+            changes.push(format!("timeout: {}s -> {}s", self.timeout, other.timeout));
+        }
+        if self.notifiers != other.notifiers {
+            changes.push(format!(
+                "notifiers: {} -> {}",
+                self.notifiers.join(", "),
+                other.notifiers.join(", ")
+            ));
+        }
+        if self.log_level != other.log_level {
+            changes.push(format!(
+                "log_level: {} -> {}",
+                self.log_level, other.log_level
+            ));
+        }
+        if self.max_concurrent_checks != other.max_concurrent_checks {
+            changes.push(format!(
+                "max_concurrent_checks: {} -> {}",
+                self.max_concurrent_checks, other.max_concurrent_checks
+            ));
+        }
+        if self.jitter != other.jitter {
+            changes.push(format!("jitter: {} -> {}", self.jitter, other.jitter));
+        }
+        if self.shutdown_grace != other.shutdown_grace {
+            changes.push(format!(
+                "shutdown_grace: {}s -> {}s",
+                self.shutdown_grace, other.shutdown_grace
+            ));
+        }
+        if self.metrics_addr != other.metrics_addr {
+            changes.push(format!(
+                "metrics_addr: {} -> {}",
+                format_addr(self.metrics_addr),
+                format_addr(other.metrics_addr)
+            ));
+        }
+        if self.api_addr != other.api_addr {
+            changes.push(format!(
+                "api_addr: {} -> {}",
+                format_addr(self.api_addr),
+                format_addr(other.api_addr)
+            ));
+        }
+        if self.statuspage_addr != other.statuspage_addr {
+            changes.push(format!(
+                "statuspage_addr: {} -> {}",
+                format_addr(self.statuspage_addr),
+                format_addr(other.statuspage_addr)
+            ));
+        }
+        if self.statuspage_dir != other.statuspage_dir {
+            let format_dir = |dir: &Option<PathBuf>| {
+                dir.as_ref()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_else(|| "off".to_string())
+            };
+            changes.push(format!(
+                "statuspage_dir: {} -> {}",
+                format_dir(&self.statuspage_dir),
+                format_dir(&other.statuspage_dir)
+            ));
+        }
+        if self.dashboard_addr != other.dashboard_addr {
+            changes.push(format!(
+                "dashboard_addr: {} -> {}",
+                format_addr(self.dashboard_addr),
+                format_addr(other.dashboard_addr)
+            ));
+        }
+        if self.statuspage_title != other.statuspage_title {
+            changes.push(format!(
+                "statuspage_title: {:?} -> {:?}",
+                self.statuspage_title, other.statuspage_title
+            ));
+        }
+
+        changes
+    }
+}
+
+// A listen address, or "off" (or nothing) to disable the server
+fn parse_addr(key: &str, value: &str) -> Result<Option<SocketAddr>, String> {
+    if value.is_empty() || value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    value.parse().map(Some).map_err(|_| {
+        format!(
+            "invalid {} '{}', expected an address like 127.0.0.1:9188 or off",
+            key, value
+        )
+    })
+}
+
+// Paths may start with ~/ for the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn format_addr(addr: Option<SocketAddr>) -> String {
+    addr.map(|addr| addr.to_string())
+        .unwrap_or_else(|| "off".to_string())
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_data_dir().join("config")
+}
+
+// Read the config file; a missing file means all defaults
+pub fn load_config() -> Result<Config> {
+    let path = get_config_path();
+    match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            Config::parse(&text).with_context(|| format!("invalid config at {}", path.display()))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read config at {}", path.display())),
+    }
+}
+
+static CURRENT: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+// The active configuration, loaded on first use
+pub fn current() -> Arc<Config> {
+    if let Some(config) = CURRENT.read().ok().and_then(|current| current.clone()) {
+        return config;
+    }
+
+    let config = Arc::new(load_config().unwrap_or_else(|e| {
+        eprintln!("🚨 {:#}, using defaults", e);
+        Config::default()
+    }));
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(config.clone());
+    }
+    config
+}
+
+// Re-read the config file and return what changed.
+// An invalid file leaves the active configuration untouched.
+pub fn reload() -> Result<Vec<String>> {
+    let old = current();
+    let new = load_config()?;
+    let changes = old.diff(&new);
+
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(Arc::new(new));
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Expectation = fn(&Config) -> bool;
+
+    #[test]
+    fn accepted_files() {
+        let cases: &[(&str, Expectation)] = &[
+            ("", |c| *c == Config::default()),
+            ("# only a comment\n\n   \n", |c| *c == Config::default()),
+            ("timeout = 2.5", |c| c.timeout == 2.5),
+            ("user_agent = \"probe/1.0\"", |c| {
+                c.user_agent == "probe/1.0"
+            }),
+            ("notifiers = Console, device", |c| {
+                c.notifiers == ["console", "device"]
+            }),
+            ("notifiers =", |c| c.notifiers.is_empty()),
+            ("log_level = WARNING", |c| c.log_level == LogLevel::Warn),
+            ("max_concurrent_checks = 1", |c| {
+                c.max_concurrent_checks == 1
+            }),
+            ("jitter = 0", |c| c.jitter == 0.0),
+            ("jitter = 0.5", |c| c.jitter == 0.5),
+            ("shutdown_grace = 0", |c| c.shutdown_grace == 0.0),
+            ("metrics_addr = 0.0.0.0:9188", |c| {
+                c.metrics_addr == Some("0.0.0.0:9188".parse().unwrap())
+            }),
+            ("metrics_addr = off", |c| c.metrics_addr.is_none()),
+            ("api_addr = off", |c| c.api_addr.is_none()),
+            ("statuspage_addr = 127.0.0.1:9190", |c| {
+                c.statuspage_addr.is_some()
+            }),
+            ("statuspage_dir = /srv/status", |c| {
+                c.statuspage_dir == Some(PathBuf::from("/srv/status"))
+            }),
+            ("statuspage_dir = off", |c| c.statuspage_dir.is_none()),
+            ("dashboard_addr = 127.0.0.1:9191", |c| {
+                c.dashboard_addr == Some("127.0.0.1:9191".parse().unwrap())
+            }),
+            ("dashboard_addr = [::1]:9191", |c| {
+                c.dashboard_addr == Some("[::1]:9191".parse().unwrap())
+            }),
+            ("dashboard_addr = off", |c| c.dashboard_addr.is_none()),
+            ("statuspage_title = Example Status", |c| {
+                c.statuspage_title == "Example Status"
+            }),
+            ("api_addr = [::1]:9187", |c| {
+                c.api_addr == Some("[::1]:9187".parse().unwrap())
+            }),
+            ("timeout = 1\ntimeout = 3", |c| c.timeout == 3.0),
+        ];
+        for (text, check) in cases {
+            let config =
+                Config::parse(text).unwrap_or_else(|e| panic!("{:?} was rejected: {:#}", text, e));
+            assert!(check(&config), "{:?} parsed to {:?}", text, config);
+        }
+    }
+
+    #[test]
+    fn rejected_files() {
+        let cases = [
+            ("timeout", "line 1: expected `key = value`"),
+            ("timeout = 0", "invalid timeout"),
+            ("timeout = -1", "invalid timeout"),
+            ("timeout = soon", "invalid timeout"),
+            ("timeout = inf", "invalid timeout"),
+            ("timeout = 1e20", "invalid timeout"),
+            ("notifiers = console, pager", "unknown notifier 'pager'"),
+            ("log_level = loud", "invalid log level"),
+            ("max_concurrent_checks = 0", "invalid max_concurrent_checks"),
+            ("jitter = -0.1", "invalid jitter"),
+            ("jitter = 0.6", "invalid jitter"),
+            ("shutdown_grace = -1", "invalid shutdown_grace"),
+            ("shutdown_grace = inf", "invalid shutdown_grace"),
+            ("metrics_addr = localhost", "invalid metrics_addr"),
+            ("api_addr = 9187", "invalid api_addr"),
+            ("statuspage_addr = :9190", "invalid statuspage_addr"),
+            (
+                "dashboard_addr = 0.0.0.0:9191",
+                "must be a loopback address",
+            ),
+            (
+                "dashboard_addr = 192.168.1.2:9191",
+                "must be a loopback address",
+            ),
+            ("colour = blue", "unknown setting 'colour'"),
+            ("\n# ok\ntimeout = 0", "line 3:"),
+        ];
+        for (text, expected) in cases {
+            let error = format!("{:#}", Config::parse(text).expect_err(text));
+            assert!(
+                error.contains(expected),
+                "{:?} gave {:?}, expected {:?}",
+                text,
+                error,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn identical_configs_have_no_diff() {
+        assert!(Config::default().diff(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn diff_names_each_changed_setting() {
+        let cases = [
+            ("user_agent = other", "user_agent: "),
+            ("timeout = 3", "timeout: 10s -> 3s"),
+            (
+                "notifiers = console",
+                "notifiers: device, console -> console",
+            ),
+            ("log_level = debug", "log_level: info -> debug"),
+            (
+                "max_concurrent_checks = 4",
+                "max_concurrent_checks: 16 -> 4",
+            ),
+            ("jitter = 0.2", "jitter: 0.1 -> 0.2"),
+            ("shutdown_grace = 3", "shutdown_grace: 10s -> 3s"),
+            (
+                "metrics_addr = 127.0.0.1:9188",
+                "metrics_addr: off -> 127.0.0.1:9188",
+            ),
+            (
+                "api_addr = 127.0.0.1:9187",
+                "api_addr: off -> 127.0.0.1:9187",
+            ),
+            (
+                "statuspage_addr = 127.0.0.1:9190",
+                "statuspage_addr: off -> 127.0.0.1:9190",
+            ),
+            (
+                "statuspage_dir = /srv/status",
+                "statuspage_dir: off -> /srv/status",
+            ),
+            (
+                "dashboard_addr = 127.0.0.1:9191",
+                "dashboard_addr: off -> 127.0.0.1:9191",
+            ),
+            (
+                "statuspage_title = Ours",
+                "statuspage_title: \"Status\" -> \"Ours\"",
+            ),
+        ];
+        let old = Config::default();
+        for (text, expected) in cases {
+            let changes = old.diff(&Config::parse(text).unwrap());
+            assert_eq!(changes.len(), 1, "{:?} changed {:?}", text, changes);
+            assert!(
+                changes[0].starts_with(expected),
+                "{:?} gave {:?}",
+                text,
+                changes[0]
+            );
+        }
+
+        let changes = old.diff(&Config::parse("timeout = 3\nlog_level = debug").unwrap());
+        assert_eq!(changes.len(), 2);
+    }
+}
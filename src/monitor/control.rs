@@ -0,0 +1,358 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::monitor::config::LogLevel;
+use crate::monitor::db::{get_all_sites, get_site_by_url, set_site_paused};
+use crate::monitor::log;
+use crate::monitor::service::{request_stop, DaemonState};
+use crate::utils::get_runtime_dir;
+
+// The control socket speaks newline-delimited JSON: each request is one object
+// like {"cmd":"status"} and each reply is one object with "ok" set, plus an
+// "error" message when "ok" is false.
+
+pub fn get_socket_path() -> PathBuf {
+    get_runtime_dir().join("isupd.sock")
+}
+
+// Listen for control requests until the daemon exits
+#[cfg(unix)]
+pub async fn serve(state: Arc<DaemonState>) -> Result<()> {
+    use tokio::net::UnixListener;
+
+    let path = get_socket_path();
+
+    // A socket file nobody answers on is left over from a daemon that didn't shut down cleanly
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(anyhow!(
+                "another daemon is already listening on {}",
+                path.display()
+            ));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // Only the owner may talk to the daemon. The socket is created with these
+    // permissions so it is never reachable by anyone else, even briefly.
+    let previous_umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(&path);
+    unsafe { libc::umask(previous_umask) };
+    let listener = listener?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, state).await {
+                log::event(
+                    LogLevel::Warn,
+                    "control_connection_failed",
+                    json!({ "error": e.to_string() }),
+                );
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn handle_connection(stream: tokio::net::UnixStream, state: Arc<DaemonState>) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle_request(&state, &request).await,
+            Err(e) => error_response(format!("invalid request: {}", e)),
+        };
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn handle_request(state: &Arc<DaemonState>, request: &Value) -> Value {
+    let url = request.get("url").and_then(Value::as_str);
+
+    match request.get("cmd").and_then(Value::as_str) {
+        Some("ping") => json!({ "ok": true }),
+        Some("status") => state.status().await,
+        Some("sync") => {
+            // Sent by the CLI after it adds, removes or pauses a site
+            state.request_resync();
+            json!({ "ok": true })
+        }
+        Some("reload") => {
+            log::event(
+                LogLevel::Info,
+                "control_request",
+                json!({ "cmd": "reload" }),
+            );
+            state.request_resync();
+            match state.reload_config() {
+                Ok(changes) => json!({ "ok": true, "changes": changes }),
+                Err(e) => error_response(format!(
+                    "config reload failed, keeping current settings: {:#}",
+                    e
+                )),
+            }
+        }
+        Some(cmd @ ("pause" | "resume")) => {
+            let Some(url) = url else {
+                return error_response("missing url");
+            };
+            let paused = cmd == "pause";
+
+            match set_site_paused(url, paused) {
+                Ok(true) => {
+                    log::event(
+                        LogLevel::Info,
+                        "control_request",
+                        json!({ "cmd": cmd, "url": url }),
+                    );
+                    // A resumed site is checked right away
+                    state.request_resync();
+                    json!({ "ok": true, "url": url, "paused": paused, "in_flight": state.is_in_flight(url) })
+                }
+                Ok(false) => error_response(format!("{} is not being monitored", url)),
+                Err(e) => error_response(e.to_string()),
+            }
+        }
+        Some("check") => {
+            // Either one site or every site that isn't paused
+            let sites = if request.get("all").and_then(Value::as_bool) == Some(true) {
+                get_all_sites().map(|sites| {
+                    sites
+                        .into_iter()
+                        .filter(|site| !site.paused)
+                        .collect::<Vec<_>>()
+                })
+            } else if let Some(url) = url {
+                get_site_by_url(url).map(|site| site.into_iter().collect())
+            } else {
+                return error_response("missing url");
+            };
+
+            let sites: Vec<_> = match sites {
+                Ok(sites) if sites.is_empty() => {
+                    return error_response(format!(
+                        "{} is not being monitored",
+                        url.unwrap_or("nothing")
+                    ));
+                }
+                Ok(sites) => sites,
+                Err(e) => return error_response(e.to_string()),
+            };
+
+            log::event(
+                LogLevel::Info,
+                "control_request",
+                json!({ "cmd": "check", "sites": sites.len() }),
+            );
+            let mut tasks = tokio::task::JoinSet::new();
+            for (index, site) in sites.into_iter().enumerate() {
+                let state = state.clone();
+                tasks.spawn(async move {
+                    let result = state.check_now(&site).await;
+                    (index, site.url, result)
+                });
+            }
+
+            let mut results = Vec::new();
+            while let Some(Ok(result)) = tasks.join_next().await {
+                results.push(result);
+            }
+            results.sort_by_key(|(index, _, _)| *index);
+
+            let results: Vec<Value> = results
+                .iter()
+                .map(|(_, url, check)| match check {
+                    Ok(check) => check.to_json(),
+                    // e.g. a scheduled check of the site was still running
+                    Err(e) => json!({ "url": url, "error": e.to_string() }),
+                })
+                .collect();
+            json!({ "ok": true, "results": results })
+        }
+        Some("stop") => {
+            log::event(LogLevel::Info, "control_request", json!({ "cmd": "stop" }));
+            request_stop();
+            state.reload.notify_one();
+            json!({ "ok": true, "in_flight": state.in_flight_count() })
+        }
+        Some(other) => error_response(format!("unknown command '{}'", other)),
+        None => error_response("missing cmd"),
+    }
+}
+
+fn error_response(message: impl Into<String>) -> Value {
+    json!({ "ok": false, "error": message.into() })
+}
+
+// Send one request to the running daemon and wait for its reply.
+// Fails if the daemon can't be reached; a reply with "ok": false is returned as is.
+#[cfg(unix)]
+pub fn send_command(request: Value) -> Result<Value> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    let mut stream = UnixStream::connect(get_socket_path())?;
+    // A check takes as long as the slowest site it covers, so only other commands time out
+    let read_timeout = if request["cmd"] == "check" {
+        None
+    } else {
+        Some(Duration::from_secs(30))
+    };
+    stream.set_read_timeout(read_timeout)?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    writeln!(stream, "{}", request)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(anyhow!("daemon closed the control connection"));
+    }
+
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(not(unix))]
+pub fn send_command(_request: Value) -> Result<Value> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the control socket is only available on unix",
+    )
+    .into())
+}
+
+// Whether a send_command error means no daemon is listening, as opposed to
+// one that failed or gave up while answering
+pub fn is_unreachable(error: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            ErrorKind::NotFound | ErrorKind::ConnectionRefused | ErrorKind::Unsupported
+        )
+    })
+}
+
+// Turn an "ok": false reply into an error
+pub fn expect_ok(response: Value) -> Result<Value> {
+    if response.get("ok").and_then(Value::as_bool) == Some(true) {
+        Ok(response)
+    } else {
+        let message = response
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("daemon returned an error");
+        Err(anyhow!("{}", message))
+    }
+}
+
+// Tell a running daemon that the site list changed; does nothing if none is running
+pub fn notify_sites_changed() {
+    let _ = send_command(json!({ "cmd": "sync" }));
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::monitor::db::{add_site_to_db, get_site_by_url, Site};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // Send raw lines over a connection to handle_connection and collect the replies
+    async fn exchange(requests: &[&str]) -> Vec<Value> {
+        let (client, server) = tokio::net::UnixStream::pair().unwrap();
+        tokio::spawn(handle_connection(server, DaemonState::for_tests()));
+
+        let (reader, mut writer) = client.into_split();
+        let mut replies = BufReader::new(reader).lines();
+        let mut out = Vec::new();
+        for request in requests {
+            writer
+                .write_all(format!("{}\n", request).as_bytes())
+                .await
+                .unwrap();
+            let reply = replies.next_line().await.unwrap().unwrap();
+            out.push(serde_json::from_str(&reply).unwrap());
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn bad_requests_get_an_error_and_keep_the_connection() {
+        let replies = exchange(&[
+            r#"{"cmd":"frobnicate"}"#,
+            "{not json",
+            r#"{"url":"https://example.com"}"#,
+            r#"{"cmd":"pause"}"#,
+            r#"{"cmd":"ping"}"#,
+        ])
+        .await;
+
+        assert_eq!(replies[0]["ok"], false);
+        assert_eq!(replies[0]["error"], "unknown command 'frobnicate'");
+        assert_eq!(replies[1]["ok"], false);
+        assert!(replies[1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request:"));
+        assert_eq!(replies[2]["error"], "missing cmd");
+        assert_eq!(replies[3]["error"], "missing url");
+        assert_eq!(replies[4], json!({ "ok": true }));
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_round_trip() {
+        let url = "https://control-test.example.com";
+        add_site_to_db(&Site {
+            url: url.to_string(),
+            interval: 60.0,
+            notify_method: "device".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let pause = json!({ "cmd": "pause", "url": url }).to_string();
+        let resume = json!({ "cmd": "resume", "url": url }).to_string();
+        let replies = exchange(&[&pause]).await;
+        assert_eq!(replies[0]["ok"], true);
+        assert_eq!(replies[0]["paused"], true);
+        assert!(get_site_by_url(url).unwrap().unwrap().paused);
+
+        let replies = exchange(&[&resume]).await;
+        assert_eq!(replies[0]["paused"], false);
+        assert!(!get_site_by_url(url).unwrap().unwrap().paused);
+
+        let missing = json!({ "cmd": "pause", "url": "https://not-monitored.test" }).to_string();
+        let replies = exchange(&[&missing]).await;
+        assert_eq!(replies[0]["ok"], false);
+        assert_eq!(
+            replies[0]["error"],
+            "https://not-monitored.test is not being monitored"
+        );
+    }
+
+    #[test]
+    fn only_connect_failures_count_as_unreachable() {
+        // Nothing listens on the test socket path
+        let missing = send_command(json!({ "cmd": "ping" })).unwrap_err();
+        assert!(is_unreachable(&missing));
+
+        let timed_out = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::WouldBlock));
+        assert!(!is_unreachable(&timed_out));
+        assert!(!is_unreachable(&anyhow!(
+            "daemon closed the control connection"
+        )));
+    }
+}
@@ -0,0 +1,456 @@
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::monitor::api;
+use crate::monitor::http::{json_response, not_found, text_response};
+use crate::monitor::service::DaemonState;
+
+// A web UI for people who'd rather not use the CLI, served on dashboard_addr.
+//
+//     GET /          the page itself
+//     GET /events    server-sent `status` events, as GET /api/status returns it
+//     /api/...       the REST API routes, without the token
+//
+// There is no login, so the address is always a loopback one. To keep other
+// websites from reaching it through the browser, the Host header must name a
+// loopback host, and changes need an X-Isup-Dashboard header, which a page on
+// another origin can't send without a preflight the dashboard never answers.
+
+// Resend the status this often even if nothing changed, so dead connections
+// are noticed and the daemon's uptime stays current
+const KEEPALIVE: Duration = Duration::from_secs(15);
+// Collect the changes from checks that end together into one event
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+pub async fn handle(state: Arc<DaemonState>, request: Request<Body>) -> Response<Body> {
+    if !loopback_host(&request) {
+        return text_response(
+            StatusCode::FORBIDDEN,
+            "text/plain; charset=utf-8",
+            "forbidden\n",
+        );
+    }
+
+    let path = request.uri().path();
+    if path == "/api" || path.starts_with("/api/") {
+        if request.method() != Method::GET && !request.headers().contains_key("x-isup-dashboard") {
+            return json_response(
+                StatusCode::FORBIDDEN,
+                &serde_json::json!({ "error": "missing X-Isup-Dashboard header" }),
+            );
+        }
+        return api::respond(&state, request).await;
+    }
+
+    match (request.method(), path) {
+        (&Method::GET, "/") => text_response(StatusCode::OK, "text/html; charset=utf-8", PAGE),
+        (&Method::GET, "/events") => events(state),
+        _ => not_found(),
+    }
+}
+
+// Whether the Host header names this machine, which a DNS rebinding attack can't fake
+fn loopback_host(request: &Request<Body>) -> bool {
+    let Some(host) = request
+        .headers()
+        .get("host")
+        .and_then(|host| host.to_str().ok())
+    else {
+        return false;
+    };
+    let Ok(authority) = host.parse::<hyper::http::uri::Authority>() else {
+        return false;
+    };
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn events(state: Arc<DaemonState>) -> Response<Body> {
+    let (mut sender, body) = Body::channel();
+    let mut updates = state.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let event = match api::status_json(&state).await {
+                Ok(status) => format!("event: status\ndata: {}\n\n", status),
+                Err(e) => format!(
+                    "event: failure\ndata: {}\n\n",
+                    serde_json::json!({ "error": format!("{:#}", e) })
+                ),
+            };
+            // Fails once the browser has gone away
+            if sender.send_data(event.into()).await.is_err() {
+                break;
+            }
+
+            tokio::select! {
+                changed = updates.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(DEBOUNCE).await;
+                    updates.borrow_and_update();
+                }
+                _ = tokio::time::sleep(KEEPALIVE) => {}
+            }
+        }
+    });
+
+    Response::builder()
+        .header("content-type", "text/event-stream")
+        .header("cache-control", "no-cache")
+        .body(body)
+        .unwrap_or_default()
+}
+
+const PAGE: &str = r##"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>isup</title>
+<style>
+body { margin: 0; background: #f6f7f9; color: #1d2330; font: 14px/1.5 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; }
+main { max-width: 1040px; margin: 0 auto; padding: 24px 16px; }
+header { display: flex; align-items: baseline; gap: 16px; margin-bottom: 16px; }
+h1 { font-size: 22px; margin: 0; }
+h2 { font-size: 16px; margin: 0 0 12px; }
+.card { background: #fff; border: 1px solid #e3e6eb; border-radius: 8px; padding: 16px; margin-bottom: 16px; }
+.muted { color: #6b7482; }
+.live::before { content: ""; display: inline-block; width: 8px; height: 8px; border-radius: 50%; margin-right: 6px; background: #9aa3b0; }
+.live.on::before { background: #1f9d55; }
+form { display: flex; flex-wrap: wrap; gap: 8px; align-items: center; }
+input { font: inherit; padding: 6px 8px; border: 1px solid #cfd4db; border-radius: 6px; }
+input[name=url] { flex: 1; min-width: 240px; }
+input[type=number] { width: 110px; }
+button { font: inherit; padding: 5px 10px; border: 1px solid #cfd4db; border-radius: 6px; background: #fff; cursor: pointer; }
+button.primary { background: #3490dc; border-color: #3490dc; color: #fff; }
+button.danger { color: #cc1f1a; }
+.error { color: #cc1f1a; min-height: 1.5em; margin-top: 6px; }
+table { width: 100%; border-collapse: collapse; }
+th, td { text-align: left; padding: 7px 8px; border-bottom: 1px solid #eef0f3; }
+th { font-weight: 600; color: #6b7482; font-size: 12px; text-transform: uppercase; }
+tr.site { cursor: pointer; }
+tr.site:hover, tr.selected { background: #f2f6fb; }
+td.url { overflow-wrap: anywhere; font-weight: 600; }
+td.actions { white-space: nowrap; text-align: right; }
+.dot { display: inline-block; width: 10px; height: 10px; border-radius: 50%; background: #9aa3b0; }
+.dot.up { background: #1f9d55; }
+.dot.degraded { background: #f6c343; }
+.dot.down { background: #cc1f1a; }
+.dot.paused { background: #b7791f; }
+.dot.checking { box-shadow: 0 0 0 3px #cde3f7; }
+svg.chart { width: 100%; height: 120px; background: #fafbfc; border: 1px solid #eef0f3; border-radius: 6px; }
+svg.chart polyline { fill: none; stroke: #3490dc; stroke-width: 1.5; vector-effect: non-scaling-stroke; }
+svg.chart .fail { fill: #e3342f; }
+.axis { display: flex; justify-content: space-between; font-size: 12px; }
+#detail[hidden] { display: none; }
+</style>
+</head>
+<body>
+<main>
+<header>
+  <h1>isup</h1>
+  <span id="daemon" class="muted"></span>
+  <span id="live" class="live muted">connecting</span>
+</header>
+
+<div class="card">
+  <h2>Add a site</h2>
+  <form id="add">
+    <input name="url" placeholder="https://example.com/health" required>
+    <input name="interval" type="number" min="0.1" step="any" placeholder="interval (s)">
+    <input name="timeout" type="number" min="0.1" step="any" placeholder="timeout (s)">
+    <button class="primary">Add</button>
+  </form>
+  <div class="error" id="add-error"></div>
+</div>
+
+<div class="card">
+  <h2>Sites</h2>
+  <table>
+    <thead><tr><th></th><th>URL</th><th>Status</th><th>Latency</th><th>Last check</th><th>Interval</th><th></th></tr></thead>
+    <tbody id="sites"></tbody>
+  </table>
+  <p class="muted" id="empty" hidden>No sites yet. Add one above.</p>
+  <div class="error" id="sites-error"></div>
+</div>
+
+<div class="card" id="detail" hidden>
+  <h2 id="detail-url"></h2>
+  <form id="edit">
+    <label>Interval (s) <input name="interval" type="number" min="0.1" step="any" required></label>
+    <label>Timeout (s) <input name="timeout" type="number" min="0.1" step="any" placeholder="default"></label>
+    <button class="primary">Save</button>
+  </form>
+  <div class="error" id="edit-error"></div>
+  <h2>Response time, last 24 hours</h2>
+  <svg class="chart" id="chart" viewBox="0 0 1000 120" preserveAspectRatio="none"></svg>
+  <div class="axis muted"><span>24h ago</span><span id="chart-summary"></span><span>now</span></div>
+  <h2 style="margin-top: 16px">Incidents, last 30 days</h2>
+  <table>
+    <thead><tr><th>Started</th><th>Duration</th><th>Failed checks</th><th>Status</th></tr></thead>
+    <tbody id="incidents"></tbody>
+  </table>
+  <p class="muted" id="no-incidents" hidden>No incidents.</p>
+</div>
+</main>
+
+<script>
+let lastStatus = { daemon: { in_flight: [] }, sites: [] };
+let sites = [];
+let selected = null;
+let selectedChecked = null;
+
+function el(tag, props, ...children) {
+  const node = document.createElement(tag);
+  Object.assign(node, props || {});
+  for (const child of children) node.append(child);
+  return node;
+}
+
+async function api(method, path, body) {
+  const response = await fetch(path, {
+    method,
+    headers: { 'content-type': 'application/json', 'x-isup-dashboard': '1' },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  const data = await response.json().catch(() => ({}));
+  if (!response.ok) throw new Error(data.error || response.statusText);
+  return data;
+}
+
+function ago(seconds) {
+  if (seconds == null) return '-';
+  const delta = Math.max(0, Math.round(Date.now() / 1000 - seconds));
+  return duration(delta) + ' ago';
+}
+
+function duration(seconds) {
+  if (seconds < 60) return seconds + 's';
+  if (seconds < 3600) return Math.floor(seconds / 60) + 'm';
+  if (seconds < 86400) return Math.floor(seconds / 3600) + 'h ' + Math.floor(seconds % 3600 / 60) + 'm';
+  return Math.floor(seconds / 86400) + 'd ' + Math.floor(seconds % 86400 / 3600) + 'h';
+}
+
+function number(value) {
+  return value === '' ? null : Number(value);
+}
+
+function showError(id, error) {
+  document.getElementById(id).textContent = error ? error.message : '';
+}
+
+function action(label, handler, className) {
+  return el('button', {
+    textContent: label,
+    className: className || '',
+    onclick: async (event) => {
+      event.stopPropagation();
+      try {
+        await handler();
+        showError('sites-error');
+      } catch (error) {
+        showError('sites-error', error);
+      }
+    },
+  });
+}
+
+function render(status) {
+  const daemon = status.daemon;
+  sites = status.sites;
+  document.getElementById('daemon').textContent =
+    `v${daemon.version} · up ${duration(daemon.uptime_secs)} · ${daemon.tracked_sites} tracked · ${daemon.in_flight.length} checking`;
+
+  const body = document.getElementById('sites');
+  body.replaceChildren(...sites.map((site) => {
+    const checking = daemon.in_flight.includes(site.url);
+    const status = site.status_code != null ? String(site.status_code) : (site.last_status || '-');
+    return el('tr', { className: 'site' + (site.id === selected ? ' selected' : ''), onclick: () => select(site.id) },
+      el('td', {}, el('span', { className: `dot ${site.state}${checking ? ' checking' : ''}`, title: site.state })),
+      el('td', { className: 'url', textContent: site.url }),
+      el('td', { textContent: status, title: site.last_status || '' }),
+      el('td', { textContent: site.latency_ms != null ? site.latency_ms + 'ms' : '-' }),
+      el('td', { textContent: ago(site.last_checked) }),
+      el('td', { textContent: site.interval + 's' }),
+      el('td', { className: 'actions' },
+        action('Check', () => api('POST', `/api/sites/${site.id}/check`)),
+        ' ',
+        action(site.paused ? 'Resume' : 'Pause', () => api('PATCH', `/api/sites/${site.id}`, { paused: !site.paused })),
+        ' ',
+        action('Remove', async () => {
+          if (!confirm(`Stop monitoring ${site.url}?`)) return;
+          await api('DELETE', `/api/sites/${site.id}`);
+          if (selected === site.id) select(null);
+        }, 'danger'),
+      ),
+    );
+  }));
+  document.getElementById('empty').hidden = sites.length > 0;
+
+  const current = sites.find((site) => site.id === selected);
+  if (selected !== null && !current) {
+    select(null);
+  } else if (current && current.last_checked !== selectedChecked) {
+    loadDetail(current);
+  }
+}
+
+function select(id) {
+  selected = id;
+  selectedChecked = null;
+  const site = sites.find((site) => site.id === id);
+  document.getElementById('detail').hidden = !site;
+  for (const row of document.querySelectorAll('tr.site')) row.classList.remove('selected');
+  if (!site) return;
+
+  document.getElementById('detail-url').textContent = site.url;
+  const form = document.getElementById('edit');
+  form.interval.value = site.interval;
+  form.timeout.value = site.timeout;
+  showError('edit-error');
+  render({ daemon: lastStatus.daemon, sites });
+}
+
+async function loadDetail(site) {
+  selectedChecked = site.last_checked;
+  try {
+    const [history, incidents] = await Promise.all([
+      api('GET', `/api/sites/${site.id}/history?since=24h&limit=1000`),
+      api('GET', `/api/sites/${site.id}/incidents?since=30d`),
+    ]);
+    drawChart(history.checks);
+    drawIncidents(incidents.incidents);
+  } catch (error) {
+    showError('edit-error', error);
+  }
+}
+
+function drawChart(checks) {
+  const svg = document.getElementById('chart');
+  const now = Date.now() / 1000;
+  const start = now - 86400;
+  const x = (at) => ((at - start) / 86400 * 1000).toFixed(1);
+  const up = checks.filter((check) => check.is_up && check.latency_ms != null).reverse();
+  const max = Math.max(1, ...up.map((check) => check.latency_ms));
+  const ns = 'http://www.w3.org/2000/svg';
+
+  const line = document.createElementNS(ns, 'polyline');
+  line.setAttribute('points', up.map((check) => `${x(check.checked_at)},${(116 - check.latency_ms / max * 108).toFixed(1)}`).join(' '));
+  const failures = checks.filter((check) => !check.is_up).map((check) => {
+    const mark = document.createElementNS(ns, 'rect');
+    mark.setAttribute('class', 'fail');
+    mark.setAttribute('x', x(check.checked_at));
+    mark.setAttribute('y', 0);
+    mark.setAttribute('width', 2);
+    mark.setAttribute('height', 120);
+    return mark;
+  });
+  svg.replaceChildren(line, ...failures);
+
+  const average = up.length ? Math.round(up.reduce((sum, check) => sum + check.latency_ms, 0) / up.length) : null;
+  document.getElementById('chart-summary').textContent = up.length
+    ? `avg ${average}ms · max ${max}ms · ${failures.length} failed checks`
+    : 'no successful checks';
+}
+
+function drawIncidents(incidents) {
+  document.getElementById('incidents').replaceChildren(...incidents.map((incident) => el('tr', {},
+    el('td', { textContent: new Date(incident.started * 1000).toLocaleString() }),
+    el('td', { textContent: duration(incident.duration_seconds) + (incident.ended == null ? ', ongoing' : '') }),
+    el('td', { textContent: incident.failed_checks }),
+    el('td', { textContent: incident.status || '-' }),
+  )));
+  document.getElementById('no-incidents').hidden = incidents.length > 0;
+}
+
+document.getElementById('add').onsubmit = async (event) => {
+  event.preventDefault();
+  const form = event.target;
+  try {
+    const site = await api('POST', '/api/sites', {
+      url: form.url.value,
+      interval: number(form.interval.value),
+      timeout: number(form.timeout.value),
+    });
+    form.reset();
+    showError('add-error');
+    selected = site.id;
+  } catch (error) {
+    showError('add-error', error);
+  }
+};
+
+document.getElementById('edit').onsubmit = async (event) => {
+  event.preventDefault();
+  const form = event.target;
+  try {
+    await api('PATCH', `/api/sites/${selected}`, {
+      interval: number(form.interval.value),
+      timeout: number(form.timeout.value),
+    });
+    showError('edit-error');
+  } catch (error) {
+    showError('edit-error', error);
+  }
+};
+
+const live = document.getElementById('live');
+const events = new EventSource('/events');
+events.addEventListener('status', (event) => {
+  lastStatus = JSON.parse(event.data);
+  render(lastStatus);
+  // A site that was just added shows up here first
+  if (selected !== null && document.getElementById('detail').hidden) select(selected);
+});
+events.addEventListener('failure', (event) => {
+  showError('sites-error', new Error(JSON.parse(event.data).error));
+});
+events.onopen = () => { live.textContent = 'live'; live.classList.add('on'); };
+events.onerror = () => { live.textContent = 'reconnecting'; live.classList.remove('on'); };
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_host(host: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/")
+            .header("host", host)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn only_loopback_hosts_are_let_in() {
+        for host in [
+            "127.0.0.1:9191",
+            "localhost:9191",
+            "LOCALHOST",
+            "[::1]:9191",
+        ] {
+            assert!(loopback_host(&with_host(host)), "{}", host);
+        }
+        for host in [
+            "evil.test:9191",
+            "192.168.1.2:9191",
+            "127.0.0.1.evil.test",
+            "",
+        ] {
+            assert!(!loopback_host(&with_host(host)), "{}", host);
+        }
+        assert!(!loopback_host(&Request::new(Body::empty())));
+    }
+}
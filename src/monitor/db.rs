@@ -1,10 +1,13 @@
-use anyhow::{Result, Context};
-use rusqlite::{Connection, params};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::fs;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Row};
+use serde_json::{json, Value};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+use crate::monitor::config;
+use crate::utils::get_data_dir;
+
+#[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
 pub struct Site {
     pub id: Option<i64>,
@@ -15,30 +18,124 @@ pub struct Site {
     pub last_checked: Option<i64>,
     pub last_status: Option<String>,
     pub downtime_started: Option<i64>,
+    pub last_status_code: Option<u16>,
+    pub last_latency_ms: Option<i64>,
+    pub paused: bool,
+    // Request timeout in seconds; None uses the timeout from the config
+    pub timeout: Option<f64>,
+    // Push monitors only: the secret in their ping URL, how many seconds late
+    // a ping may be, and when the last one arrived
+    pub push_token: Option<String>,
+    pub grace: Option<f64>,
+    pub last_ping: Option<i64>,
+    pub created_at: Option<i64>,
+    // Command monitors only: the shell command and extra environment
+    // variables for it, as a JSON object
+    pub command: Option<String>,
+    pub env: Option<String>,
+    // Up, but the last check warned, like a Nagios plugin exiting with 1
+    pub degraded: bool,
 }
 
-fn get_db_path() -> PathBuf {
-    // Get user's home directory for data storage
-    let mut data_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-    data_dir.push(".isup");
-    
-    // Create the directory if it doesn't exist
-    if !data_dir.exists() {
-        let _ = fs::create_dir_all(&data_dir);
+impl Site {
+    // Request timeout for this site's checks, in seconds
+    pub fn timeout_secs(&self) -> f64 {
+        self.timeout.unwrap_or_else(|| config::current().timeout)
+    }
+
+    // Name of a push monitor, which waits for pings instead of checking a URL
+    pub fn push_name(&self) -> Option<&str> {
+        self.url.strip_prefix(PUSH_SCHEME)
+    }
+
+    pub fn state(&self) -> &'static str {
+        match self.is_up {
+            _ if self.paused => "paused",
+            Some(true) if self.degraded => "degraded",
+            Some(true) => "up",
+            Some(false) => "down",
+            None => "unknown",
+        }
+    }
+}
+
+// Limits on a site's check interval and request timeout, in seconds
+pub const MIN_INTERVAL: f64 = 0.1;
+pub const MAX_INTERVAL: f64 = 366.0 * 86400.0;
+pub const MAX_TIMEOUT: f64 = 3600.0;
+
+// Refuse an interval or timeout outside the limits above
+pub fn validate_settings(interval: f64, timeout: Option<f64>) -> Result<()> {
+    if !(MIN_INTERVAL..=MAX_INTERVAL).contains(&interval) {
+        return Err(anyhow::anyhow!(
+            "interval must be between {} and {} seconds",
+            MIN_INTERVAL,
+            MAX_INTERVAL
+        ));
     }
-    
+    if timeout.is_some_and(|timeout| !(timeout > 0.0 && timeout <= MAX_TIMEOUT)) {
+        return Err(anyhow::anyhow!(
+            "timeout must be greater than 0 and at most {} seconds",
+            MAX_TIMEOUT
+        ));
+    }
+    Ok(())
+}
+
+// Seconds as a Duration within [min, max], for values read back from the
+// database that may predate the limits
+pub fn bounded_duration(seconds: f64, min: f64, max: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds.clamp(min, max)).unwrap_or(Duration::from_secs_f64(max))
+}
+
+pub const PUSH_SCHEME: &str = "push://";
+
+// One entry in a site's check history
+#[derive(Debug, Clone)]
+pub struct CheckRecord {
+    pub checked_at: i64,
+    pub is_up: bool,
+    pub status_code: Option<u16>,
+    pub latency_ms: Option<i64>,
+    pub status: Option<String>,
+}
+
+// How long check history is kept
+pub const HISTORY_RETENTION_DAYS: i64 = 90;
+
+// Columns selected for every Site query, in the order site_from_row expects
+const SITE_COLUMNS: &str = "id, url, interval, notify_method, is_up, last_checked, last_status, downtime_started, \
+     last_status_code, last_latency_ms, paused, timeout, push_token, grace, last_ping, created_at, command, env, degraded";
+
+// Columns added after the original schema, created on demand for existing databases
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("last_status_code", "INTEGER"),
+    ("last_latency_ms", "INTEGER"),
+    ("paused", "INTEGER NOT NULL DEFAULT 0"),
+    ("timeout", "REAL"),
+    ("push_token", "TEXT"),
+    ("grace", "REAL"),
+    ("last_ping", "INTEGER"),
+    ("created_at", "INTEGER"),
+    ("command", "TEXT"),
+    ("env", "TEXT"),
+    ("degraded", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+fn get_db_path() -> PathBuf {
     // Add database file name
+    let mut data_dir = get_data_dir();
     data_dir.push("sites.db");
     data_dir
 }
 
 fn get_db_connection() -> Result<Connection> {
     let db_path = get_db_path();
-    
+
     // Open connection to SQLite database
     let conn = Connection::open(&db_path)
         .with_context(|| format!("Failed to open database at {:?}", db_path))?;
-    
+
     // Create tables if they don't exist
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sites (
@@ -53,77 +150,167 @@ fn get_db_connection() -> Result<Connection> {
         )",
         params![],
     )?;
-    
+
+    // Every check result, used for uptime, latency trends and incident history
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS check_history (
+            id INTEGER PRIMARY KEY,
+            site_id INTEGER NOT NULL,
+            checked_at INTEGER NOT NULL,
+            is_up INTEGER NOT NULL,
+            status_code INTEGER,
+            latency_ms INTEGER,
+            status TEXT
+        )",
+        params![],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_check_history_site ON check_history (site_id, checked_at)",
+        params![],
+    )?;
+
+    migrate(&conn)?;
+
     Ok(conn)
 }
 
+// Add any columns missing from databases created by older versions
+fn migrate(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("PRAGMA table_info(sites)")?;
+    let existing: Vec<String> = stmt
+        .query_map(params![], |row| row.get(1))?
+        .collect::<Result<_, _>>()?;
+
+    for (column, column_type) in MIGRATIONS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute(
+                &format!("ALTER TABLE sites ADD COLUMN {} {}", column, column_type),
+                params![],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn site_from_row(row: &Row) -> rusqlite::Result<Site> {
+    Ok(Site {
+        id: Some(row.get(0)?),
+        url: row.get(1)?,
+        interval: row.get(2)?,
+        notify_method: row.get(3)?,
+        is_up: row.get(4)?,
+        last_checked: row.get(5)?,
+        last_status: row.get(6)?,
+        downtime_started: row.get(7)?,
+        last_status_code: row.get(8)?,
+        last_latency_ms: row.get(9)?,
+        paused: row.get(10)?,
+        timeout: row.get(11)?,
+        push_token: row.get(12)?,
+        grace: row.get(13)?,
+        last_ping: row.get(14)?,
+        created_at: row.get(15)?,
+        command: row.get(16)?,
+        env: row.get(17)?,
+        degraded: row.get(18)?,
+    })
+}
+
 pub fn add_site_to_db(site: &Site) -> Result<i64> {
     let conn = get_db_connection()?;
-    
+
     // Insert new site record
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
     conn.execute(
-        "INSERT INTO sites (url, interval, notify_method) VALUES (?1, ?2, ?3)",
-        params![site.url, site.interval, site.notify_method],
+        "INSERT INTO sites (url, interval, notify_method, timeout, push_token, grace, created_at, command, env)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            site.url,
+            site.interval,
+            site.notify_method,
+            site.timeout,
+            site.push_token,
+            site.grace,
+            now,
+            site.command,
+            site.env
+        ],
     )?;
-    
+
     // Get the ID of the inserted record
     let id = conn.last_insert_rowid();
-    
+
     Ok(id)
 }
 
 pub fn get_site_by_url(url: &str) -> Result<Option<Site>> {
     let conn = get_db_connection()?;
-    
+
     // Query for site with the given URL
-    let mut stmt = conn.prepare(
-        "SELECT id, url, interval, notify_method, is_up, last_checked, last_status, downtime_started 
-         FROM sites 
-         WHERE url = ?1"
-    )?;
-    
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sites WHERE url = ?1",
+        SITE_COLUMNS
+    ))?;
+
     let mut rows = stmt.query(params![url])?;
-    
+
     // Process first row (if any)
     if let Some(row) = rows.next()? {
-        Ok(Some(Site {
-            id: Some(row.get(0)?),
-            url: row.get(1)?,
-            interval: row.get(2)?,
-            notify_method: row.get(3)?,
-            is_up: row.get(4)?,
-            last_checked: row.get(5)?,
-            last_status: row.get(6)?,
-            downtime_started: row.get(7)?,
-        }))
+        Ok(Some(site_from_row(row)?))
     } else {
         Ok(None)
     }
 }
 
+pub fn get_site_by_id(id: i64) -> Result<Option<Site>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM sites WHERE id = ?1", SITE_COLUMNS))?;
+
+    let mut rows = stmt.query(params![id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(site_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn get_site_by_push_token(token: &str) -> Result<Option<Site>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sites WHERE push_token = ?1",
+        SITE_COLUMNS
+    ))?;
+
+    let mut rows = stmt.query(params![token])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(site_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+// Note that a push monitor was pinged just now
+pub fn record_ping(url: &str) -> Result<bool> {
+    let conn = get_db_connection()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    let rows_affected = conn.execute(
+        "UPDATE sites SET last_ping = ?1 WHERE url = ?2",
+        params![now, url],
+    )?;
+
+    Ok(rows_affected > 0)
+}
+
 pub fn get_all_sites() -> Result<Vec<Site>> {
     let conn = get_db_connection()?;
-    
+
     // Query for all sites
-    let mut stmt = conn.prepare(
-        "SELECT id, url, interval, notify_method, is_up, last_checked, last_status, downtime_started 
-         FROM sites 
-         ORDER BY url"
-    )?;
-    
-    let site_iter = stmt.query_map(params![], |row| {
-        Ok(Site {
-            id: Some(row.get(0)?),
-            url: row.get(1)?,
-            interval: row.get(2)?,
-            notify_method: row.get(3)?,
-            is_up: row.get(4)?,
-            last_checked: row.get(5)?,
-            last_status: row.get(6)?,
-            downtime_started: row.get(7)?,
-        })
-    })?;
-    
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM sites ORDER BY url", SITE_COLUMNS))?;
+
+    let site_iter = stmt.query_map(params![], site_from_row)?;
+
     // Convert to Vec and filter out any errors
     let sites: Result<Vec<Site>, _> = site_iter.collect();
     Ok(sites?)
@@ -131,47 +318,257 @@ pub fn get_all_sites() -> Result<Vec<Site>> {
 
 pub fn remove_site_from_db(url: &str) -> Result<bool> {
     let conn = get_db_connection()?;
-    
-    // Delete site with the given URL
-    let rows_affected = conn.execute(
-        "DELETE FROM sites WHERE url = ?1",
+
+    // Delete the site's history along with it
+    conn.execute(
+        "DELETE FROM check_history WHERE site_id IN (SELECT id FROM sites WHERE url = ?1)",
         params![url],
     )?;
-    
+
+    // Delete site with the given URL
+    let rows_affected = conn.execute("DELETE FROM sites WHERE url = ?1", params![url])?;
+
     // Return success if at least one row was deleted
     Ok(rows_affected > 0)
 }
 
-pub fn update_site_status(url: &str, is_up: bool, status: &str) -> Result<()> {
+pub fn update_site_status(
+    url: &str,
+    is_up: bool,
+    degraded: bool,
+    status: &str,
+    status_code: Option<u16>,
+    latency_ms: Option<i64>,
+) -> Result<()> {
     let conn = get_db_connection()?;
     let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-    
+
     // Get existing site status for downtime tracking
     let existing_site = get_site_by_url(url)?;
+    let site_id = existing_site.as_ref().and_then(|site| site.id);
     let downtime_started = match (existing_site, is_up) {
         // Site was previously up but is now down - start downtime tracking
         (Some(site), false) if site.is_up == Some(true) => Some(now),
-        
+
         // Site was previously down and is still down - keep existing downtime
         (Some(site), false) => site.downtime_started,
-        
+
         // Site is up, so no downtime
         (_, true) => None,
-        
+
         // Default case - shouldn't happen but for safety
         _ => None,
     };
-    
+
     // Update the site status
     conn.execute(
-        "UPDATE sites 
-         SET is_up = ?1, 
-             last_checked = ?2, 
+        "UPDATE sites
+         SET is_up = ?1,
+             last_checked = ?2,
              last_status = ?3,
-             downtime_started = ?4
-         WHERE url = ?5",
-        params![is_up, now, status, downtime_started, url],
+             downtime_started = ?4,
+             last_status_code = ?5,
+             last_latency_ms = ?6,
+             degraded = ?7
+         WHERE url = ?8",
+        params![
+            is_up,
+            now,
+            status,
+            downtime_started,
+            status_code,
+            latency_ms,
+            degraded,
+            url
+        ],
     )?;
-    
+
+    // Keep a record of the check
+    if let Some(site_id) = site_id {
+        conn.execute(
+            "INSERT INTO check_history (site_id, checked_at, is_up, status_code, latency_ms, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![site_id, now, is_up, status_code, latency_ms, status],
+        )?;
+    }
+
     Ok(())
-} 
\ No newline at end of file
+}
+
+pub fn set_site_paused(url: &str, paused: bool) -> Result<bool> {
+    let conn = get_db_connection()?;
+
+    let rows_affected = conn.execute(
+        "UPDATE sites SET paused = ?1 WHERE url = ?2",
+        params![paused, url],
+    )?;
+
+    Ok(rows_affected > 0)
+}
+
+// Change how often a site is checked and its request timeout
+pub fn update_site_settings(url: &str, interval: f64, timeout: Option<f64>) -> Result<bool> {
+    let conn = get_db_connection()?;
+
+    let rows_affected = conn.execute(
+        "UPDATE sites SET interval = ?1, timeout = ?2 WHERE url = ?3",
+        params![interval, timeout, url],
+    )?;
+
+    Ok(rows_affected > 0)
+}
+
+// Most recent checks for a site since the given time, newest first
+pub fn get_site_history(site_id: i64, since: i64, limit: usize) -> Result<Vec<CheckRecord>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT checked_at, is_up, status_code, latency_ms, status
+         FROM check_history
+         WHERE site_id = ?1 AND checked_at >= ?2
+         ORDER BY checked_at DESC, id DESC
+         LIMIT ?3",
+    )?;
+
+    let records = stmt.query_map(params![site_id, since, limit as i64], |row| {
+        Ok(CheckRecord {
+            checked_at: row.get(0)?,
+            is_up: row.get(1)?,
+            status_code: row.get(2)?,
+            latency_ms: row.get(3)?,
+            status: row.get(4)?,
+        })
+    })?;
+
+    Ok(records.collect::<Result<Vec<_>, _>>()?)
+}
+
+// Number of checks and how many of them were up since the given time
+pub fn get_check_counts(site_id: i64, since: i64) -> Result<(i64, i64)> {
+    let conn = get_db_connection()?;
+
+    let counts = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(is_up), 0) FROM check_history WHERE site_id = ?1 AND checked_at >= ?2",
+        params![site_id, since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    Ok(counts)
+}
+
+// A stretch of consecutive failed checks
+#[derive(Debug, Clone)]
+pub struct Incident {
+    pub started: i64,
+    // When the first successful check after it ran; None while still down
+    pub ended: Option<i64>,
+    // Status of the first failed check
+    pub status: Option<String>,
+    pub failed_checks: i64,
+}
+
+impl Incident {
+    pub fn to_json(&self, now: i64) -> Value {
+        json!({
+            "started": self.started,
+            "ended": self.ended,
+            "duration_seconds": self.ended.unwrap_or(now) - self.started,
+            "status": self.status,
+            "failed_checks": self.failed_checks,
+        })
+    }
+}
+
+// Checks and successful checks per UTC day since the given time, oldest day first.
+// Days are numbered from the Unix epoch.
+pub fn get_daily_uptime(site_id: i64, since: i64) -> Result<Vec<(i64, i64, i64)>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT checked_at / 86400 AS day, COUNT(*), COALESCE(SUM(is_up), 0)
+         FROM check_history
+         WHERE site_id = ?1 AND checked_at >= ?2
+         GROUP BY day
+         ORDER BY day",
+    )?;
+
+    let days = stmt.query_map(params![site_id, since], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?;
+    Ok(days.collect::<Result<Vec<_>, _>>()?)
+}
+
+// Average latency per `bucket_secs` window since the given time, oldest first.
+// Windows are identified by their start time.
+pub fn get_latency_buckets(site_id: i64, since: i64, bucket_secs: i64) -> Result<Vec<(i64, f64)>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT (checked_at / ?3) * ?3 AS bucket, AVG(latency_ms)
+         FROM check_history
+         WHERE site_id = ?1 AND checked_at >= ?2 AND latency_ms IS NOT NULL
+         GROUP BY bucket
+         ORDER BY bucket",
+    )?;
+
+    let buckets = stmt.query_map(params![site_id, since, bucket_secs.max(1)], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    Ok(buckets.collect::<Result<Vec<_>, _>>()?)
+}
+
+// Outages found in a site's check history since the given time, newest first
+pub fn get_incidents(site_id: i64, since: i64) -> Result<Vec<Incident>> {
+    let conn = get_db_connection()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT checked_at, is_up, status
+         FROM check_history
+         WHERE site_id = ?1 AND checked_at >= ?2
+         ORDER BY checked_at, id",
+    )?;
+    let mut rows = stmt.query(params![site_id, since])?;
+
+    let mut incidents = Vec::new();
+    let mut current: Option<Incident> = None;
+    while let Some(row) = rows.next()? {
+        let checked_at: i64 = row.get(0)?;
+        let is_up: bool = row.get(1)?;
+
+        match (&mut current, is_up) {
+            (Some(incident), false) => incident.failed_checks += 1,
+            (Some(_), true) => {
+                if let Some(mut incident) = current.take() {
+                    incident.ended = Some(checked_at);
+                    incidents.push(incident);
+                }
+            }
+            (None, false) => {
+                current = Some(Incident {
+                    started: checked_at,
+                    ended: None,
+                    status: row.get(2)?,
+                    failed_checks: 1,
+                });
+            }
+            (None, true) => {}
+        }
+    }
+    incidents.extend(current);
+
+    incidents.reverse();
+    Ok(incidents)
+}
+
+// Drop history older than the retention period
+pub fn prune_history() -> Result<usize> {
+    let conn = get_db_connection()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    let rows_affected = conn.execute(
+        "DELETE FROM check_history WHERE checked_at < ?1",
+        params![now - HISTORY_RETENTION_DAYS * 86400],
+    )?;
+
+    Ok(rows_affected)
+}
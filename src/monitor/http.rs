@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde_json::json;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::monitor::config::LogLevel;
+use crate::monitor::log;
+
+// An HTTP server the daemon runs on an address from the config. Applying a
+// new address replaces the running server; None shuts it down.
+#[derive(Default)]
+pub struct Listener {
+    running: Mutex<Option<(SocketAddr, JoinHandle<()>)>>,
+}
+
+impl Listener {
+    // Must be called from inside the daemon's runtime
+    pub fn apply<H, F>(
+        &self,
+        name: &'static str,
+        addr: Option<SocketAddr>,
+        handler: H,
+    ) -> Result<()>
+    where
+        H: Fn(Request<Body>) -> F + Clone + Send + Sync + 'static,
+        F: Future<Output = Response<Body>> + Send + 'static,
+    {
+        let Ok(mut running) = self.running.lock() else {
+            return Ok(());
+        };
+        if running.as_ref().map(|(current, _)| *current) == addr {
+            return Ok(());
+        }
+
+        if let Some((old, task)) = running.take() {
+            task.abort();
+            log::event(
+                LogLevel::Info,
+                "server_stopped",
+                json!({ "server": name, "addr": old.to_string() }),
+            );
+        }
+        let Some(addr) = addr else {
+            return Ok(());
+        };
+
+        let make_service = make_service_fn(move |_| {
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let response = handler(request);
+                    async move { Ok::<_, Infallible>(response.await) }
+                }))
+            }
+        });
+        let server = Server::try_bind(&addr)
+            .with_context(|| format!("failed to listen for {} on {}", name, addr))?
+            .serve(make_service);
+
+        log::event(
+            LogLevel::Info,
+            "server_started",
+            json!({ "server": name, "addr": addr.to_string() }),
+        );
+        let task = tokio::spawn(async move {
+            if let Err(e) = server.await {
+                log::event(
+                    LogLevel::Error,
+                    "server_failed",
+                    json!({ "server": name, "error": e.to_string() }),
+                );
+            }
+        });
+        *running = Some((addr, task));
+        Ok(())
+    }
+}
+
+pub fn text_response(
+    status: StatusCode,
+    content_type: &str,
+    body: impl Into<Body>,
+) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", content_type)
+        .body(body.into())
+        .unwrap_or_default()
+}
+
+pub fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    text_response(status, "application/json", format!("{}\n", body))
+}
+
+pub fn not_found() -> Response<Body> {
+    text_response(
+        StatusCode::NOT_FOUND,
+        "text/plain; charset=utf-8",
+        "not found\n",
+    )
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+use crate::utils::get_data_dir;
+
+// The daemon holds an exclusive flock on this file for as long as it runs.
+// The kernel drops the lock when the process exits, however it exits, so a
+// leftover file never looks like a running daemon. The PID written inside is
+// only trusted while the lock is held, so a reused PID can't fool us either.
+// It sits next to the database it protects, so daemons started with different
+// environments still find each other's lock.
+pub fn get_lock_path() -> PathBuf {
+    get_data_dir().join("isupd.lock")
+}
+
+// How long acquire keeps trying while the CLI briefly holds a shared probe lock
+#[cfg(unix)]
+const ACQUIRE_RETRY: Duration = Duration::from_secs(1);
+
+// Held by the running daemon; the lock is released when this is dropped
+pub struct DaemonLock {
+    _file: File,
+}
+
+impl DaemonLock {
+    // Take the lock, or None if another daemon already holds it
+    pub fn acquire() -> Result<Option<Self>> {
+        Self::acquire_at(&get_lock_path())
+    }
+
+    #[cfg(unix)]
+    fn acquire_at(path: &Path) -> Result<Option<Self>> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("failed to open lock file {}", path.display()))?;
+
+        // A CLI checking for a daemon holds a shared lock for an instant;
+        // only a lock that stays taken means another daemon
+        let deadline = Instant::now() + ACQUIRE_RETRY;
+        while !try_lock(&file, libc::LOCK_EX)? {
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Only rewrite the PID once the lock is ours
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Some(DaemonLock { _file: file }))
+    }
+
+    // Without flock there is nothing to hold
+    #[cfg(not(unix))]
+    fn acquire_at(path: &Path) -> Result<Option<Self>> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to open lock file {}", path.display()))?;
+        Ok(Some(DaemonLock { _file: file }))
+    }
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File, mode: libc::c_int) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), mode | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+
+    let error = std::io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(error).context("failed to lock daemon lock file")
+    }
+}
+
+// Whether a daemon currently holds the lock
+pub fn is_locked() -> bool {
+    is_locked_at(&get_lock_path())
+}
+
+#[cfg(unix)]
+fn is_locked_at(path: &Path) -> bool {
+    // A shared lock only fails against the daemon's exclusive one. If it's free we
+    // hold it for a moment, which acquire waits out; closing the file releases it.
+    match File::open(path) {
+        Ok(file) => matches!(try_lock(&file, libc::LOCK_SH), Ok(false)),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_locked_at(_path: &Path) -> bool {
+    false
+}
+
+// PID of the daemon holding the lock, if one is running
+pub fn daemon_pid() -> Option<u32> {
+    daemon_pid_at(&get_lock_path())
+}
+
+fn daemon_pid_at(path: &Path) -> Option<u32> {
+    if !is_locked_at(path) {
+        return None;
+    }
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_holder_at_a_time() {
+        let path = std::env::temp_dir().join(format!("isup-lock-test-{}.lock", std::process::id()));
+        // A stale PID from an earlier run must not count
+        std::fs::write(&path, "1").unwrap();
+        assert!(!is_locked_at(&path));
+        assert_eq!(daemon_pid_at(&path), None);
+
+        let held = DaemonLock::acquire_at(&path)
+            .unwrap()
+            .expect("lock is free");
+        assert!(is_locked_at(&path));
+        assert_eq!(daemon_pid_at(&path), Some(std::process::id()));
+        assert!(DaemonLock::acquire_at(&path).unwrap().is_none());
+
+        drop(held);
+        assert!(!is_locked_at(&path));
+        assert!(DaemonLock::acquire_at(&path).unwrap().is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
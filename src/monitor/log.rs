@@ -0,0 +1,502 @@
+use chrono::{DateTime, FixedOffset};
+use serde_json::{Map, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::monitor::config::{self, LogLevel};
+use crate::monitor::systemd;
+use crate::utils::get_data_dir;
+
+// Structured logging for the daemon.
+//
+// Every entry is an event name plus fields, written as one line. In the text
+// format a line looks like
+//
+//     2026-10-18T09:12:03.511+02:00 INFO check_result url=https://example.com up=true status_code=200
+//
+// and in the JSON format it is an object with "ts", "level" and "event" next
+// to the same fields. Lines go to files under ~/.isup/logs, which are rotated
+// by size, and also to stdout when the daemon runs in the foreground.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFile {
+    // Daemon lifecycle and check events
+    #[value(name = "checks")]
+    Daemon,
+    // Notification deliveries, also written by CLI commands that check sites
+    Notifications,
+}
+
+impl LogFile {
+    pub fn path(self) -> PathBuf {
+        let name = match self {
+            LogFile::Daemon => "daemon.log",
+            LogFile::Notifications => "notifications.log",
+        };
+        get_log_dir().join(name)
+    }
+
+    // Path of the nth rotated file, e.g. daemon.log.1
+    pub fn rotated_path(self, n: usize) -> PathBuf {
+        let mut path = self.path().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    // Every file of this log that exists, oldest first
+    pub fn existing_paths(self) -> Vec<PathBuf> {
+        (1..=KEPT_LOGS)
+            .rev()
+            .map(|n| self.rotated_path(n))
+            .chain(std::iter::once(self.path()))
+            .filter(|path| path.exists())
+            .collect()
+    }
+}
+
+// Start a new file once the current one reaches this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+// Rotated files kept next to the current one
+const KEPT_LOGS: usize = 3;
+
+pub fn get_log_dir() -> PathBuf {
+    let dir = get_data_dir().join("logs");
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+struct Settings {
+    format: LogFormat,
+    // Overrides log_level from the config when set
+    level: Option<LogLevel>,
+    foreground: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+// Keeps lines from one process whole and in order while a file is rotated
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+// Turn on daemon logging. Until this is called only notification events are
+// written, so CLI commands that reuse the daemon's check code stay quiet.
+pub fn init(format: LogFormat, level: Option<LogLevel>, foreground: bool) {
+    let _ = SETTINGS.set(Settings {
+        format,
+        level,
+        foreground,
+    });
+}
+
+// Whether events at this level are written
+pub fn enabled(level: LogLevel) -> bool {
+    SETTINGS.get().is_some() && level <= max_level()
+}
+
+fn max_level() -> LogLevel {
+    SETTINGS
+        .get()
+        .and_then(|settings| settings.level)
+        .unwrap_or_else(|| config::current().log_level)
+}
+
+// Log a daemon event; `fields` is a JSON object and null fields are left out
+pub fn event(level: LogLevel, name: &str, fields: Value) {
+    if !enabled(level) {
+        return;
+    }
+    write_event(LogFile::Daemon, level, name, fields);
+}
+
+// Log a notification event to the notifications log, and to the daemon's own log when running in it
+pub fn notification(level: LogLevel, name: &str, fields: Value) {
+    if level > max_level() {
+        return;
+    }
+    if SETTINGS.get().is_some() {
+        write_event(LogFile::Daemon, level, name, fields.clone());
+    }
+    write_event(LogFile::Notifications, level, name, fields);
+}
+
+fn write_event(file: LogFile, level: LogLevel, name: &str, fields: Value) {
+    let format = SETTINGS
+        .get()
+        .map(|settings| settings.format)
+        .unwrap_or(LogFormat::Text);
+    let timestamp = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+    let fields = match fields {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+
+    let line = match format {
+        LogFormat::Text => format_text(&timestamp, level, name, &fields),
+        LogFormat::Json => format_json(&timestamp, level, name, &fields),
+    };
+
+    let _guard = WRITE_LOCK.lock();
+
+    if file == LogFile::Daemon && SETTINGS.get().is_some_and(|settings| settings.foreground) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = if systemd::logging_to_journal() {
+            // journald reads the <N> prefix as the message priority
+            writeln!(stdout, "<{}>{}", level.syslog_priority(), line)
+        } else {
+            writeln!(stdout, "{}", line)
+        };
+        let _ = stdout.flush();
+    }
+
+    append_line(file, &line);
+}
+
+// Fields in the order they are written: the site first, then alphabetical
+fn ordered(fields: &Map<String, Value>) -> impl Iterator<Item = (&String, &Value)> {
+    let url = fields.get_key_value("url");
+    url.into_iter()
+        .chain(fields.iter().filter(|(key, _)| key.as_str() != "url"))
+        .filter(|(_, value)| !value.is_null())
+}
+
+fn format_text(
+    timestamp: &str,
+    level: LogLevel,
+    name: &str,
+    fields: &Map<String, Value>,
+) -> String {
+    let mut line = format!(
+        "{} {:<5} {}",
+        timestamp,
+        level.as_str().to_uppercase(),
+        name
+    );
+    for (key, value) in ordered(fields) {
+        let value = match value {
+            // Quoted when it would otherwise read back as something else
+            Value::String(text)
+                if text.is_empty()
+                    || text.contains(|c: char| c.is_whitespace() || c == '"' || c == '=')
+                    || serde_json::from_str::<Value>(text).is_ok()
+                    || text.starts_with(['[', '{']) =>
+            {
+                Value::String(text.clone()).to_string()
+            }
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line
+}
+
+// Written by hand so ts, level and event lead every line
+fn format_json(
+    timestamp: &str,
+    level: LogLevel,
+    name: &str,
+    fields: &Map<String, Value>,
+) -> String {
+    let mut line = format!(
+        "{{\"ts\":{},\"level\":{},\"event\":{}",
+        Value::from(timestamp),
+        Value::from(level.as_str()),
+        Value::from(name)
+    );
+    for (key, value) in ordered(fields) {
+        line.push_str(&format!(",{}:{}", Value::from(key.as_str()), value));
+    }
+    line.push('}');
+    line
+}
+
+// The file is opened for every line, so a CLI process and the daemon can both
+// append to the notifications log and notice each other's rotations
+fn append_line(file: LogFile, line: &str) {
+    let path = file.path();
+    let Some(mut out) = open_locked(&path) else {
+        return;
+    };
+    let size = out.metadata().map(|meta| meta.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 + 1 > MAX_LOG_BYTES {
+        rotate(file);
+        match open_locked(&path) {
+            Some(fresh) => out = fresh,
+            None => return,
+        }
+    }
+    let _ = writeln!(out, "{}", line);
+}
+
+// Open a log for appending and lock it against other processes until the
+// handle is dropped. Whoever holds the lock may rotate the file, so a lock
+// won on a file that was renamed meanwhile is dropped and the new one opened.
+#[cfg(unix)]
+fn open_locked(path: &Path) -> Option<File> {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        let out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .ok()?;
+        if unsafe { libc::flock(out.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Some(out);
+        }
+        let current = fs::metadata(path).ok().map(|meta| (meta.dev(), meta.ino()));
+        let opened = out.metadata().ok().map(|meta| (meta.dev(), meta.ino()));
+        if current.is_some() && current == opened {
+            return Some(out);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn open_locked(path: &Path) -> Option<File> {
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+// daemon.log becomes daemon.log.1, .1 becomes .2, and so on; the oldest is dropped
+fn rotate(file: LogFile) {
+    let _ = fs::remove_file(file.rotated_path(KEPT_LOGS));
+    for n in (1..KEPT_LOGS).rev() {
+        let _ = fs::rename(file.rotated_path(n), file.rotated_path(n + 1));
+    }
+    let _ = fs::rename(file.path(), file.rotated_path(1));
+}
+
+// One line read back from a log file
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub timestamp: DateTime<FixedOffset>,
+    pub level: LogLevel,
+    pub event: String,
+    pub fields: Map<String, Value>,
+}
+
+impl LogEntry {
+    pub fn url(&self) -> Option<&str> {
+        self.fields.get("url").and_then(Value::as_str)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut record = self.fields.clone();
+        record.insert("ts".to_string(), Value::from(self.timestamp.to_rfc3339()));
+        record.insert("level".to_string(), Value::from(self.level.as_str()));
+        record.insert("event".to_string(), Value::from(self.event.as_str()));
+        Value::Object(record)
+    }
+}
+
+// Parse a line in either log format. Anything else, like a panic message, gives None.
+pub fn parse_line(line: &str) -> Option<LogEntry> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        parse_json_line(line)
+    } else {
+        parse_text_line(line)
+    }
+}
+
+fn parse_json_line(line: &str) -> Option<LogEntry> {
+    let Value::Object(mut fields) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+    let timestamp = DateTime::parse_from_rfc3339(fields.remove("ts")?.as_str()?).ok()?;
+    let level = fields.remove("level")?.as_str()?.parse().ok()?;
+    let event = fields.remove("event")?.as_str()?.to_string();
+    Some(LogEntry {
+        timestamp,
+        level,
+        event,
+        fields,
+    })
+}
+
+fn parse_text_line(line: &str) -> Option<LogEntry> {
+    let (timestamp, rest) = line.split_once(char::is_whitespace)?;
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    let (level, rest) = rest.trim_start().split_once(char::is_whitespace)?;
+    let level = level.parse().ok()?;
+    let rest = rest.trim_start();
+    let (event, mut rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    let mut fields = Map::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let (key, value) = rest.split_once('=')?;
+
+        // Quoted strings, lists and objects are JSON and may contain spaces;
+        // anything else, or something that only looks like JSON, runs to the next space
+        let raw = value.split(char::is_whitespace).next().unwrap_or_default();
+        let json = value.starts_with(['"', '[', '{']).then(|| {
+            let mut values = serde_json::Deserializer::from_str(value).into_iter::<Value>();
+            let parsed = values.next()?.ok()?;
+            let len = values.byte_offset();
+            value[len..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+                .then_some((parsed, len))
+        });
+        let (value, len) = match json.flatten() {
+            Some(json) => json,
+            None => (
+                serde_json::from_str(raw).unwrap_or_else(|_| Value::from(raw)),
+                raw.len(),
+            ),
+        };
+
+        fields.insert(key.to_string(), value);
+        rest = &rest[key.len() + 1 + len..];
+    }
+
+    Some(LogEntry {
+        timestamp,
+        level,
+        event: event.to_string(),
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TS: &str = "2026-10-18T09:12:03.511+02:00";
+
+    fn fields(value: Value) -> Map<String, Value> {
+        let Value::Object(fields) = value else {
+            unreachable!()
+        };
+        fields
+    }
+
+    #[test]
+    fn text_lines_lead_with_the_url_and_skip_nulls() {
+        let fields = fields(json!({
+            "up": true,
+            "url": "https://example.com",
+            "error": null,
+            "status_code": 200,
+        }));
+        assert_eq!(
+            format_text(TS, LogLevel::Info, "check_result", &fields),
+            format!(
+                "{} INFO  check_result url=https://example.com status_code=200 up=true",
+                TS
+            )
+        );
+    }
+
+    #[test]
+    fn text_values_are_quoted_only_when_needed() {
+        let fields = fields(json!({
+            "plain": "timeout",
+            "spaced": "Service Unavailable",
+            "empty": "",
+            "numeric": "200",
+            "bracketed": "[not json",
+            "list": ["a", "b c"],
+        }));
+        let line = format_text(TS, LogLevel::Warn, "x", &fields);
+        assert!(line.contains(" plain=timeout"), "{}", line);
+        assert!(line.contains(" spaced=\"Service Unavailable\""), "{}", line);
+        assert!(line.contains(" empty=\"\""), "{}", line);
+        assert!(line.contains(" numeric=\"200\""), "{}", line);
+        assert!(line.contains(" bracketed=\"[not json\""), "{}", line);
+        assert!(line.contains(" list=[\"a\",\"b c\"]"), "{}", line);
+    }
+
+    #[test]
+    fn json_lines_are_objects_led_by_ts_level_and_event() {
+        let fields = fields(json!({ "url": "https://example.com", "up": false, "error": null }));
+        let line = format_json(TS, LogLevel::Warn, "check_result", &fields);
+        assert!(line.starts_with(&format!(
+            "{{\"ts\":\"{}\",\"level\":\"warn\",\"event\":\"check_result\"",
+            TS
+        )));
+        let parsed: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["url"], "https://example.com");
+        assert_eq!(parsed["up"], false);
+        assert!(parsed.get("error").is_none());
+    }
+
+    fn sample() -> Map<String, Value> {
+        fields(json!({
+            "url": "https://example.com/a?b=c",
+            "up": false,
+            "status_code": 503,
+            "latency_ms": 12.5,
+            "status": "Service Unavailable",
+            "empty": "",
+            "plain": "timeout",
+            "bracketed": "[not json",
+            "braced": "{half",
+            "numeric": "200",
+            "boolean": "true",
+            "quoted": "say \"hi\"",
+            "list": ["a", "b c"],
+            "object": { "k": 1 },
+        }))
+    }
+
+    fn assert_round_trip(line: &str) {
+        let entry = parse_line(line).unwrap_or_else(|| panic!("unparsed: {}", line));
+        assert_eq!(entry.timestamp, DateTime::parse_from_rfc3339(TS).unwrap());
+        assert_eq!(entry.level, LogLevel::Warn);
+        assert_eq!(entry.event, "check_result");
+        assert_eq!(entry.fields, sample(), "{}", line);
+    }
+
+    #[test]
+    fn text_lines_round_trip() {
+        assert_round_trip(&format_text(TS, LogLevel::Warn, "check_result", &sample()));
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        assert_round_trip(&format_json(TS, LogLevel::Warn, "check_result", &sample()));
+    }
+
+    #[test]
+    fn null_fields_are_left_out() {
+        let mut with_null = sample();
+        with_null.insert("error".to_string(), Value::Null);
+        assert_round_trip(&format_text(TS, LogLevel::Warn, "check_result", &with_null));
+    }
+
+    #[test]
+    fn values_that_only_look_like_json_are_plain_strings() {
+        let line = format!(
+            "{} INFO check_result status=[oops detail={{x error=\"open reason=[1]x ok=[1,2]",
+            TS
+        );
+        let entry = parse_line(&line).unwrap();
+        assert_eq!(entry.fields["status"], "[oops");
+        assert_eq!(entry.fields["detail"], "{x");
+        assert_eq!(entry.fields["error"], "\"open");
+        assert_eq!(entry.fields["reason"], "[1]x");
+        assert_eq!(entry.fields["ok"], json!([1, 2]));
+    }
+
+    #[test]
+    fn other_lines_are_not_entries() {
+        assert!(parse_line("thread 'main' panicked at src/main.rs:1:1").is_none());
+        assert!(parse_line("").is_none());
+        assert!(parse_line(&format!("{} NOPE event", TS)).is_none());
+        assert!(parse_line("{\"ts\":\"yesterday\",\"level\":\"info\",\"event\":\"x\"}").is_none());
+    }
+}
@@ -0,0 +1,486 @@
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::monitor::config;
+use crate::monitor::http::{not_found, text_response};
+use crate::monitor::service::DaemonState;
+
+// Counters and gauges for the Prometheus endpoint. Checks record into one
+// global registry; it is rendered in the text exposition format on each scrape
+// of /metrics.
+
+// Upper bounds of the check duration buckets, in seconds
+const DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+// How late the loop started due checks, in seconds
+const LAG_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+// Certificates are looked at again after this long
+pub const CERT_PROBE_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+struct Histogram {
+    bounds: &'static [f64],
+    // One count per bound; +Inf is `count`
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: Vec::new(),
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        self.buckets.resize(self.bounds.len(), 0);
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter_mut()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (index, bound) in self.bounds.iter().enumerate() {
+            let count = self.buckets.get(index).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let braces = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braces, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braces, self.count);
+    }
+}
+
+#[derive(Default)]
+struct SiteMetrics {
+    up: bool,
+    status_code: Option<u16>,
+    checks: u64,
+    // Failed checks by error class
+    failures: BTreeMap<&'static str, u64>,
+    duration: Option<Histogram>,
+    cert_expiry_days: Option<f64>,
+    cert_probed: Option<Instant>,
+}
+
+struct Registry {
+    sites: BTreeMap<String, SiteMetrics>,
+    // Failed deliveries by notification channel
+    notification_failures: BTreeMap<String, u64>,
+    skipped_checks: u64,
+    scheduler_lag: Histogram,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    sites: BTreeMap::new(),
+    notification_failures: BTreeMap::new(),
+    skipped_checks: 0,
+    scheduler_lag: Histogram::new(LAG_BUCKETS),
+});
+
+fn with_registry(update: impl FnOnce(&mut Registry)) {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        update(&mut registry);
+    }
+}
+
+// Record a finished check; `failure` is the error class when it failed
+pub fn record_check(
+    url: &str,
+    up: bool,
+    status_code: Option<u16>,
+    duration: Duration,
+    failure: Option<&'static str>,
+) {
+    with_registry(|registry| {
+        let site = registry.sites.entry(url.to_string()).or_default();
+        site.up = up;
+        site.status_code = status_code;
+        site.checks += 1;
+        if let Some(class) = failure {
+            *site.failures.entry(class).or_default() += 1;
+        }
+        site.duration
+            .get_or_insert_with(|| Histogram::new(DURATION_BUCKETS))
+            .observe(duration.as_secs_f64());
+    });
+}
+
+pub fn record_skipped_check() {
+    with_registry(|registry| registry.skipped_checks += 1);
+}
+
+pub fn record_scheduler_lag(lag: Duration) {
+    with_registry(|registry| registry.scheduler_lag.observe(lag.as_secs_f64()));
+}
+
+pub fn record_notification_failure(channel: &str) {
+    with_registry(|registry| {
+        *registry
+            .notification_failures
+            .entry(channel.to_string())
+            .or_default() += 1
+    });
+}
+
+// Whether the site's certificate should be looked at again. Marks it as
+// probed, so concurrent checks of the same site don't both do it.
+pub fn cert_probe_due(url: &str) -> bool {
+    let mut due = false;
+    with_registry(|registry| {
+        let site = registry.sites.entry(url.to_string()).or_default();
+        if site
+            .cert_probed
+            .is_none_or(|at| at.elapsed() >= CERT_PROBE_INTERVAL)
+        {
+            site.cert_probed = Some(Instant::now());
+            due = true;
+        }
+    });
+    due
+}
+
+pub fn record_cert_expiry(url: &str, days: f64) {
+    with_registry(|registry| {
+        registry
+            .sites
+            .entry(url.to_string())
+            .or_default()
+            .cert_expiry_days = Some(days)
+    });
+}
+
+// Forget sites that are no longer checked, so their series disappear
+pub fn retain_sites(urls: &HashSet<&str>) {
+    with_registry(|registry| registry.sites.retain(|url, _| urls.contains(url.as_str())));
+}
+
+// Error class of a failed request, for isup_check_failures_total
+pub fn classify_error(error: &anyhow::Error) -> &'static str {
+    let Some(error) = error.downcast_ref::<reqwest::Error>() else {
+        return "other";
+    };
+    if error.is_timeout() {
+        return "timeout";
+    }
+    if error.is_redirect() {
+        return "redirect";
+    }
+
+    // reqwest wraps the underlying failure, so look through the whole chain
+    let mut chain = String::new();
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(error) = source {
+        chain.push_str(&error.to_string().to_lowercase());
+        chain.push(' ');
+        source = error.source();
+    }
+
+    if chain.contains("dns error") || chain.contains("failed to lookup") {
+        "dns"
+    } else if chain.contains("certificate") || chain.contains("ssl") || chain.contains("tls") {
+        "tls"
+    } else if error.is_connect() {
+        "connect"
+    } else {
+        "request"
+    }
+}
+
+// Serve GET /metrics; everything else is a 404
+pub async fn handle(state: Arc<DaemonState>, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        return not_found();
+    }
+    let body = render(&state).await;
+    text_response(
+        StatusCode::OK,
+        "text/plain; version=0.0.4; charset=utf-8",
+        body,
+    )
+}
+
+async fn render(state: &DaemonState) -> String {
+    let tracked = state.scheduler.lock().await.iter().count();
+    let mut out = String::new();
+
+    let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    };
+
+    header(
+        &mut out,
+        "isup_daemon_info",
+        "gauge",
+        "Version of the running daemon.",
+    );
+    let _ = writeln!(
+        out,
+        "isup_daemon_info{{version=\"{}\"}} 1",
+        env!("CARGO_PKG_VERSION")
+    );
+    header(
+        &mut out,
+        "isup_daemon_uptime_seconds",
+        "gauge",
+        "Seconds since the daemon started.",
+    );
+    let _ = writeln!(
+        out,
+        "isup_daemon_uptime_seconds {}",
+        state.started_at.elapsed().as_secs_f64()
+    );
+    header(
+        &mut out,
+        "isup_daemon_tracked_sites",
+        "gauge",
+        "Sites the scheduler is checking.",
+    );
+    let _ = writeln!(out, "isup_daemon_tracked_sites {}", tracked);
+    header(
+        &mut out,
+        "isup_daemon_in_flight_checks",
+        "gauge",
+        "Checks running or waiting for a slot.",
+    );
+    let _ = writeln!(
+        out,
+        "isup_daemon_in_flight_checks {}",
+        state.in_flight_count()
+    );
+    header(
+        &mut out,
+        "isup_daemon_max_concurrent_checks",
+        "gauge",
+        "Checks allowed to run at once.",
+    );
+    let _ = writeln!(
+        out,
+        "isup_daemon_max_concurrent_checks {}",
+        config::current().max_concurrent_checks
+    );
+
+    let Ok(registry) = REGISTRY.lock() else {
+        return out;
+    };
+
+    header(
+        &mut out,
+        "isup_scheduler_lag_seconds",
+        "histogram",
+        "How long after its due time each scheduled check started.",
+    );
+    registry
+        .scheduler_lag
+        .render(&mut out, "isup_scheduler_lag_seconds", "");
+    header(
+        &mut out,
+        "isup_skipped_checks_total",
+        "counter",
+        "Checks skipped because the previous check of the site was still running.",
+    );
+    let _ = writeln!(out, "isup_skipped_checks_total {}", registry.skipped_checks);
+
+    header(
+        &mut out,
+        "isup_notification_failures_total",
+        "counter",
+        "Notifications a channel failed to deliver.",
+    );
+    for (channel, count) in &registry.notification_failures {
+        let _ = writeln!(
+            out,
+            "isup_notification_failures_total{{channel=\"{}\"}} {}",
+            escape(channel),
+            count
+        );
+    }
+
+    let sites: Vec<(String, &SiteMetrics)> = registry
+        .sites
+        .iter()
+        .filter(|(_, site)| site.checks > 0)
+        .map(|(url, site)| (format!("url=\"{}\"", escape(url)), site))
+        .collect();
+
+    header(
+        &mut out,
+        "isup_site_up",
+        "gauge",
+        "Whether the last check of the site succeeded.",
+    );
+    for (labels, site) in &sites {
+        let _ = writeln!(out, "isup_site_up{{{}}} {}", labels, site.up as u8);
+    }
+    header(
+        &mut out,
+        "isup_site_status_code",
+        "gauge",
+        "HTTP status code of the last check, 0 if there was no response.",
+    );
+    for (labels, site) in &sites {
+        let _ = writeln!(
+            out,
+            "isup_site_status_code{{{}}} {}",
+            labels,
+            site.status_code.unwrap_or(0)
+        );
+    }
+    header(
+        &mut out,
+        "isup_checks_total",
+        "counter",
+        "Checks run against the site.",
+    );
+    for (labels, site) in &sites {
+        let _ = writeln!(out, "isup_checks_total{{{}}} {}", labels, site.checks);
+    }
+    header(
+        &mut out,
+        "isup_check_failures_total",
+        "counter",
+        "Failed checks by error class.",
+    );
+    for (labels, site) in &sites {
+        for (class, count) in &site.failures {
+            let _ = writeln!(
+                out,
+                "isup_check_failures_total{{{},class=\"{}\"}} {}",
+                labels, class, count
+            );
+        }
+    }
+    header(
+        &mut out,
+        "isup_check_duration_seconds",
+        "histogram",
+        "How long checks of the site took.",
+    );
+    for (labels, site) in &sites {
+        if let Some(duration) = &site.duration {
+            duration.render(&mut out, "isup_check_duration_seconds", labels);
+        }
+    }
+    header(
+        &mut out,
+        "isup_cert_expiry_days",
+        "gauge",
+        "Days until the site's TLS certificate expires.",
+    );
+    for (labels, site) in &sites {
+        if let Some(days) = site.cert_expiry_days {
+            let _ = writeln!(out, "isup_cert_expiry_days{{{}}} {:.3}", labels, days);
+        }
+    }
+
+    out
+}
+
+// Escape a label value for the exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[0.1, 1.0, 10.0]);
+        for value in [0.05, 0.1, 0.5, 2.0, 60.0] {
+            histogram.observe(value);
+        }
+
+        let mut out = String::new();
+        histogram.render(&mut out, "x_seconds", "");
+        assert_eq!(
+            out,
+            "x_seconds_bucket{le=\"0.1\"} 2\n\
+             x_seconds_bucket{le=\"1\"} 3\n\
+             x_seconds_bucket{le=\"10\"} 4\n\
+             x_seconds_bucket{le=\"+Inf\"} 5\n\
+             x_seconds_sum 62.65\n\
+             x_seconds_count 5\n"
+        );
+    }
+
+    #[test]
+    fn histogram_labels_go_before_le_and_on_sum_and_count() {
+        let mut histogram = Histogram::new(&[1.0]);
+        histogram.observe(0.5);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "x_seconds", "url=\"https://a.test\"");
+        assert_eq!(
+            out,
+            "x_seconds_bucket{url=\"https://a.test\",le=\"1\"} 1\n\
+             x_seconds_bucket{url=\"https://a.test\",le=\"+Inf\"} 1\n\
+             x_seconds_sum{url=\"https://a.test\"} 0.5\n\
+             x_seconds_count{url=\"https://a.test\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn empty_histograms_render_zeros() {
+        let mut out = String::new();
+        Histogram::new(&[1.0]).render(&mut out, "x", "");
+        assert_eq!(
+            out,
+            "x_bucket{le=\"1\"} 0\nx_bucket{le=\"+Inf\"} 0\nx_sum 0\nx_count 0\n"
+        );
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape("https://a.test/?q=1"), "https://a.test/?q=1");
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[tokio::test]
+    async fn sites_no_longer_checked_drop_out_of_the_output() {
+        let (kept, dropped) = ("https://kept.metrics.test", "https://dropped.metrics.test");
+        record_check(kept, true, Some(200), Duration::from_millis(20), None);
+        record_check(dropped, false, None, Duration::from_secs(1), Some("dns"));
+
+        let state = DaemonState::for_tests();
+        let out = render(&state).await;
+        assert!(out.contains(&format!("isup_site_up{{url=\"{}\"}} 1\n", kept)));
+        assert!(out.contains(&format!(
+            "isup_check_failures_total{{url=\"{}\",class=\"dns\"}} 1\n",
+            dropped
+        )));
+
+        retain_sites(&HashSet::from([kept]));
+        let out = render(&state).await;
+        assert!(out.contains(&format!("isup_site_status_code{{url=\"{}\"}} 200\n", kept)));
+        assert!(!out.contains(dropped));
+    }
+}
@@ -1,4 +1,20 @@
+pub mod api;
+pub mod cert;
+pub mod commands;
+pub mod config;
+pub mod control;
+pub mod dashboard;
 pub mod db;
+pub mod http;
+pub mod lock;
+pub mod log;
+pub mod metrics;
+pub mod notifier;
+pub mod probe;
+pub mod scheduler;
 pub mod service;
-pub mod commands;
-pub mod notifier; 
\ No newline at end of file
+pub mod statuspage;
+pub mod systemd;
+pub mod tcp;
+pub mod udp;
+pub mod watch;
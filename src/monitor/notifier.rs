@@ -1,8 +1,9 @@
-use anyhow::{Result, anyhow};
+use anyhow::{anyhow, Result};
+use serde_json::json;
 use std::fmt;
-use std::fs::OpenOptions;
-use std::io::Write;
-use chrono;
+
+use crate::monitor::config::{self, LogLevel};
+use crate::monitor::{log, metrics};
 
 // We'll define a trait for notifications to standardize the interface
 trait Notifier: fmt::Debug {
@@ -10,7 +11,6 @@ trait Notifier: fmt::Debug {
     fn name(&self) -> &'static str;
 }
 
-
 // Notification service that manages multiple notifiers
 pub struct NotificationService {
     notifiers: Vec<Box<dyn Notifier>>,
@@ -21,45 +21,46 @@ impl NotificationService {
         let mut service = NotificationService {
             notifiers: Vec::new(),
         };
-        
-        // Add notifica notifier as the primary notifier
-        service.add_notifier(Box::new(NotificaNotifier::new()));
-        
-        // Add fallback notifier for all platforms
-        service.add_notifier(Box::new(ConsoleNotifier::new()));
-        
+
+        // Channels come from the config, in the order they should be tried
+        for channel in &config::current().notifiers {
+            match channel.as_str() {
+                "device" => service.add_notifier(Box::new(NotificaNotifier::new())),
+                "console" => service.add_notifier(Box::new(ConsoleNotifier::new())),
+                _ => {}
+            }
+        }
+
         service
     }
-    
+
     fn add_notifier(&mut self, notifier: Box<dyn Notifier>) {
         self.notifiers.push(notifier);
     }
-    
-    pub fn send_notification(&self, title: &str, body: &str) -> Result<()> {
-        log_to_file(&format!("Attempting to send notification: '{}' - '{}'", title, body));
-        
+
+    // Returns the channel that delivered the notification
+    pub fn send_notification(&self, title: &str, body: &str) -> Result<&'static str> {
         // Try each notifier in order until one succeeds
         for notifier in &self.notifiers {
-            log_to_file(&format!("Trying notifier: {}", notifier.name()));
             match notifier.notify(title, body) {
-                Ok(()) => {
-                    log_to_file(&format!("Notification successful with {}", notifier.name()));
-                    return Ok(());
-                },
+                Ok(()) => return Ok(notifier.name()),
                 Err(e) => {
-                    log_to_file(&format!("Notification with {} failed: {}", notifier.name(), e));
+                    metrics::record_notification_failure(notifier.name());
+                    log::notification(
+                        LogLevel::Warn,
+                        "notify_channel_failed",
+                        json!({ "channel": notifier.name(), "title": title, "error": e.to_string() }),
+                    );
                     // Continue to next notifier
                 }
             }
         }
-        
+
         // If we get here, all notifiers failed
-        log_to_file("All notification methods failed");
         Err(anyhow!("All notification methods failed"))
     }
 }
 
-
 #[derive(Debug)]
 struct NotificaNotifier {}
 
@@ -71,22 +72,11 @@ impl NotificaNotifier {
 
 impl Notifier for NotificaNotifier {
     fn notify(&self, title: &str, body: &str) -> Result<()> {
-        log_to_file(&format!("Sending notification via notifica: {} - {}", title, body));
-        
-        match notifica::notify(title, body) {
-            Ok(_) => {
-                log_to_file("Notification sent successfully with notifica");
-                Ok(())
-            },
-            Err(e) => {
-                log_to_file(&format!("Notifica notification failed: {}", e));
-                Err(anyhow!("Notifica notification failed: {}", e))
-            }
-        }
+        notifica::notify(title, body).map_err(|e| anyhow!("Notifica notification failed: {}", e))
     }
-    
+
     fn name(&self) -> &'static str {
-        "Notifica"
+        "device"
     }
 }
 
@@ -102,50 +92,59 @@ impl ConsoleNotifier {
 
 impl Notifier for ConsoleNotifier {
     fn notify(&self, title: &str, body: &str) -> Result<()> {
-        println!("\n{}", "!".repeat(50));
-        println!("{}", title);
-        println!("{}", body);
-        println!("{}\n", "!".repeat(50));
+        say!("\n{}", "!".repeat(50));
+        say!("{}", title);
+        say!("{}", body);
+        say!("{}\n", "!".repeat(50));
         Ok(())
     }
-    
+
     fn name(&self) -> &'static str {
-        "Console"
+        "console"
     }
 }
 
-// Public API for notifications
-pub fn send_notification(
-    url: &str, 
-    is_down: bool, 
-    status: &str
-) -> Result<()> {
-    let title = if is_down {
-        format!("🚨 site down: {}", url)
-    } else {
-        format!(" 👍 site recovered: {}", url)
-    };
-    
-    let body = if is_down {
-        format!("{} is down! status: {}", url, status)
-    } else {
-        format!("{} is up! status: {}", url, status)
+// Public API for notifications. `name` is the short site name shown to the
+// user and `state` is the one the site just entered: up, degraded or down.
+pub fn send_notification(url: &str, name: &str, state: &str, status: &str) -> Result<()> {
+    let (title, body) = match state {
+        "down" => (
+            format!("🚨 site down: {}", name),
+            format!("{} is down! status: {}", name, status),
+        ),
+        "degraded" => (
+            format!("⚠️ site degraded: {}", name),
+            format!("{} is degraded. status: {}", name, status),
+        ),
+        _ => (
+            format!(" 👍 site recovered: {}", name),
+            format!("{} is up! status: {}", name, status),
+        ),
     };
-    
+
     // Create a notification service
     let service = NotificationService::new();
-    
-    // Send notification and record result
-    let result = service.send_notification(&title, &body);
-    
+
     // Log the attempt regardless of success/failure
-    log_notification_attempt(url, is_down, status, &result);
-    
+    match service.send_notification(&title, &body) {
+        Ok(channel) => log::notification(
+            LogLevel::Info,
+            "notify_sent",
+            json!({ "url": url, "state": state, "status": status, "channel": channel }),
+        ),
+        Err(e) => log::notification(
+            LogLevel::Error,
+            "notify_failed",
+            json!({ "url": url, "state": state, "status": status, "error": e.to_string() }),
+        ),
+    }
+
     // Even if notification fails, don't fail the process
     Ok(())
 }
 
 // Helper for NotifyMethod enum (kept for backward compatibility)
+#[allow(dead_code)]
 pub enum NotifyMethod {
     Device,
 }
@@ -156,36 +155,3 @@ impl From<&str> for NotifyMethod {
         NotifyMethod::Device
     }
 }
-
-// Log notification attempt to help with debugging
-pub fn log_notification_attempt(url: &str, is_down: bool, status: &str, result: &Result<()>) {
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-    let status_type = if is_down { "DOWN" } else { "UP" };
-    
-    let message = match result {
-        Ok(_) => {
-            format!("[{}] Notification sent: {} is {} ({})", timestamp, url, status_type, status)
-        },
-        Err(e) => {
-            format!("[{}] Notification failed: {} is {} ({}). Error: {}", 
-                timestamp, url, status_type, status, e)
-        }
-    };
-    
-    println!("{}", message);
-    log_to_file(&message);
-}
-
-// Helper function to log to a dedicated notification log file
-fn log_to_file(message: &str) {
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-    let log_message = format!("[{}] {}\n", timestamp, message);
-    
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("/tmp/isup_notifications.log") 
-    {
-        let _ = file.write_all(log_message.as_bytes());
-    }
-} 
\ No newline at end of file
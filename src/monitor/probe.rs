@@ -0,0 +1,770 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::process::Stdio;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::monitor::db::{Site, PUSH_SCHEME};
+use crate::monitor::{metrics, tcp, udp};
+use crate::utils::{format_duration, get_status_description};
+
+// The checks behind each kind of monitor. A site's URL scheme picks the
+// probe: http(s):// sites are fetched, push:// monitors are judged by their
+// pings, cmd:// monitors run a command, the local schemes below look at
+// this machine, udp://, ntp:// and dns:// send datagrams (see udp.rs) and
+// redis://, smtp://, ssh:// and friends speak their protocol (see tcp.rs).
+
+pub const CMD_SCHEME: &str = "cmd://";
+
+// process://<name>          a process with this name is running
+// pidfile://<path>          the process in this pidfile is running
+// systemd://<unit>          the systemd unit is active
+// unix://<path>             the unix socket accepts connections
+pub const PROCESS_SCHEME: &str = "process://";
+pub const PIDFILE_SCHEME: &str = "pidfile://";
+pub const SYSTEMD_SCHEME: &str = "systemd://";
+pub const UNIX_SCHEME: &str = "unix://";
+const LOCAL_SCHEMES: &[&str] = &[PROCESS_SCHEME, PIDFILE_SCHEME, SYSTEMD_SCHEME, UNIX_SCHEME];
+
+// Longest status a command may report; the rest of its line is cut off
+const MAX_STATUS_CHARS: usize = 200;
+
+// What one check found
+pub struct Outcome {
+    pub is_up: bool,
+    // Up, but with a warning, like a Nagios plugin exiting with 1
+    pub degraded: bool,
+    pub status_code: Option<u16>,
+    pub status: String,
+    pub latency_ms: Option<i64>,
+    // Details for the log when the check failed outright
+    pub error: Option<String>,
+    // Error class for isup_check_failures_total
+    pub failure: Option<&'static str>,
+}
+
+impl Outcome {
+    pub fn down(status: impl Into<String>, failure: &'static str) -> Self {
+        Outcome {
+            is_up: false,
+            degraded: false,
+            status_code: None,
+            status: status.into(),
+            latency_ms: None,
+            error: None,
+            failure: Some(failure),
+        }
+    }
+}
+
+// Parts of query keys that suggest the value is a secret
+const SECRET_KEYS: &[&str] = &[
+    "pass",
+    "pwd",
+    "secret",
+    "token",
+    "auth",
+    "credential",
+    "key",
+];
+
+// A monitor's URL ends up in logs, metrics labels, notifications, the status
+// page and the dashboard, so it must not carry credentials
+pub fn reject_credentials(url: &reqwest::Url) -> Result<()> {
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(anyhow!(
+            "monitor URLs can't contain a user name or password, \
+             since they are shown in logs and on the status page"
+        ));
+    }
+    let secret = url.query_pairs().find(|(key, _)| {
+        let key = key.to_ascii_lowercase();
+        SECRET_KEYS.iter().any(|part| key.contains(part))
+    });
+    if let Some((key, _)) = secret {
+        return Err(anyhow!(
+            "'{}' looks like a secret; monitor URLs are shown in logs and on the status page",
+            key
+        ));
+    }
+    Ok(())
+}
+
+// Check a site. `site` is its database row, which push and command monitors
+// need; plain URLs can be checked without one.
+pub async fn run(
+    client: &reqwest::Client,
+    url: &str,
+    site: Option<&Site>,
+    timeout: Duration,
+) -> Outcome {
+    if let Some(name) = url.strip_prefix(PROCESS_SCHEME) {
+        return process(name, timeout).await;
+    }
+    if let Some(path) = url.strip_prefix(PIDFILE_SCHEME) {
+        return pidfile(path);
+    }
+    if let Some(unit) = url.strip_prefix(SYSTEMD_SCHEME) {
+        return systemd_unit(unit, timeout).await;
+    }
+    if let Some(path) = url.strip_prefix(UNIX_SCHEME) {
+        return unix_socket(path, timeout).await;
+    }
+    if udp::SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+        return udp::check(url, timeout).await;
+    }
+    if tcp::handles(url) {
+        return tcp::check(url, timeout).await;
+    }
+    match site {
+        Some(site) if site.push_name().is_some() => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default();
+            push(site, now)
+        }
+        Some(site) if url.starts_with(CMD_SCHEME) => command(site, timeout).await,
+        _ => http(client, url, timeout).await,
+    }
+}
+
+// Make sure a site added by its URL is one a probe can check. Push and
+// command monitors need more than a URL, and command monitors in particular
+// must never be added over the API, since anyone with a token could run
+// commands on this machine with them.
+pub fn validate(url: &str) -> Result<()> {
+    if url.starts_with(PUSH_SCHEME) {
+        return Err(anyhow!("push monitors are added with isup add --push"));
+    }
+    if url.starts_with(CMD_SCHEME) {
+        return Err(anyhow!(
+            "command monitors can only be added with isup add --command"
+        ));
+    }
+    #[cfg(not(unix))]
+    if let Some(scheme) = [PIDFILE_SCHEME, UNIX_SCHEME]
+        .into_iter()
+        .find(|scheme| url.starts_with(scheme))
+    {
+        return Err(anyhow!(
+            "{} monitors are only supported on Linux and macOS",
+            scheme
+        ));
+    }
+    if let Some(target) = LOCAL_SCHEMES
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+    {
+        if target.trim().is_empty() {
+            return Err(anyhow!(
+                "{} needs something to watch, e.g. process://nginx",
+                url
+            ));
+        }
+        return Ok(());
+    }
+    if udp::SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+        return udp::validate(url);
+    }
+    if tcp::handles(url) {
+        return tcp::validate(url);
+    }
+
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid url {}", url))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        other => Err(anyhow!(
+            "unsupported scheme '{}://', expected http, https, process, pidfile, systemd, unix, udp, ntp, dns, \
+             redis, smtp, ssh, postgres, mysql or mqtt",
+            other
+        )),
+    }
+}
+
+async fn http(client: &reqwest::Client, url: &str, timeout: Duration) -> Outcome {
+    let started = Instant::now();
+    match client.get(url).timeout(timeout).send().await {
+        Ok(response) => {
+            let status = response.status();
+            let is_up = status.is_success();
+            Outcome {
+                is_up,
+                degraded: false,
+                status_code: Some(status.as_u16()),
+                status: get_status_description(status.as_u16()),
+                latency_ms: Some(started.elapsed().as_millis() as i64),
+                error: None,
+                failure: (!is_up).then_some("status"),
+            }
+        }
+        Err(e) => {
+            let e = anyhow::Error::from(e);
+            // Service Unavailable stands in for every failed request
+            Outcome {
+                error: Some(e.to_string()),
+                ..Outcome::down(get_status_description(503), metrics::classify_error(&e))
+            }
+        }
+    }
+}
+
+// A push monitor is up while its last ping, or its creation before the first
+// ping, is no older than the expected interval plus the grace period
+fn push(site: &Site, now: i64) -> Outcome {
+    let allowed = site.interval + site.grace.unwrap_or(0.0);
+
+    let (is_up, status) = match site.last_ping {
+        Some(at) if (now - at) as f64 <= allowed => {
+            (true, format!("last ping {} ago", format_duration(now - at)))
+        }
+        Some(at) => (false, format!("no ping for {}", format_duration(now - at))),
+        None => {
+            let age = now - site.created_at.unwrap_or(now);
+            if age as f64 <= allowed {
+                (true, "waiting for the first ping".to_string())
+            } else {
+                (
+                    false,
+                    format!("no ping since it was added {} ago", format_duration(age)),
+                )
+            }
+        }
+    };
+
+    Outcome {
+        is_up,
+        degraded: false,
+        status_code: None,
+        status,
+        latency_ms: None,
+        error: None,
+        failure: (!is_up).then_some("missed_ping"),
+    }
+}
+
+// Run a command monitor's command with the shell. Like a Nagios plugin, exit
+// code 0 is up, 1 is degraded and anything else is down, and the first line
+// it prints (without any |perfdata) becomes the status.
+async fn command(site: &Site, timeout: Duration) -> Outcome {
+    let Some(command) = site.command.as_deref() else {
+        return Outcome::down("no command configured", "exec");
+    };
+
+    #[cfg(unix)]
+    let mut process = {
+        let mut process = tokio::process::Command::new("sh");
+        process.arg("-c").arg(command);
+        // Its own process group, so a timeout kills whatever the shell started too
+        process.process_group(0);
+        process
+    };
+    #[cfg(not(unix))]
+    let mut process = {
+        let mut process = tokio::process::Command::new("cmd");
+        process.arg("/C").arg(command);
+        process
+    };
+
+    let env: Value = site
+        .env
+        .as_deref()
+        .and_then(|env| serde_json::from_str(env).ok())
+        .unwrap_or_default();
+    if let Some(env) = env.as_object() {
+        for (key, value) in env {
+            process.env(key, value.as_str().unwrap_or_default());
+        }
+    }
+    process
+        .env("ISUP_MONITOR", site.url.trim_start_matches(CMD_SCHEME))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let started = Instant::now();
+    let child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return Outcome {
+                error: Some(e.to_string()),
+                ..Outcome::down(format!("could not run the command: {}", e), "exec")
+            };
+        }
+    };
+    let pid = child.id();
+
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Outcome {
+                error: Some(e.to_string()),
+                ..Outcome::down("the command failed", "exec")
+            }
+        }
+        Err(_) => {
+            // Dropping the child killed the shell; this gets the rest of its group
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                unsafe {
+                    libc::kill(-(pid as i32), libc::SIGKILL);
+                }
+            }
+            #[cfg(not(unix))]
+            let _ = pid;
+            return Outcome::down(
+                format!(
+                    "timed out after {}",
+                    format_duration(timeout.as_secs() as i64)
+                ),
+                "timeout",
+            );
+        }
+    };
+    let latency_ms = Some(started.elapsed().as_millis() as i64);
+
+    let summary = first_line(&output.stdout).or_else(|| first_line(&output.stderr));
+    let (is_up, degraded, status) = match output.status.code() {
+        Some(0) => (true, false, summary.unwrap_or_else(|| "ok".to_string())),
+        Some(1) => (true, true, summary.unwrap_or_else(|| "warning".to_string())),
+        Some(code) => (
+            false,
+            false,
+            summary.unwrap_or_else(|| format!("exit status {}", code)),
+        ),
+        None => (
+            false,
+            false,
+            summary.unwrap_or_else(|| "killed by a signal".to_string()),
+        ),
+    };
+
+    Outcome {
+        is_up,
+        degraded,
+        status_code: None,
+        status,
+        latency_ms,
+        error: None,
+        failure: (!is_up).then_some("status"),
+    }
+}
+
+fn first_line(output: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(output);
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.split('|').next().unwrap_or(line).trim();
+    (!line.is_empty()).then(|| line.chars().take(MAX_STATUS_CHARS).collect())
+}
+
+// A process matching `name` is running. On Linux this reads /proc; elsewhere
+// it asks pgrep.
+async fn process(name: &str, timeout: Duration) -> Outcome {
+    let started = Instant::now();
+    let count = match count_processes(name, timeout).await {
+        Ok(count) => count,
+        Err(e) => {
+            return Outcome {
+                error: Some(format!("{:#}", e)),
+                ..Outcome::down(format!("could not list processes: {:#}", e), "exec")
+            };
+        }
+    };
+
+    let status = match count {
+        0 => format!("no process named {}", name),
+        1 => "1 process running".to_string(),
+        n => format!("{} processes running", n),
+    };
+    Outcome {
+        is_up: count > 0,
+        degraded: false,
+        status_code: None,
+        status,
+        latency_ms: Some(started.elapsed().as_millis() as i64),
+        error: None,
+        failure: (count == 0).then_some("status"),
+    }
+}
+
+// Processes whose name or program is `name`, leaving out zombies
+#[cfg(target_os = "linux")]
+async fn count_processes(name: &str, timeout: Duration) -> Result<usize> {
+    // Reading /proc blocks, so it runs off the runtime's only thread
+    let name = name.to_string();
+    let walk = tokio::task::spawn_blocking(move || count_in_proc(&name));
+    tokio::time::timeout(timeout, walk)
+        .await
+        .map_err(|_| anyhow!("timed out reading /proc"))?
+        .context("reading /proc failed")?
+}
+
+#[cfg(target_os = "linux")]
+fn count_in_proc(name: &str) -> Result<usize> {
+    let mut count = 0;
+    for entry in std::fs::read_dir("/proc").context("failed to read /proc")? {
+        let Ok(entry) = entry else { continue };
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+        // Processes can exit while we look; they just don't count
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // pid (comm) state ...; comm may itself contain spaces and parentheses
+        let (Some(open), Some(close)) = (stat.find('('), stat.rfind(')')) else {
+            continue;
+        };
+        let comm = &stat[open + 1..close];
+        if stat[close + 1..].trim_start().starts_with('Z') {
+            continue;
+        }
+        // comm is cut to 15 bytes, so longer names are matched on argv[0]
+        let program = std::fs::read(entry.path().join("cmdline"))
+            .ok()
+            .and_then(|cmdline| {
+                let argv0 = cmdline.split(|b| *b == 0).next()?.to_vec();
+                let argv0 = String::from_utf8_lossy(&argv0).into_owned();
+                argv0.rsplit('/').next().map(str::to_string)
+            });
+        if comm == name || program.as_deref() == Some(name) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn count_processes(name: &str, timeout: Duration) -> Result<usize> {
+    let output = tokio::time::timeout(
+        timeout,
+        tokio::process::Command::new("pgrep")
+            .arg("-x")
+            .arg(name)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| anyhow!("pgrep timed out"))?
+    .context("could not run pgrep")?;
+    match output.status.code() {
+        Some(0) => Ok(String::from_utf8_lossy(&output.stdout).lines().count()),
+        Some(1) => Ok(0),
+        _ => Err(anyhow!(
+            "pgrep failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+// The process whose pid is in the file at `path` is running
+#[cfg(unix)]
+fn pidfile(path: &str) -> Outcome {
+    let pid = match std::fs::read_to_string(path) {
+        Ok(text) => match text.trim().parse::<u32>() {
+            Ok(pid) if pid > 0 => pid,
+            _ => return Outcome::down(format!("{} does not hold a pid", path), "status"),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Outcome::down(format!("{} does not exist", path), "status");
+        }
+        Err(e) => return Outcome::down(format!("could not read {}: {}", path, e), "exec"),
+    };
+
+    let is_up = process_exists(pid);
+    Outcome {
+        is_up,
+        degraded: false,
+        status_code: None,
+        status: if is_up {
+            format!("pid {} is running", pid)
+        } else {
+            format!("pid {} is not running", pid)
+        },
+        latency_ms: None,
+        error: None,
+        failure: (!is_up).then_some("status"),
+    }
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    // Signal 0 only checks; EPERM means it exists but belongs to someone else
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// validate keeps these from being added; this covers a database copied from another machine
+#[cfg(not(unix))]
+fn pidfile(_path: &str) -> Outcome {
+    Outcome::down(
+        "pidfile monitors are not supported on this platform",
+        "exec",
+    )
+}
+
+// `systemctl is-active` says the unit is active. A unit that is starting or
+// reloading is degraded rather than down.
+async fn systemd_unit(unit: &str, timeout: Duration) -> Outcome {
+    let started = Instant::now();
+    let output = tokio::time::timeout(
+        timeout,
+        tokio::process::Command::new("systemctl")
+            .arg("is-active")
+            .arg("--")
+            .arg(unit)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+    let output = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Outcome {
+                error: Some(e.to_string()),
+                ..Outcome::down(format!("could not run systemctl: {}", e), "exec")
+            };
+        }
+        Err(_) => {
+            return Outcome::down(
+                format!(
+                    "systemctl timed out after {}",
+                    format_duration(timeout.as_secs() as i64)
+                ),
+                "timeout",
+            );
+        }
+    };
+
+    let state = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let (is_up, degraded) = match state.as_str() {
+        "active" => (true, false),
+        "activating" | "reloading" => (true, true),
+        _ => (false, false),
+    };
+    let status = match state.as_str() {
+        "" => first_line(&output.stderr).unwrap_or_else(|| "systemctl printed nothing".to_string()),
+        state => format!("{} is {}", unit, state),
+    };
+
+    Outcome {
+        is_up,
+        degraded,
+        status_code: None,
+        status,
+        latency_ms: Some(started.elapsed().as_millis() as i64),
+        error: None,
+        failure: (!is_up).then_some("status"),
+    }
+}
+
+// The unix socket at `path` accepts a connection
+#[cfg(unix)]
+async fn unix_socket(path: &str, timeout: Duration) -> Outcome {
+    let started = Instant::now();
+    match tokio::time::timeout(timeout, tokio::net::UnixStream::connect(path)).await {
+        Ok(Ok(_)) => Outcome {
+            is_up: true,
+            degraded: false,
+            status_code: None,
+            status: "accepting connections".to_string(),
+            latency_ms: Some(started.elapsed().as_millis() as i64),
+            error: None,
+            failure: None,
+        },
+        Ok(Err(e)) => Outcome {
+            error: Some(e.to_string()),
+            ..Outcome::down(format!("cannot connect: {}", e), "connect")
+        },
+        Err(_) => Outcome::down(
+            format!(
+                "timed out after {}",
+                format_duration(timeout.as_secs() as i64)
+            ),
+            "timeout",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+async fn unix_socket(_path: &str, _timeout: Duration) -> Outcome {
+    Outcome::down("unix sockets are not supported on this platform", "exec")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_site(last_ping: Option<i64>, created_at: i64) -> Site {
+        Site {
+            url: "push://backup".to_string(),
+            interval: 3600.0,
+            grace: Some(300.0),
+            last_ping,
+            created_at: Some(created_at),
+            ..Default::default()
+        }
+    }
+
+    fn command_site(command: &str) -> Site {
+        Site {
+            url: "cmd://test".to_string(),
+            command: Some(command.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn push_monitors_are_up_while_pings_are_on_time() {
+        let now = 1_760_000_000;
+        let created = now - 30 * 86400;
+
+        // Within the interval plus the grace period
+        let outcome = push(&push_site(Some(now - 600), created), now);
+        assert!(outcome.is_up);
+        assert_eq!(outcome.status, "last ping 10m ago");
+        assert!(push(&push_site(Some(now - 3900), created), now).is_up);
+
+        // Any later than that
+        let outcome = push(&push_site(Some(now - 3901), created), now);
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "no ping for 1h 5m");
+        assert_eq!(outcome.failure, Some("missed_ping"));
+    }
+
+    #[test]
+    fn push_monitors_that_never_pinged_get_one_interval_to_start() {
+        let now = 1_760_000_000;
+
+        let outcome = push(&push_site(None, now - 60), now);
+        assert!(outcome.is_up);
+        assert_eq!(outcome.status, "waiting for the first ping");
+
+        let outcome = push(&push_site(None, now - 2 * 86400), now);
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "no ping since it was added 2d 0h ago");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_map_exit_codes_like_nagios_plugins() {
+        let timeout = Duration::from_secs(10);
+
+        let outcome = command(&command_site("echo 'OK - 3 jobs | jobs=3'"), timeout).await;
+        assert!(outcome.is_up && !outcome.degraded);
+        assert_eq!(outcome.status, "OK - 3 jobs");
+
+        let outcome = command(&command_site("echo 'WARNING - disk 91%'; exit 1"), timeout).await;
+        assert!(outcome.is_up && outcome.degraded);
+        assert_eq!(outcome.status, "WARNING - disk 91%");
+
+        // Falls back to stderr, then to the exit status
+        let outcome = command(&command_site("echo 'CRITICAL' >&2; exit 2"), timeout).await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "CRITICAL");
+        assert_eq!(outcome.failure, Some("status"));
+        let outcome = command(&command_site("exit 3"), timeout).await;
+        assert_eq!(outcome.status, "exit status 3");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_see_their_env_and_monitor_name() {
+        let mut site = command_site("echo \"$GREETING from $ISUP_MONITOR\"");
+        site.env = Some(r#"{"GREETING":"hello"}"#.to_string());
+        let outcome = command(&site, Duration::from_secs(10)).await;
+        assert_eq!(outcome.status, "hello from test");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timed_out_commands_are_killed_with_everything_they_started() {
+        let pid_file = std::env::temp_dir().join(format!("isup-probe-{}.pid", std::process::id()));
+        let site = command_site(&format!(
+            "sleep 30 & echo $! > '{}'; wait",
+            pid_file.display()
+        ));
+
+        let outcome = command(&site, Duration::from_millis(500)).await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.failure, Some("timeout"));
+
+        let pid: i32 = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        // The background sleep went with the shell. It may linger as a zombie
+        // until it is reaped, which is dead enough.
+        let running = || {
+            let zombie = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .is_ok_and(|stat| stat.contains(") Z"));
+            !zombie && unsafe { libc::kill(pid, 0) } == 0
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while running() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!running());
+    }
+
+    #[test]
+    fn first_line_skips_blanks_and_perfdata() {
+        assert_eq!(
+            first_line(b"\n  OK - fine | load=0.1\nmore\n"),
+            Some("OK - fine".to_string())
+        );
+        assert_eq!(first_line(b"\n \n"), None);
+        assert_eq!(first_line(b"| only=perfdata"), None);
+        let long = "x".repeat(500);
+        assert_eq!(first_line(long.as_bytes()).unwrap().len(), MAX_STATUS_CHARS);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn processes_are_counted_off_the_runtime_thread() {
+        let own = std::fs::read_to_string("/proc/self/comm").unwrap();
+        let outcome = process(own.trim(), Duration::from_secs(5)).await;
+        assert!(outcome.is_up, "{}", outcome.status);
+
+        let outcome = process("isup-no-such-process", Duration::from_secs(5)).await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "no process named isup-no-such-process");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pidfiles_name_a_running_process() {
+        let dir = std::env::temp_dir().join(format!("isup-pidfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.pid");
+        let path_str = path.to_str().unwrap();
+
+        std::fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+        assert!(pidfile(path_str).is_up);
+        std::fs::write(&path, "not a pid").unwrap();
+        assert_eq!(
+            pidfile(path_str).status,
+            format!("{} does not hold a pid", path_str)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!pidfile(path_str).is_up);
+    }
+
+    #[test]
+    fn local_monitors_need_a_target() {
+        assert!(validate("process://nginx").is_ok());
+        assert!(validate("process:// ").is_err());
+        assert!(validate("push://backup").is_err());
+        assert!(validate("cmd://disk").is_err());
+        assert_eq!(validate("pidfile:///run/app.pid").is_ok(), cfg!(unix));
+        assert_eq!(validate("unix:///run/app.sock").is_ok(), cfg!(unix));
+    }
+}
@@ -0,0 +1,470 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::monitor::db::{bounded_duration, Site, MAX_INTERVAL, MIN_INTERVAL};
+
+// Timer queue for the daemon: a min-heap of due times, so the monitoring loop
+// can sleep until exactly the next check instead of polling.
+//
+// Rescheduling or removing a site doesn't touch the heap. Each entry carries
+// a generation, and heap items whose generation no longer matches are
+// skipped when they reach the top.
+//
+// Checks are spread out so sites don't all fire together: when the daemon
+// starts, each site's first check is offset by a phase derived from its URL,
+// and every check after that lands within a random jitter of its nominal time.
+// The jitter isn't carried forward, so a site keeps its phase.
+pub struct Scheduler {
+    heap: BinaryHeap<Reverse<(Instant, u64, String)>>,
+    entries: HashMap<String, Entry>,
+    generation: u64,
+    // False until the first sync, whose sites are staggered instead of all due at once
+    started: bool,
+    rng: XorShift,
+}
+
+struct Entry {
+    // When the check is due before jitter is applied
+    nominal: Instant,
+    next: Instant,
+    interval: Duration,
+    generation: u64,
+}
+
+// The first checks after startup are spread over at most this long
+const STARTUP_SPREAD: Duration = Duration::from_secs(30);
+
+// Push monitors are looked at this often, however rarely they expect pings,
+// so a missed ping is noticed soon after its grace period runs out
+const PUSH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Check interval for a site, guarding against nonsense values in the database
+pub fn site_interval(site: &Site) -> Duration {
+    let interval = bounded_duration(site.interval, MIN_INTERVAL, MAX_INTERVAL);
+    match site.push_name() {
+        Some(_) => interval.min(PUSH_CHECK_INTERVAL),
+        None => interval,
+    }
+}
+
+// Position of a site within its interval, in [0, 1). Derived from an FNV-1a
+// hash of the URL so it is the same on every start.
+pub fn site_phase(url: &str) -> f64 {
+    let mut hash = url.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    // FNV barely touches the high bits for URLs that differ only at the end,
+    // so mix them in before taking the fraction
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Small xorshift64* generator; check jitter doesn't need anything stronger
+struct XorShift(u64);
+
+impl XorShift {
+    fn seeded() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        XorShift((nanos ^ ((std::process::id() as u64) << 32)) | 1)
+    }
+
+    // Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545f4914f6cdd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler {
+            heap: BinaryHeap::new(),
+            entries: HashMap::new(),
+            generation: 0,
+            started: false,
+            rng: XorShift::seeded(),
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Bring the schedule in line with the database. New and resumed sites are
+    // due right away (staggered by phase on the first sync), removed and
+    // paused sites are dropped, and a changed interval applies from now if
+    // that is sooner than the current due time.
+    pub fn sync(&mut self, sites: &[Site], now: Instant) {
+        let staggered = !self.started;
+        self.started = true;
+
+        let active: HashMap<&str, Duration> = sites
+            .iter()
+            .filter(|site| !site.paused)
+            .map(|site| (site.url.as_str(), site_interval(site)))
+            .collect();
+
+        self.entries
+            .retain(|url, _| active.contains_key(url.as_str()));
+
+        for (url, interval) in active {
+            let next = match self.entries.get(url) {
+                Some(entry) if entry.interval == interval => continue,
+                Some(entry) => entry.nominal.min(now + interval),
+                None if staggered => now + interval.min(STARTUP_SPREAD).mul_f64(site_phase(url)),
+                None => now,
+            };
+            self.schedule(url, interval, next);
+        }
+
+        // Stale heap items would otherwise pile up if sites churn a lot
+        if self.heap.len() > self.entries.len() * 2 + 16 {
+            self.rebuild_heap();
+        }
+    }
+
+    // Set when a site is next due, adding it if it isn't tracked yet
+    pub fn schedule(&mut self, url: &str, interval: Duration, at: Instant) {
+        self.schedule_jittered(url, interval, at, at);
+    }
+
+    fn schedule_jittered(&mut self, url: &str, interval: Duration, nominal: Instant, at: Instant) {
+        self.generation += 1;
+        self.entries.insert(
+            url.to_string(),
+            Entry {
+                nominal,
+                next: at,
+                interval,
+                generation: self.generation,
+            },
+        );
+        self.heap
+            .push(Reverse((at, self.generation, url.to_string())));
+    }
+
+    // Take every site that is due, with how late it is, and schedule its next
+    // check one interval after its nominal due time, moved by up to `jitter`
+    // of the interval either way.
+    pub fn pop_due(&mut self, now: Instant, jitter: f64) -> Vec<(String, Duration)> {
+        let mut due = Vec::new();
+
+        while let Some(Reverse((at, generation, _))) = self.heap.peek() {
+            if *at > now {
+                break;
+            }
+            let generation = *generation;
+            let Some(Reverse((at, _, url))) = self.heap.pop() else {
+                break;
+            };
+
+            let lag = now.saturating_duration_since(at);
+            let Some((interval, nominal)) = self
+                .entries
+                .get(&url)
+                .filter(|entry| entry.generation == generation)
+                .map(|entry| (entry.interval, entry.nominal))
+            else {
+                continue;
+            };
+
+            // After a long stall (e.g. the machine was asleep) start over from now
+            let mut nominal = nominal + interval;
+            if nominal <= now {
+                nominal = now + interval;
+            }
+
+            let spread = jitter * (self.rng.next_f64() * 2.0 - 1.0);
+            let at = if spread >= 0.0 {
+                nominal + interval.mul_f64(spread)
+            } else {
+                nominal
+                    .checked_sub(interval.mul_f64(-spread))
+                    .unwrap_or(nominal)
+                    .max(now)
+            };
+
+            self.schedule_jittered(&url, interval, nominal, at);
+            due.push((url, lag));
+        }
+
+        due
+    }
+
+    // When the earliest site is due, if any
+    pub fn next_due(&mut self) -> Option<Instant> {
+        while let Some(Reverse((at, generation, url))) = self.heap.peek() {
+            if self
+                .entries
+                .get(url)
+                .is_some_and(|entry| entry.generation == *generation)
+            {
+                return Some(*at);
+            }
+            self.heap.pop();
+        }
+        None
+    }
+
+    // Every tracked site with its next due time
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Instant)> + '_ {
+        self.entries
+            .iter()
+            .map(|(url, entry)| (url.as_str(), entry.next))
+    }
+
+    fn rebuild_heap(&mut self) {
+        self.heap = self
+            .entries
+            .iter()
+            .map(|(url, entry)| Reverse((entry.next, entry.generation, url.clone())))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(url: &str, interval: f64) -> Site {
+        Site {
+            url: url.to_string(),
+            interval,
+            ..Default::default()
+        }
+    }
+
+    fn due_urls(due: &[(String, Duration)]) -> Vec<&str> {
+        due.iter().map(|(url, _)| url.as_str()).collect()
+    }
+
+    // A scheduler past its first sync, so new sites are due right away
+    fn started() -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        scheduler.sync(&[], Instant::now());
+        scheduler
+    }
+
+    #[test]
+    fn pop_due_yields_sites_in_deadline_order() {
+        let now = Instant::now();
+        let mut scheduler = started();
+        let interval = Duration::from_secs(60);
+        scheduler.schedule("https://c.test", interval, now + Duration::from_secs(3));
+        scheduler.schedule("https://a.test", interval, now + Duration::from_secs(1));
+        scheduler.schedule("https://b.test", interval, now + Duration::from_secs(2));
+
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(1)));
+        assert!(scheduler.pop_due(now, 0.0).is_empty());
+
+        let due = scheduler.pop_due(now + Duration::from_secs(5), 0.0);
+        assert_eq!(
+            due_urls(&due),
+            ["https://a.test", "https://b.test", "https://c.test"]
+        );
+        // Each reports how late it was started
+        assert_eq!(due[0].1, Duration::from_secs(4));
+        assert_eq!(due[2].1, Duration::from_secs(2));
+
+        // Each is next due one interval after its nominal time
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn resync_after_an_interval_edit_drops_the_stale_entry() {
+        let now = Instant::now();
+        let mut scheduler = started();
+        scheduler.sync(&[site("https://a.test", 60.0)], now);
+        assert_eq!(due_urls(&scheduler.pop_due(now, 0.0)), ["https://a.test"]);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(60)));
+
+        // A shorter interval applies from now
+        scheduler.sync(&[site("https://a.test", 10.0)], now);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(10)));
+        assert_eq!(
+            due_urls(&scheduler.pop_due(now + Duration::from_secs(10), 0.0)),
+            ["https://a.test"]
+        );
+
+        // The old 60s item is still in the heap but must not fire
+        assert_eq!(
+            due_urls(&scheduler.pop_due(now + Duration::from_secs(60), 0.0)),
+            ["https://a.test"],
+            "fired once, from the 10s schedule"
+        );
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(70)));
+
+        // Syncing the same interval again changes nothing
+        scheduler.sync(
+            &[site("https://a.test", 10.0)],
+            now + Duration::from_secs(61),
+        );
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(70)));
+    }
+
+    #[test]
+    fn removed_and_paused_sites_are_dropped() {
+        let now = Instant::now();
+        let mut scheduler = started();
+        scheduler.sync(
+            &[site("https://a.test", 10.0), site("https://b.test", 10.0)],
+            now,
+        );
+
+        let mut paused = site("https://b.test", 10.0);
+        paused.paused = true;
+        scheduler.sync(&[paused], now);
+
+        assert!(scheduler
+            .pop_due(now + Duration::from_secs(100), 0.0)
+            .is_empty());
+        assert_eq!(scheduler.next_due(), None);
+        assert_eq!(scheduler.iter().count(), 0);
+    }
+
+    #[test]
+    fn site_phase_is_stable_and_spread() {
+        let urls: Vec<String> = (0..200)
+            .map(|i| format!("https://example.com/{}", i))
+            .collect();
+        let phases: Vec<f64> = urls.iter().map(|url| site_phase(url)).collect();
+
+        for (url, phase) in urls.iter().zip(&phases) {
+            assert_eq!(site_phase(url), *phase);
+            assert!((0.0..1.0).contains(phase), "{} has phase {}", url, phase);
+        }
+        // URLs differing only at the end still land all over the interval
+        assert!(phases.iter().any(|phase| *phase < 0.25));
+        assert!(phases.iter().any(|phase| *phase > 0.75));
+    }
+
+    #[test]
+    fn first_sync_staggers_within_the_startup_spread() {
+        let now = Instant::now();
+        let mut scheduler = Scheduler::new();
+        let sites: Vec<Site> = (0..50)
+            .map(|i| {
+                site(
+                    &format!("https://example.com/{}", i),
+                    if i % 2 == 0 { 3600.0 } else { 10.0 },
+                )
+            })
+            .collect();
+        scheduler.sync(&sites, now);
+
+        for (url, at) in scheduler.iter() {
+            let interval = site_interval(sites.iter().find(|site| site.url == url).unwrap());
+            let offset = at - now;
+            assert!(
+                offset < interval.min(STARTUP_SPREAD),
+                "{} is due after {:?}",
+                url,
+                offset
+            );
+            assert_eq!(
+                offset,
+                interval.min(STARTUP_SPREAD).mul_f64(site_phase(url))
+            );
+        }
+
+        // Sites added later are due right away
+        let mut with_new = sites.clone();
+        with_new.push(site("https://new.test", 3600.0));
+        scheduler.sync(&with_new, now);
+        assert!(scheduler
+            .iter()
+            .any(|(url, at)| url == "https://new.test" && at == now));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_share_of_the_interval() {
+        let now = Instant::now();
+        let interval = Duration::from_secs(100);
+        for jitter in [0.0, 0.1, 0.5] {
+            let mut scheduler = started();
+            let mut shortest = Duration::MAX;
+            let mut longest = Duration::ZERO;
+            for round in 0..500u32 {
+                let due_at = now + Duration::from_secs(1000) * round;
+                scheduler.schedule("https://a.test", interval, due_at);
+                scheduler.pop_due(due_at, jitter);
+                let (_, next) = scheduler.iter().next().unwrap();
+                assert!(
+                    next >= due_at,
+                    "jitter {} scheduled the next check in the past",
+                    jitter
+                );
+
+                let gap = next - due_at;
+                let offset = gap.abs_diff(interval);
+                assert!(
+                    offset <= interval.mul_f64(jitter),
+                    "jitter {} moved a check by {:?}",
+                    jitter,
+                    offset
+                );
+                shortest = shortest.min(gap);
+                longest = longest.max(gap);
+            }
+            if jitter > 0.0 {
+                // Both directions actually happen
+                assert!(
+                    shortest < interval && longest > interval,
+                    "jitter {} never spread checks",
+                    jitter
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn push_monitors_are_checked_at_least_every_30_seconds() {
+        let mut push = site("push://backup", 86400.0);
+        assert_eq!(site_interval(&push), PUSH_CHECK_INTERVAL);
+        push.interval = 5.0;
+        assert_eq!(site_interval(&push), Duration::from_secs(5));
+        assert_eq!(
+            site_interval(&site("https://a.test", -3.0)),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn nonsense_intervals_in_the_database_are_bounded() {
+        for interval in [1e20, f64::INFINITY, f64::MAX] {
+            assert_eq!(
+                site_interval(&site("https://a.test", interval)),
+                Duration::from_secs_f64(MAX_INTERVAL)
+            );
+        }
+        for interval in [0.0, -1.0, 1e-9, f64::NEG_INFINITY] {
+            assert_eq!(
+                site_interval(&site("https://a.test", interval)),
+                Duration::from_secs_f64(MIN_INTERVAL)
+            );
+        }
+        // NaN can't be ordered, so it gets the longest interval
+        assert_eq!(
+            site_interval(&site("https://a.test", f64::NAN)),
+            Duration::from_secs_f64(MAX_INTERVAL)
+        );
+
+        let mut scheduler = started();
+        let now = Instant::now();
+        scheduler.sync(&[site("https://a.test", 1e20)], now);
+        assert_eq!(due_urls(&scheduler.pop_due(now, 0.1)), ["https://a.test"]);
+        assert!(scheduler.next_due().is_some());
+    }
+}
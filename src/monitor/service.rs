@@ -1,23 +1,448 @@
 use anyhow::Result;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
-use tokio::time::sleep;
-use std::collections::HashMap;
-use reqwest::StatusCode;
-use std::process::Command;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::io::Write;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Command;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Mutex, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::sleep_until;
 
-use crate::monitor::db::{get_all_sites, update_site_status};
-use crate::monitor::notifier::{send_notification, log_notification_attempt};
-use crate::utils::get_status_description;
-use crate::monitor::db::get_site_by_url;
+use crate::monitor::config::{self, Config, LogLevel};
+use crate::monitor::control;
+use crate::monitor::db::{bounded_duration, MAX_TIMEOUT};
+use crate::monitor::db::{get_all_sites, prune_history, update_site_status};
+use crate::monitor::db::{get_site_by_url, Site};
+use crate::monitor::lock::{self, DaemonLock};
+use crate::monitor::log;
+use crate::monitor::notifier::send_notification;
+use crate::monitor::scheduler::{site_interval, Scheduler};
+use crate::monitor::systemd;
+use crate::monitor::{api, cert, dashboard, http, metrics, probe, statuspage};
 
 // Global state to track if the service is running
 static SERVICE_RUNNING: AtomicBool = AtomicBool::new(false);
 // Global state to track if the service should stop
 static SERVICE_SHOULD_STOP: AtomicBool = AtomicBool::new(false);
 
+// The CLI tells the daemon about site changes over the control socket.
+// Without one the site list is re-read periodically instead.
+#[cfg(unix)]
+const RESYNC_INTERVAL: Option<Duration> = None;
+#[cfg(not(unix))]
+const RESYNC_INTERVAL: Option<Duration> = Some(Duration::from_secs(60));
+
+// Ask the monitoring loop to finish
+pub fn request_stop() {
+    SERVICE_SHOULD_STOP.store(true, Ordering::SeqCst);
+}
+
+// Runs one check of a site; the daemon's is check_and_record
+type CheckFn = Box<
+    dyn Fn(reqwest::Client, String) -> Pin<Box<dyn Future<Output = SiteCheck> + Send>>
+        + Send
+        + Sync,
+>;
+
+// State shared between the monitoring loop and the control socket
+pub struct DaemonState {
+    pub started_at: Instant,
+    pub started_unix: i64,
+    // When each site is next due
+    pub scheduler: Mutex<Scheduler>,
+    // Set when the site list in the database has changed
+    resync: AtomicBool,
+    // Sites with a check currently running or waiting for a slot
+    pub in_flight: std::sync::Mutex<HashSet<String>>,
+    // One permit per check allowed to run at once (max_concurrent_checks)
+    check_slots: Arc<Semaphore>,
+    check_slot_limit: AtomicUsize,
+    // Permits still to be retired after max_concurrent_checks was lowered
+    check_slot_debt: AtomicUsize,
+    // Wakes the monitoring loop early, e.g. after a reload or stop request
+    pub reload: Notify,
+    // Signalled whenever a check ends, so shutdown can wait for them
+    check_finished: Notify,
+    // Rebuilt when a config reload changes the user agent or timeout
+    client: std::sync::RwLock<reqwest::Client>,
+    check: CheckFn,
+    // Prometheus endpoint on metrics_addr
+    metrics_server: http::Listener,
+    // Management API on api_addr, and the token it expects
+    api_server: http::Listener,
+    api_token: std::sync::RwLock<String>,
+    // Generated status page on statuspage_addr
+    statuspage_server: http::Listener,
+    // Web dashboard on dashboard_addr
+    dashboard_server: http::Listener,
+    // Bumped whenever a check ends or the site list changes, for live views
+    updates: watch::Sender<u64>,
+}
+
+impl DaemonState {
+    fn new() -> Result<Self> {
+        Self::with_check(
+            &config::current(),
+            Box::new(|client, url| Box::pin(async move { check_and_record(&client, &url).await })),
+        )
+    }
+
+    fn with_check(config: &Config, check: CheckFn) -> Result<Self> {
+        // Create a shared HTTP client
+        let client = build_client(config)?;
+
+        Ok(DaemonState {
+            started_at: Instant::now(),
+            started_unix: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            scheduler: Mutex::new(Scheduler::new()),
+            resync: AtomicBool::new(false),
+            in_flight: std::sync::Mutex::new(HashSet::new()),
+            check_slots: Arc::new(Semaphore::new(config.max_concurrent_checks)),
+            check_slot_limit: AtomicUsize::new(config.max_concurrent_checks),
+            check_slot_debt: AtomicUsize::new(0),
+            reload: Notify::new(),
+            check_finished: Notify::new(),
+            client: std::sync::RwLock::new(client),
+            check,
+            metrics_server: http::Listener::default(),
+            api_server: http::Listener::default(),
+            api_token: std::sync::RwLock::new(String::new()),
+            statuspage_server: http::Listener::default(),
+            dashboard_server: http::Listener::default(),
+            updates: watch::channel(0).0,
+        })
+    }
+
+    // Re-read the site list from the database on the loop's next pass
+    pub fn request_resync(&self) {
+        self.resync.store(true, Ordering::SeqCst);
+        self.reload.notify_one();
+        self.updates.send_modify(|version| *version += 1);
+    }
+
+    // Changes whenever a site may look different
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.updates.subscribe()
+    }
+
+    fn take_resync(&self) -> bool {
+        self.resync.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn client(&self) -> Result<reqwest::Client> {
+        self.client
+            .read()
+            .map(|client| client.clone())
+            .map_err(|_| anyhow::anyhow!("the HTTP client is unusable after a panic"))
+    }
+
+    // Re-read ~/.isup/config and apply it without touching the check schedule.
+    // Returns the settings that changed; an invalid file keeps the old settings.
+    pub fn reload_config(self: &Arc<Self>) -> Result<Vec<String>> {
+        let old = config::current();
+        let changes = config::reload()?;
+        let new = config::current();
+
+        if old.user_agent != new.user_agent || old.timeout != new.timeout {
+            let client = build_client(&new)?;
+            if let Ok(mut current) = self.client.write() {
+                *current = client;
+            }
+        }
+
+        self.resize_check_slots(new.max_concurrent_checks);
+        self.apply_servers();
+
+        log::event(
+            LogLevel::Info,
+            "config_reloaded",
+            json!({ "changes": changes }),
+        );
+
+        Ok(changes)
+    }
+
+    // Start, move or stop the HTTP servers to match the config. A server that
+    // can't bind is logged and left off rather than failing the daemon.
+    fn apply_servers(self: &Arc<Self>) {
+        let config = config::current();
+        let state = self.clone();
+        if let Err(e) = self
+            .metrics_server
+            .apply("metrics", config.metrics_addr, move |request| {
+                metrics::handle(state.clone(), request)
+            })
+        {
+            log::event(
+                LogLevel::Error,
+                "server_failed",
+                json!({ "server": "metrics", "error": format!("{:#}", e) }),
+            );
+        }
+
+        // Re-read on every reload, so replacing the token file takes effect without a restart
+        let mut api_addr = config.api_addr;
+        if api_addr.is_some() {
+            match api::load_or_create_token() {
+                Ok(token) => {
+                    if let Ok(mut current) = self.api_token.write() {
+                        *current = token;
+                    }
+                }
+                Err(e) => {
+                    log::event(
+                        LogLevel::Error,
+                        "server_failed",
+                        json!({ "server": "api", "error": format!("{:#}", e) }),
+                    );
+                    api_addr = None;
+                }
+            }
+        }
+        let state = self.clone();
+        if let Err(e) = self.api_server.apply("api", api_addr, move |request| {
+            api::handle(state.clone(), request)
+        }) {
+            log::event(
+                LogLevel::Error,
+                "server_failed",
+                json!({ "server": "api", "error": format!("{:#}", e) }),
+            );
+        }
+
+        if let Err(e) =
+            self.statuspage_server
+                .apply("statuspage", config.statuspage_addr, statuspage::handle)
+        {
+            log::event(
+                LogLevel::Error,
+                "server_failed",
+                json!({ "server": "statuspage", "error": format!("{:#}", e) }),
+            );
+        }
+
+        let state = self.clone();
+        if let Err(e) =
+            self.dashboard_server
+                .apply("dashboard", config.dashboard_addr, move |request| {
+                    dashboard::handle(state.clone(), request)
+                })
+        {
+            log::event(
+                LogLevel::Error,
+                "server_failed",
+                json!({ "server": "dashboard", "error": format!("{:#}", e) }),
+            );
+        }
+    }
+
+    pub fn api_token(&self) -> String {
+        self.api_token
+            .read()
+            .map(|token| token.clone())
+            .unwrap_or_default()
+    }
+
+    // Check a site right away and push its next scheduled check a full interval out.
+    // Fails if a check of the site is already running.
+    pub async fn check_now(self: &Arc<Self>, site: &Site) -> Result<SiteCheck> {
+        let url = site.url.as_str();
+        if SERVICE_SHOULD_STOP.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("the monitoring service is shutting down"));
+        }
+
+        let guard = self
+            .begin_check(url)
+            .ok_or_else(|| anyhow::anyhow!("a check of {} is already running", url))?;
+
+        let interval = site_interval(site);
+        self.scheduler
+            .lock()
+            .await
+            .schedule(url, interval, Instant::now() + interval);
+
+        self.run_check(guard).await
+    }
+
+    // Mark a site as in flight, or None if a check of it is already running
+    fn begin_check(self: &Arc<Self>, url: &str) -> Option<InFlightGuard> {
+        let mut in_flight = self.in_flight.lock().ok()?;
+        if !in_flight.insert(url.to_string()) {
+            return None;
+        }
+        Some(InFlightGuard {
+            state: self.clone(),
+            url: url.to_string(),
+        })
+    }
+
+    // Wait for a free check slot, then check the site
+    async fn run_check(self: &Arc<Self>, guard: InFlightGuard) -> Result<SiteCheck> {
+        let permit = self.check_slots.clone().acquire_owned().await.ok();
+        let _slot = CheckSlot {
+            state: self.clone(),
+            permit,
+        };
+
+        Ok((self.check)(self.client()?, guard.url.clone()).await)
+    }
+
+    fn resize_check_slots(&self, limit: usize) {
+        let old = self.check_slot_limit.swap(limit, Ordering::SeqCst);
+        if limit > old {
+            // Cancel outstanding debt before handing out new permits
+            let mut extra = limit - old;
+            while extra > 0 {
+                let debt = self.check_slot_debt.load(Ordering::SeqCst);
+                if debt == 0 {
+                    break;
+                }
+                let paid = debt.min(extra);
+                if self
+                    .check_slot_debt
+                    .compare_exchange(debt, debt - paid, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
+                    extra -= paid;
+                }
+            }
+            self.check_slots.add_permits(extra);
+        } else if limit < old {
+            // Idle permits go now; busy ones are retired as their checks finish
+            let forgotten = self.check_slots.forget_permits(old - limit);
+            self.check_slot_debt
+                .fetch_add(old - limit - forgotten, Ordering::SeqCst);
+        }
+    }
+
+    // A daemon whose checks report every site up without probing it
+    #[cfg(test)]
+    pub fn for_tests() -> Arc<Self> {
+        let check: CheckFn = Box::new(|_, url| {
+            Box::pin(async move {
+                SiteCheck {
+                    url,
+                    is_up: true,
+                    degraded: false,
+                    status_code: None,
+                    status_desc: "up".to_string(),
+                    latency_ms: None,
+                    state_changed: false,
+                }
+            })
+        });
+        Arc::new(Self::with_check(&Config::default(), check).expect("client builds"))
+    }
+
+    pub fn is_in_flight(&self, url: &str) -> bool {
+        self.in_flight
+            .lock()
+            .map(|set| set.contains(url))
+            .unwrap_or(false)
+    }
+
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.lock().map(|set| set.len()).unwrap_or(0)
+    }
+
+    // Snapshot reported by the control socket's status command
+    pub async fn status(&self) -> Value {
+        let now = Instant::now();
+        let in_flight: Vec<String> = self
+            .in_flight
+            .lock()
+            .map(|set| set.iter().cloned().collect())
+            .unwrap_or_default();
+
+        let scheduler = self.scheduler.lock().await;
+        let mut sites: Vec<Value> = scheduler
+            .iter()
+            .map(|(url, next)| {
+                json!({
+                    "url": url,
+                    "next_check_in": next.saturating_duration_since(now).as_secs_f64(),
+                    "in_flight": in_flight.iter().any(|busy| busy == url),
+                })
+            })
+            .collect();
+        sites.sort_by(|a, b| a["url"].as_str().cmp(&b["url"].as_str()));
+
+        json!({
+            "ok": true,
+            "pid": std::process::id(),
+            "version": env!("CARGO_PKG_VERSION"),
+            "started_at": self.started_unix,
+            "uptime_secs": self.started_at.elapsed().as_secs(),
+            "tracked_sites": sites.len(),
+            "in_flight": in_flight,
+            "sites": sites,
+        })
+    }
+}
+
+// HTTP client for monitoring checks, built from the current config
+pub fn build_client(config: &Config) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent(config.user_agent.as_str())
+        .timeout(Duration::from_secs_f64(config.timeout))
+        .build()?)
+}
+
+// Returns a check slot when a check ends, or retires it if the limit was lowered meanwhile
+struct CheckSlot {
+    state: Arc<DaemonState>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for CheckSlot {
+    fn drop(&mut self) {
+        let retire = self
+            .state
+            .check_slot_debt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |debt| {
+                debt.checked_sub(1)
+            })
+            .is_ok();
+        if let (true, Some(permit)) = (retire, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+// Removes a site from the in-flight set when its check task ends, even if it panics
+struct InFlightGuard {
+    state: Arc<DaemonState>,
+    url: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut set) = self.state.in_flight.lock() {
+            set.remove(&self.url);
+        }
+        self.state.check_finished.notify_one();
+        self.state.updates.send_modify(|version| *version += 1);
+    }
+}
+
+// Sleep until the given time, waking early on a site change, reload or stop request
+async fn sleep_until_or_woken(state: &DaemonState, deadline: Instant) {
+    tokio::select! {
+        _ = sleep_until(deadline.into()) => {}
+        _ = state.reload.notified() => {}
+    }
+}
+
 // Get the path to the daemon executable
 fn get_daemon_path() -> PathBuf {
     std::env::current_exe().unwrap_or_else(|_| "isup".into())
@@ -27,180 +452,119 @@ fn get_daemon_path() -> PathBuf {
 pub fn start_background_service() -> Result<()> {
     // Check if daemon is already running
     if is_daemon_running() {
-        println!("monitoring service (daemon) is already running");
+        say!("monitoring service (daemon) is already running");
         return Ok(());
     }
-    
-    // Clean up any stale PID file before starting
-    #[cfg(target_family = "unix")]
-    {
-        if std::path::Path::new("/tmp/isup_daemon.pid").exists() {
-            println!("removing stale PID file before starting service");
-            let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-        }
-    }
-    
-    println!("starting monitoring service...");
-    
+
+    say!("starting monitoring service...");
+
     // Start the daemon process
     let daemon_path = get_daemon_path();
-    
+
+    // With the user unit installed, systemd owns the daemon
+    if systemd::unit_installed() {
+        let status = Command::new("systemctl")
+            .args(["--user", "start", "isup.service"])
+            .status()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "failed to start isup.service, see 'journalctl --user -u isup'"
+            ));
+        }
+        say!("✅ monitoring service started with systemd");
+        return Ok(());
+    }
+
     #[cfg(target_family = "unix")]
     {
-        // Use a more reliable approach with explicit output redirection and environment variables
-        // Get the current user ID for DBUS session
-        let uid = std::process::id();
-        
+        // The daemon inherits our environment, including XDG_RUNTIME_DIR, so it
+        // finds the same control socket as the CLI. Only fill in what
+        // desktop notifications need if it's missing.
+        let uid = unsafe { libc::getuid() };
+        let runtime_dir =
+            std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| format!("/run/user/{}", uid));
+
         // Get the current DISPLAY environment variable or default to :0
         let display = std::env::var("DISPLAY").unwrap_or_else(|_| String::from(":0"));
-        
+
         // Construct DBUS session address - critical for notifications
-        let dbus_session = format!("unix:path=/run/user/{}/bus", uid);
-        
+        let dbus_session = std::env::var("DBUS_SESSION_BUS_ADDRESS")
+            .unwrap_or_else(|_| format!("unix:path={}/bus", runtime_dir));
+
         // Create a command with proper environment variables for notifications
+        // The daemon writes its own log; this only catches anything printed outside it, like a panic
         let cmd = format!(
-            "DISPLAY={} DBUS_SESSION_BUS_ADDRESS={} XDG_RUNTIME_DIR=/run/user/{} nohup \"{}\" daemon > /tmp/isup_daemon.log 2>&1 & echo $! > /tmp/isup_daemon.pid",
+            "DISPLAY='{}' DBUS_SESSION_BUS_ADDRESS='{}' nohup \"{}\" daemon > \"{}\" 2>&1 &",
             display,
             dbus_session,
-            uid,
-            daemon_path.display()
+            daemon_path.display(),
+            log::get_log_dir().join("daemon.out").display()
         );
-        
-        let status = Command::new("sh")
-            .arg("-c")
-            .arg(&cmd)
-            .status()?;
-            
+
+        let status = Command::new("sh").arg("-c").arg(&cmd).status()?;
+
         if !status.success() {
             return Err(anyhow::anyhow!("failed to start monitoring daemon"));
         }
-        
-        // Wait briefly for the process to start
-        std::thread::sleep(Duration::from_millis(1000));
-        
-        // Read the PID file to confirm the process started
-        match std::fs::read_to_string("/tmp/isup_daemon.pid") {
-            Ok(pid_str) => {
-                if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                    // Verify the process is actually running
-                    if is_daemon_running() {
-                        println!("monitoring daemon started with PID: {}", pid);
-                    } else {
-                        return Err(anyhow::anyhow!("daemon process failed to start properly"));
-                    }
-                } else {
-                    return Err(anyhow::anyhow!("invalid PID in daemon PID file"));
-                }
-            },
-            Err(_) => {
-                return Err(anyhow::anyhow!("failed to read daemon PID file"));
+
+        // The daemon is up once it holds the lock. If another `isup` started one
+        // at the same moment, ours exits quietly and theirs is the one we find.
+        for _ in 0..30 {
+            std::thread::sleep(Duration::from_millis(100));
+            if let Some(pid) = lock::daemon_pid() {
+                say!("monitoring daemon started with PID: {}", pid);
+                break;
             }
         }
     }
-    
+
     #[cfg(target_family = "windows")]
     {
         // More reliable Windows implementation
+        let output_path = log::get_log_dir().join("daemon.out");
         let status = Command::new("cmd")
             .args(&[
-                "/C", 
-                "start", 
-                "/B", 
-                &format!("\"ISUP Monitor\""), 
-                daemon_path.to_str().unwrap(), 
-                "daemon", 
-                ">", 
-                "%TEMP%\\isup_daemon.log", 
-                "2>&1"
+                "/C",
+                "start",
+                "/B",
+                &format!("\"ISUP Monitor\""),
+                daemon_path.to_str().unwrap(),
+                "daemon",
+                ">",
+                output_path.to_str().unwrap(),
+                "2>&1",
             ])
             .status()?;
-            
+
         if !status.success() {
             return Err(anyhow::anyhow!("failed to start monitoring daemon"));
         }
+
+        // Wait a moment to see if the daemon starts successfully
+        std::thread::sleep(Duration::from_millis(500));
     }
-    
-    // Wait a moment to see if the daemon starts successfully
-    std::thread::sleep(Duration::from_millis(500));
-    
+
     if is_daemon_running() {
-        println!("✅ monitoring service started successfully");
+        say!("✅ monitoring service started successfully");
     } else {
-        println!("⚠️ monitoring service may not have started properly");
-        println!("   Check logs at /tmp/isup_daemon.log for details");
+        say!("⚠️ monitoring service may not have started properly");
+        say!(
+            "   run 'isup logs' or see {} for details",
+            log::get_log_dir().display()
+        );
     }
-    
+
     Ok(())
 }
 
-// Add a new function to check if the daemon is running
+// Check if the daemon is running
 pub fn is_daemon_running() -> bool {
     #[cfg(target_family = "unix")]
     {
-        // Try to read the PID file and check if process exists
-        if let Ok(pid_str) = std::fs::read_to_string("/tmp/isup_daemon.pid") {
-            if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                // On Unix, check if process exists using kill -0
-                let exists = Command::new("kill")
-                    .args(&["-0", &pid.to_string()])
-                    .status()
-                    .map(|status| status.success())
-                    .unwrap_or(false);
-                
-                if !exists {
-                    // Clean up stale PID file if process doesn't exist
-                    println!("found stale PID file for process {} that no longer exists", pid);
-                    let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                    return false;
-                }
-                
-                // Also verify this is actually our daemon process
-                #[cfg(target_os = "macos")]
-                {
-                    // On macOS, use ps to check the command name
-                    let output = Command::new("ps")
-                        .args(&["-p", &pid.to_string(), "-o", "comm="])
-                        .output();
-                    
-                    if let Ok(output) = output {
-                        if output.status.success() {
-                            let cmd = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                            // Check if the process is our daemon
-                            if cmd.contains("isup") {
-                                return true;
-                            } else {
-                                println!("PID {} belongs to '{}', not our daemon", pid, cmd);
-                                let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                                return false;
-                            }
-                        }
-                    }
-                }
-                
-                #[cfg(target_os = "linux")]
-                {
-                    // On Linux, check /proc/{pid}/cmdline
-                    if let Ok(cmdline) = std::fs::read_to_string(format!("/proc/{}/cmdline", pid)) {
-                        if cmdline.contains("isup") {
-                            return true;
-                        } else {
-                            println!("PID {} belongs to another process, not our daemon", pid);
-                            let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                            return false;
-                        }
-                    }
-                }
-                
-                return exists;
-            } else {
-                println!("invalid PID in daemon PID file");
-            }
-        }
-        // Clean up PID file if it's invalid
-        let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-        false
+        // Only a live daemon can hold the lock
+        lock::is_locked()
     }
-    
+
     #[cfg(target_family = "windows")]
     {
         // On Windows, check using tasklist
@@ -218,90 +582,76 @@ pub fn is_daemon_running() -> bool {
 // Function to stop the monitoring service
 pub fn stop_monitoring_service() -> Result<()> {
     if !is_daemon_running() {
-        println!("monitoring service is not running");
-        // Clean up any stale PID file
-        #[cfg(target_family = "unix")]
-        {
-            let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-        }
+        say!("monitoring service is not running");
         return Ok(());
     }
-    
+
     #[cfg(target_family = "unix")]
     {
-        if let Ok(pid_str) = std::fs::read_to_string("/tmp/isup_daemon.pid") {
-            if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                // Set the global flag to stop the service
-                SERVICE_SHOULD_STOP.store(true, Ordering::SeqCst);
-                
-                // Check if the process exists before trying to kill it
-                let process_exists = Command::new("kill")
-                    .args(&["-0", &pid.to_string()])
-                    .status()
-                    .map(|status| status.success())
-                    .unwrap_or(false);
-                
-                if !process_exists {
-                    println!("process with PID {} no longer exists", pid);
-                    let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                    println!("✅ cleaned up stale PID file");
-                    return Ok(());
-                }
-                
-                // Send SIGTERM to gracefully terminate the process
-                let status = Command::new("kill")
-                    .arg(pid.to_string())
-                    .status()?;
-                
-                if status.success() {
-                    // Wait a moment for the service to clean up
-                    std::thread::sleep(Duration::from_millis(500));
-                    
-                    // Check if the process is still running
-                    if !is_daemon_running() {
-                        println!("✅ monitoring service stopped successfully");
-                        
-                        // Clean up the PID file
-                        let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                        return Ok(());
-                    } else {
-                        // If still running, try a more forceful approach
-                        println!("service still running, attempting forceful termination...");
-                        let force_status = Command::new("kill")
-                            .args(&["-9", &pid.to_string()])
-                            .status()?;
-                            
-                        if force_status.success() {
-                            // Wait a moment to ensure process is terminated
-                            std::thread::sleep(Duration::from_millis(300));
-                            
-                            if !is_daemon_running() {
-                                println!("✅ monitoring service stopped successfully");
-                                let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-                                return Ok(());
-                            }
-                        }
-                    }
-                }
-                
-                return Err(anyhow::anyhow!("failed to stop monitoring service, you may need to terminate it manually"));
+        // The daemon may take its whole shutdown grace period to drain checks
+        let grace =
+            Duration::from_secs_f64(config::current().shutdown_grace) + Duration::from_secs(5);
+
+        // Ask the daemon to shut itself down first; signals are the fallback
+        if let Ok(response) =
+            control::send_command(json!({ "cmd": "stop" })).and_then(control::expect_ok)
+        {
+            let in_flight = response["in_flight"].as_u64().unwrap_or(0);
+            if in_flight > 0 {
+                say!("waiting for {} in-flight check(s) to finish...", in_flight);
             }
+
+            if wait_for_daemon_exit(grace) {
+                say!("✅ monitoring service stopped successfully");
+                return Ok(());
+            }
+            say!("service did not stop on request, sending SIGTERM...");
         }
-        
-        // If we get here, there was an issue with the PID file, but we should clean it up
-        let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
-        return Err(anyhow::anyhow!("could not read PID file, cleaned up stale file"));
+
+        // The lock file holds the PID of whoever holds the lock, so this is never a stale PID
+        let Some(pid) = lock::daemon_pid() else {
+            if !is_daemon_running() {
+                say!("✅ monitoring service stopped successfully");
+                return Ok(());
+            }
+            return Err(anyhow::anyhow!(
+                "could not find the daemon's PID, you may need to terminate it manually"
+            ));
+        };
+
+        // Send SIGTERM to gracefully terminate the process
+        let status = Command::new("kill").arg(pid.to_string()).status()?;
+
+        if status.success() && wait_for_daemon_exit(grace) {
+            say!("✅ monitoring service stopped successfully");
+            return Ok(());
+        }
+
+        // If still running, try a more forceful approach
+        say!("service still running, attempting forceful termination...");
+        let force_status = Command::new("kill")
+            .args(["-9", &pid.to_string()])
+            .status()?;
+
+        if force_status.success() && wait_for_daemon_exit(Duration::from_secs(1)) {
+            say!("✅ monitoring service stopped successfully");
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!(
+            "failed to stop monitoring service, you may need to terminate it manually"
+        ))
     }
-    
+
     #[cfg(target_family = "windows")]
     {
         // On Windows, use taskkill
         let status = Command::new("taskkill")
             .args(&["/F", "/IM", "isup.exe", "/T"])
             .status()?;
-            
+
         if status.success() {
-            println!("✅ monitoring service stopped successfully");
+            say!("✅ monitoring service stopped successfully");
             return Ok(());
         } else {
             return Err(anyhow::anyhow!("failed to stop monitoring service"));
@@ -309,275 +659,514 @@ pub fn stop_monitoring_service() -> Result<()> {
     }
 }
 
+// Poll until the daemon releases its lock
+#[cfg(target_family = "unix")]
+fn wait_for_daemon_exit(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        if !is_daemon_running() {
+            return true;
+        }
+    }
+    false
+}
+
 // This function runs the monitoring service - this is the one referenced in main.rs
 pub fn run_monitor_service() -> Result<()> {
+    // Hold the daemon lock for as long as we run, so two daemons never share a database
+    let _lock = match DaemonLock::acquire()? {
+        Some(lock) => lock,
+        None => {
+            log::event(
+                LogLevel::Warn,
+                "daemon_already_running",
+                json!({ "pid": lock::daemon_pid() }),
+            );
+            return Ok(());
+        }
+    };
+
     // Set the service as running
     SERVICE_RUNNING.store(true, Ordering::SeqCst);
     SERVICE_SHOULD_STOP.store(false, Ordering::SeqCst);
-    
-    // Create a file to store the PID for management
-    #[cfg(target_family = "unix")]
-    {
-        let pid = std::process::id();
-        let _ = std::fs::write("/tmp/isup_daemon.pid", pid.to_string());
-    }
-    
+
     // Build a minimal runtime for efficiency
     let runtime = tokio::runtime::Builder::new_current_thread()
         .worker_threads(1) // Use just one worker thread to minimize resource usage
         .enable_io()
         .enable_time()
         .build()?;
-    
-    println!("monitoring service started successfully");
-    
+
+    log::event(
+        LogLevel::Info,
+        "daemon_started",
+        json!({ "pid": std::process::id(), "version": env!("CARGO_PKG_VERSION") }),
+    );
+
+    let state = Arc::new(DaemonState::new()?);
+
     // Run the service with signal handling
     runtime.block_on(async {
+        state.apply_servers();
+        tokio::spawn(statuspage::refresh_periodically());
+
+        // Answer CLI requests on the control socket
+        #[cfg(target_family = "unix")]
+        {
+            let control_state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = control::serve(control_state).await {
+                    log::event(
+                        LogLevel::Error,
+                        "control_socket_failed",
+                        json!({ "error": e.to_string() }),
+                    );
+                }
+            });
+        }
+
         // Set up signal handlers
         #[cfg(target_family = "unix")]
         {
-            let mut term_signal = signal(SignalKind::terminate())
-                .expect("failed to create SIGTERM handler");
-            let mut int_signal = signal(SignalKind::interrupt())
-                .expect("failed to create SIGINT handler");
-            
+            let mut term_signal =
+                signal(SignalKind::terminate()).expect("failed to create SIGTERM handler");
+            let mut int_signal =
+                signal(SignalKind::interrupt()).expect("failed to create SIGINT handler");
+
             // Spawn a task to handle termination signals
+            let signal_state = state.clone();
             tokio::spawn(async move {
                 tokio::select! {
                     _ = term_signal.recv() => {
-                        println!("received SIGTERM signal");
+                        log::event(LogLevel::Info, "signal", json!({ "signal": "SIGTERM" }));
                         SERVICE_SHOULD_STOP.store(true, Ordering::SeqCst);
                     }
                     _ = int_signal.recv() => {
-                        println!("received SIGINT signal");
+                        log::event(LogLevel::Info, "signal", json!({ "signal": "SIGINT" }));
                         SERVICE_SHOULD_STOP.store(true, Ordering::SeqCst);
                     }
                 }
+                signal_state.reload.notify_one();
+            });
+
+            // SIGHUP reloads the config in place
+            let mut hup_signal =
+                signal(SignalKind::hangup()).expect("failed to create SIGHUP handler");
+            let reload_state = state.clone();
+            tokio::spawn(async move {
+                while hup_signal.recv().await.is_some() {
+                    log::event(LogLevel::Info, "signal", json!({ "signal": "SIGHUP" }));
+                    if let Err(e) = reload_state.reload_config() {
+                        log::event(
+                            LogLevel::Error,
+                            "config_reload_failed",
+                            json!({ "error": format!("{:#}", e) }),
+                        );
+                    }
+                }
             });
         }
-        
+
         // The main monitoring loop
-        monitor_sites_loop().await
+        monitor_sites_loop(state).await
     })?;
-    
-    // Clean up
+
+    // Clean up once every check has been drained
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+    // The lock file stays; deleting it could let a new daemon lock a different file
     #[cfg(target_family = "unix")]
     {
-        let _ = std::fs::remove_file("/tmp/isup_daemon.pid");
+        let _ = std::fs::remove_file(control::get_socket_path());
     }
-    
+
     // Service is no longer running
     SERVICE_RUNNING.store(false, Ordering::SeqCst);
-    
+    log::event(LogLevel::Info, "daemon_stopped", json!({}));
+
     Ok(())
 }
 
-// The main monitoring loop
-async fn monitor_sites_loop() -> Result<()> {
-    // Create a shared HTTP client
-    let client = reqwest::Client::builder()
-        .user_agent("isup/0.1.0")
-        .timeout(Duration::from_secs(10))
-        .build()?;
-    
-    // Track the next check time for each site
-    let next_checks: Arc<Mutex<HashMap<String, Instant>>> = Arc::new(Mutex::new(HashMap::new()));
-    
-    println!("starting monitoring loop");
-    
+// The main monitoring loop. It sleeps until the next site is due, or until
+// it is woken by a site change, reload or stop request.
+async fn monitor_sites_loop(state: Arc<DaemonState>) -> Result<()> {
+    // Scheduled checks, kept so shutdown can wait for them
+    let mut checks = JoinSet::new();
+    // History is pruned on startup and then hourly
+    let mut next_prune = Instant::now();
+    // When to re-read the site list; None means only when told about changes
+    let mut next_sync = Some(Instant::now());
+    // Under systemd, the watchdog restarts us if the loop stops pinging
+    let watchdog = systemd::watchdog_interval();
+    let mut next_watchdog = watchdog.map(|_| Instant::now());
+
+    systemd::notify("READY=1");
+
     // Main loop
     loop {
         // Check if we should stop
         if SERVICE_SHOULD_STOP.load(Ordering::SeqCst) {
-            println!("stopping monitoring service due to stop request");
+            log::event(
+                LogLevel::Info,
+                "daemon_stopping",
+                json!({ "in_flight": state.in_flight_count() }),
+            );
+            systemd::notify("STOPPING=1");
+            let grace = Duration::from_secs_f64(config::current().shutdown_grace);
+            drain_checks(&state, &mut checks, grace).await;
             return Ok(());
         }
-        
-        // Get all sites from the database
-        let sites = match get_all_sites() {
-            Ok(sites) => sites,
-            Err(e) => {
-                eprintln!("🚨 error fetching sites: {}", e);
-                sleep(Duration::from_secs(5)).await;
-                continue;
+
+        // Forget checks that have finished
+        while checks.try_join_next().is_some() {}
+
+        let now = Instant::now();
+
+        if let (Some(every), Some(at)) = (watchdog, next_watchdog) {
+            if now >= at {
+                systemd::notify("WATCHDOG=1");
+                next_watchdog = Some(now + every);
             }
-        };
-        
-        if sites.is_empty() {
-            // No sites to monitor, sleep for a bit and check again
-            sleep(Duration::from_secs(5)).await;
-            continue;
         }
-        
-        // Process each site
-        for site in sites {
-            let url = site.url.clone();
-            let interval = site.interval;
-            
-            let mut next_checks_map = next_checks.lock().await;
-            let now = Instant::now();
-            
-            // Check if it's time to check this site
-            let should_check = match next_checks_map.get(&url) {
-                Some(next_time) if *next_time > now => false,
-                _ => true,
-            };
-            
-            if should_check {
-                // Schedule the next check
-                next_checks_map.insert(
-                    url.clone(),
-                    now + Duration::from_secs_f64(interval)
+
+        if now >= next_prune {
+            if let Err(e) = prune_history() {
+                log::event(
+                    LogLevel::Error,
+                    "prune_failed",
+                    json!({ "error": e.to_string() }),
                 );
-                
-                // Clone what we need for the task
-                let url_clone = url.clone();
-                let client_clone = client.clone();
-                
-                // Spawn a task to check the site
-                tokio::spawn(async move {
-                    println!("🔄 checking site: {}", url_clone);
-                    
-                    match check_site(&client_clone, &url_clone).await {
-                        Ok((status, is_success)) => {
-                            let status_code = status.as_u16();
-                            let status_desc = get_status_description(status_code);
-                            let status_text = format!("{} - {}", status_code, status_desc);
-                            
-                            // Get the current site status BEFORE updating it
-                            let previous_status = match get_site_by_url(&url_clone) {
-                                Ok(Some(site)) => site.is_up,
-                                _ => None
-                            };
-                            
-                            // Determine if this is a state change that requires notification
-                            let state_changed = match previous_status {
-                                Some(was_up) => was_up != is_success,
-                                None => false // For first check, don't notify
-                            };
-                            
-                            // Log the status check
-                            println!("🔄 site {} status: {} ({}), previous status: {:?}, state changed: {}", 
-                                url_clone, 
-                                if is_success { "UP" } else { "DOWN" }, 
-                                status_text,
-                                previous_status,
-                                state_changed);
-                            
-                            // Update the site status in the database
-                            if let Err(e) = update_site_status(&url_clone, is_success, &status_desc) {
-                                eprintln!("Failed to update site status: {}", e);
-                            }
-                            
-                            // Send notification if state changed
-                            if state_changed {
-                                println!("🔄 state change detected for {}: was {:?}, now {}", 
-                                    url_clone, 
-                                    previous_status, 
-                                    if is_success { "UP" } else { "DOWN" });
-                                
-                                // Extract just the hostname from URL for cleaner notifications
-                                let site_name = extract_hostname(&url_clone);
-                                
-                                // Send a single notification with simplified content
-                                let notification_result = send_notification(
-                                    &site_name,
-                                    !is_success,
-                                    &status_desc
-                                );
-                                
-                                // Log whether notification was successful
-                                log_notification_attempt(
-                                    &url_clone, 
-                                    !is_success, 
-                                    &status_desc,
-                                    &notification_result
-                                );
-                            }
-                        },
-                        Err(e) => {
-                            println!("🚨 site check failed for {}: {}", url_clone, e);
-                            
-                            // Get the current site status BEFORE updating it
-                            let previous_status = match get_site_by_url(&url_clone) {
-                                Ok(Some(site)) => site.is_up,
-                                _ => None
-                            };
-                            
-                            // Determine if this is a state change that requires notification
-                            let state_changed = match previous_status {
-                                Some(was_up) => was_up, // If it was up before, we need to notify
-                                None => false // For first check, don't notify
-                            };
-                            
-                            // Get a generic error status description
-                            let status_desc = get_status_description(503); // Service Unavailable
-                            
-                            // Site is down due to connection error
-                            if let Err(db_err) = update_site_status(&url_clone, false, &status_desc) {
-                                eprintln!("Failed to update site status: {}", db_err);
-                            }
-                            
-                            // Send notification if state changed
-                            if state_changed {
-                                println!("🔄 state change detected for {}: was {:?}, now DOWN (error)", 
-                                    url_clone, 
-                                    previous_status);
-                                
-                                // Extract just the hostname from URL for cleaner notifications
-                                let site_name = extract_hostname(&url_clone);
-                                
-                                // Send a single notification with simplified content
-                                let notification_result = send_notification(
-                                    &site_name,
-                                    true,
-                                    &status_desc
-                                );
-                                
-                                // Log whether notification was successful
-                                log_notification_attempt(
-                                    &url_clone, 
-                                    true, 
-                                    &status_desc,
-                                    &notification_result
-                                );
-                            }
-                        }
-                    }
-                });
             }
+            next_prune = now + Duration::from_secs(3600);
+        }
+
+        if state.take_resync() || next_sync.is_some_and(|at| at <= now) {
+            match get_all_sites() {
+                Ok(sites) => {
+                    state.scheduler.lock().await.sync(&sites, now);
+                    metrics::retain_sites(
+                        &sites
+                            .iter()
+                            .filter(|site| !site.paused)
+                            .map(|site| site.url.as_str())
+                            .collect(),
+                    );
+                    next_sync = RESYNC_INTERVAL.map(|every| now + every);
+                }
+                Err(e) => {
+                    log::event(
+                        LogLevel::Error,
+                        "sync_failed",
+                        json!({ "error": e.to_string() }),
+                    );
+                    next_sync = Some(now + Duration::from_secs(5));
+                }
+            }
+        }
+
+        let (due, next_due) = {
+            let mut scheduler = state.scheduler.lock().await;
+            (
+                scheduler.pop_due(now, config::current().jitter),
+                scheduler.next_due(),
+            )
+        };
+
+        for (url, lag) in due {
+            metrics::record_scheduler_lag(lag);
+
+            // Never overlap checks of one site; this one waits for its next turn
+            let Some(guard) = state.begin_check(&url) else {
+                metrics::record_skipped_check();
+                log::event(
+                    LogLevel::Warn,
+                    "check_skipped",
+                    json!({ "url": url, "reason": "previous check still running" }),
+                );
+                continue;
+            };
+
+            // Spawn a task to check the site
+            let task_state = state.clone();
+            checks.spawn(async move {
+                let url = guard.url.clone();
+                if let Err(e) = task_state.run_check(guard).await {
+                    log::event(
+                        LogLevel::Error,
+                        "check_failed",
+                        json!({ "url": url, "error": format!("{:#}", e) }),
+                    );
+                }
+            });
+        }
+
+        let wake = [Some(next_prune), next_sync, next_due, next_watchdog]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(next_prune);
+        sleep_until_or_woken(&state, wake).await;
+    }
+}
+
+// Wait up to the configured grace period for running checks, including their
+// database updates and notifications, then cancel whatever is left
+async fn drain_checks(state: &DaemonState, checks: &mut JoinSet<()>, grace: Duration) {
+    let deadline = Instant::now() + grace;
+
+    let running = state.in_flight_count();
+    if running > 0 {
+        log::event(
+            LogLevel::Info,
+            "drain_started",
+            json!({ "in_flight": running, "grace_secs": grace.as_secs_f64() }),
+        );
+    }
+
+    // Checks started over the control socket aren't in `checks`,
+    // so the in-flight set is what decides when we're done
+    while state.in_flight_count() > 0 && Instant::now() < deadline {
+        tokio::select! {
+            _ = state.check_finished.notified() => {}
+            _ = sleep_until(deadline.into()) => {}
+        }
+    }
+
+    let cancelled = state.in_flight_count();
+    checks.abort_all();
+    while checks.join_next().await.is_some() {}
+
+    if cancelled > 0 {
+        log::event(
+            LogLevel::Warn,
+            "drain_expired",
+            json!({ "cancelled": cancelled }),
+        );
+    } else if running > 0 {
+        log::event(
+            LogLevel::Info,
+            "drain_finished",
+            json!({ "finished": running }),
+        );
+    }
+}
+
+// Outcome of a monitoring check once it has been recorded
+#[derive(Debug, Clone)]
+pub struct SiteCheck {
+    pub url: String,
+    pub is_up: bool,
+    pub degraded: bool,
+    pub status_code: Option<u16>,
+    pub status_desc: String,
+    pub latency_ms: Option<i64>,
+    pub state_changed: bool,
+}
+
+impl SiteCheck {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "url": self.url,
+            "is_up": self.is_up,
+            "degraded": self.degraded,
+            "status_code": self.status_code,
+            "status": self.status_desc,
+            "latency_ms": self.latency_ms,
+            "state_changed": self.state_changed,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        Some(SiteCheck {
+            url: value.get("url")?.as_str()?.to_string(),
+            is_up: value.get("is_up")?.as_bool()?,
+            degraded: value
+                .get("degraded")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            status_code: value
+                .get("status_code")
+                .and_then(Value::as_u64)
+                .map(|code| code as u16),
+            status_desc: value
+                .get("status")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            latency_ms: value.get("latency_ms").and_then(Value::as_i64),
+            state_changed: value
+                .get("state_changed")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        })
+    }
+}
+
+// Check a site, store the result and send a notification if its state changed.
+// Used by the daemon loop and anywhere else a check should behave exactly like the daemon's.
+pub async fn check_and_record(client: &reqwest::Client, url: &str) -> SiteCheck {
+    // Get the current site BEFORE checking it, for its timeout and previous status.
+    // SQLite and the notifier block, so they run off the runtime's threads.
+    let site = {
+        let url = url.to_string();
+        tokio::task::spawn_blocking(move || get_site_by_url(&url))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .flatten()
+    };
+    let previous_state = site
+        .as_ref()
+        .and_then(|site| Some(health(site.is_up?, site.degraded)));
+    let timeout = site
+        .as_ref()
+        .map(|site| site.timeout_secs())
+        .unwrap_or_else(|| config::current().timeout);
+
+    log::event(
+        LogLevel::Debug,
+        "check_started",
+        json!({ "url": url, "timeout_secs": timeout }),
+    );
+
+    let started = Instant::now();
+    let timeout = bounded_duration(timeout, 0.001, MAX_TIMEOUT);
+    let outcome = probe::run(client, url, site.as_ref(), timeout).await;
+    metrics::record_check(
+        url,
+        outcome.is_up,
+        outcome.status_code,
+        started.elapsed(),
+        outcome.failure,
+    );
+
+    // Certificate expiry is only worth the extra handshake when someone scrapes it
+    if url.starts_with("https://")
+        && config::current().metrics_addr.is_some()
+        && metrics::cert_probe_due(url)
+    {
+        match cert::fetch_expiry(url, timeout).await {
+            Ok(expires) => {
+                let days = (expires - chrono::Utc::now()).num_seconds() as f64 / 86400.0;
+                metrics::record_cert_expiry(url, days);
+            }
+            Err(e) => log::event(
+                LogLevel::Debug,
+                "cert_probe_failed",
+                json!({ "url": url, "error": format!("{:#}", e) }),
+            ),
         }
-        
-        // Sleep for a short time before the next iteration
-        // This makes the loop responsive while using minimal resources
-        sleep(Duration::from_millis(100)).await;
+    }
+
+    let state = health(outcome.is_up, outcome.degraded);
+
+    // Determine if this is a state change that requires notification
+    let state_changed = match previous_state {
+        Some(previous) => previous != state,
+        None => false, // For first check, don't notify
+    };
+
+    log::event(
+        if outcome.is_up && !outcome.degraded {
+            LogLevel::Info
+        } else {
+            LogLevel::Warn
+        },
+        "check_result",
+        json!({
+            "url": url,
+            "up": outcome.is_up,
+            "degraded": outcome.degraded.then_some(true),
+            "status_code": outcome.status_code,
+            "status": outcome.status,
+            "latency_ms": outcome.latency_ms,
+            "error": outcome.error,
+        }),
+    );
+
+    // Update the site status in the database
+    let recorded = {
+        let (url, status) = (url.to_string(), outcome.status.clone());
+        let (is_up, degraded, status_code, latency_ms) = (
+            outcome.is_up,
+            outcome.degraded,
+            outcome.status_code,
+            outcome.latency_ms,
+        );
+        tokio::task::spawn_blocking(move || {
+            update_site_status(&url, is_up, degraded, &status, status_code, latency_ms)
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.into()))
+    };
+    if let Err(e) = recorded {
+        log::event(
+            LogLevel::Error,
+            "record_failed",
+            json!({ "url": url, "error": e.to_string() }),
+        );
+    }
+
+    // Send notification if state changed
+    if let (true, Some(previous)) = (state_changed, previous_state) {
+        log::event(
+            LogLevel::Info,
+            "state_change",
+            json!({ "url": url, "from": previous, "to": state }),
+        );
+
+        // Extract just the hostname from URL for cleaner notifications
+        let site_name = extract_hostname(url);
+
+        // Delivery is logged by the notifier; a failed notification never fails the check
+        let (url, status) = (url.to_string(), outcome.status.clone());
+        let _ = tokio::task::spawn_blocking(move || {
+            send_notification(&url, &site_name, state, &status)
+        })
+        .await;
+    }
+
+    SiteCheck {
+        url: url.to_string(),
+        is_up: outcome.is_up,
+        degraded: outcome.degraded,
+        status_code: outcome.status_code,
+        status_desc: outcome.status,
+        latency_ms: outcome.latency_ms,
+        state_changed,
+    }
+}
+
+// Name of a check result's state: up, degraded or down
+fn health(is_up: bool, degraded: bool) -> &'static str {
+    match (is_up, degraded) {
+        (true, false) => "up",
+        (true, true) => "degraded",
+        (false, _) => "down",
     }
 }
 
 // Helper function to extract the hostname from a URL
 fn extract_hostname(url: &str) -> String {
-    // Remove protocol (http:// or https://)
-    let without_protocol = url.trim_start_matches("http://").trim_start_matches("https://");
-    
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    // Other monitors are named by what they watch: a process, a unit, a path...
+    if scheme != "http" && scheme != "https" {
+        return rest.split('?').next().unwrap_or(rest).to_string();
+    }
+
     // Extract domain (everything up to the first / or the entire string if no /)
-    let domain = match without_protocol.find('/') {
-        Some(pos) => &without_protocol[..pos],
-        None => without_protocol,
+    let domain = match rest.find('/') {
+        Some(pos) => &rest[..pos],
+        None => rest,
     };
-    
+
     // Remove www. prefix if present
     let hostname = domain.trim_start_matches("www.");
-    
-    hostname.to_string()
-}
 
-// Check a single site
-async fn check_site(client: &reqwest::Client, url: &str) -> Result<(StatusCode, bool)> {
-    let response = client.get(url).send().await?;
-    let status = response.status();
-    let is_success = status.is_success();
-    
-    Ok((status, is_success))
+    hostname.to_string()
 }
 
 // Add platform-specific signal handling
@@ -589,6 +1178,7 @@ use tokio::signal::windows::ctrl_c;
 
 // Then wherever you're using the signal handling, wrap it in platform-specific code:
 #[cfg(unix)]
+#[allow(dead_code)]
 async fn handle_shutdown_signals() {
     let mut sigterm = signal(SignalKind::terminate()).unwrap();
     let mut sigint = signal(SignalKind::interrupt()).unwrap();
@@ -599,7 +1189,149 @@ async fn handle_shutdown_signals() {
 }
 
 #[cfg(windows)]
+#[allow(dead_code)]
 async fn handle_shutdown_signals() {
     let mut ctrlc = ctrl_c().unwrap();
     ctrlc.recv().await;
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts checks as they run, and the most that ever ran at once
+    #[derive(Default)]
+    struct Probe {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+        finished: AtomicUsize,
+    }
+
+    // Daemon state whose checks take `takes` instead of probing anything
+    fn state_with_stub(limit: usize, takes: Duration) -> (Arc<DaemonState>, Arc<Probe>) {
+        let probe = Arc::new(Probe::default());
+        let counts = probe.clone();
+        let config = Config {
+            max_concurrent_checks: limit,
+            ..Default::default()
+        };
+        let check: CheckFn = Box::new(move |_, url| {
+            let counts = counts.clone();
+            Box::pin(async move {
+                let running = counts.running.fetch_add(1, Ordering::SeqCst) + 1;
+                counts.peak.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(takes).await;
+                counts.running.fetch_sub(1, Ordering::SeqCst);
+                counts.finished.fetch_add(1, Ordering::SeqCst);
+                SiteCheck {
+                    url,
+                    is_up: true,
+                    degraded: false,
+                    status_code: Some(200),
+                    status_desc: "OK".to_string(),
+                    latency_ms: Some(1),
+                    state_changed: false,
+                }
+            })
+        });
+        (
+            Arc::new(DaemonState::with_check(&config, check).unwrap()),
+            probe,
+        )
+    }
+
+    fn spawn_checks(state: &Arc<DaemonState>, checks: &mut JoinSet<()>, count: usize) {
+        for n in 0..count {
+            let guard = state
+                .begin_check(&format!("https://site{}.test", n))
+                .unwrap();
+            let task_state = state.clone();
+            checks.spawn(async move {
+                task_state.run_check(guard).await.unwrap();
+            });
+        }
+    }
+
+    async fn run_checks(state: &Arc<DaemonState>, count: usize) {
+        let mut checks = JoinSet::new();
+        spawn_checks(state, &mut checks, count);
+        while checks.join_next().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn max_concurrent_checks_is_respected() {
+        let (state, probe) = state_with_stub(2, Duration::from_millis(50));
+        run_checks(&state, 6).await;
+        assert_eq!(probe.finished.load(Ordering::SeqCst), 6);
+        assert_eq!(probe.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(state.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn lowering_the_limit_takes_effect_as_checks_finish() {
+        let (state, probe) = state_with_stub(4, Duration::from_millis(50));
+        let busy = state.clone();
+        let first = tokio::spawn(async move { run_checks(&busy, 4).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        state.resize_check_slots(1);
+        first.await.unwrap();
+
+        probe.peak.store(0, Ordering::SeqCst);
+        run_checks(&state, 3).await;
+        assert_eq!(probe.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_site_in_flight_is_not_checked_twice() {
+        let (state, probe) = state_with_stub(4, Duration::from_millis(50));
+        let url = "https://busy.test";
+
+        let guard = state.begin_check(url).unwrap();
+        assert!(state.is_in_flight(url));
+        assert!(state.begin_check(url).is_none());
+        let site = Site {
+            url: url.to_string(),
+            interval: 60.0,
+            ..Default::default()
+        };
+        assert!(state.check_now(&site).await.is_err());
+
+        state.run_check(guard).await.unwrap();
+        assert_eq!(probe.finished.load(Ordering::SeqCst), 1);
+        assert!(!state.is_in_flight(url));
+
+        // Free again once the first check is done
+        let check = state.check_now(&site).await.unwrap();
+        assert_eq!(check.url, url);
+        assert_eq!(probe.finished.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_checks_that_finish_within_the_grace_period() {
+        let (state, probe) = state_with_stub(4, Duration::from_millis(100));
+        let mut checks = JoinSet::new();
+        spawn_checks(&state, &mut checks, 3);
+
+        let started = Instant::now();
+        drain_checks(&state, &mut checks, Duration::from_secs(5)).await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(probe.finished.load(Ordering::SeqCst), 3);
+        assert_eq!(state.in_flight_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn shutdown_cancels_checks_still_running_after_the_grace_period() {
+        let (state, probe) = state_with_stub(4, Duration::from_secs(60));
+        let mut checks = JoinSet::new();
+        spawn_checks(&state, &mut checks, 2);
+
+        let started = Instant::now();
+        drain_checks(&state, &mut checks, Duration::from_millis(200)).await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(probe.finished.load(Ordering::SeqCst), 0);
+        assert_eq!(state.in_flight_count(), 0);
+        assert!(checks.is_empty());
+    }
+}
@@ -0,0 +1,505 @@
+use anyhow::{Context, Result};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::monitor::config::{self, LogLevel};
+use crate::monitor::db::{
+    get_all_sites, get_daily_uptime, get_incidents, get_latency_buckets, Incident, Site,
+};
+use crate::monitor::http::{not_found, text_response};
+use crate::monitor::log;
+use crate::utils::{format_duration, get_data_dir};
+
+// A static status page built from the check history: index.html for people
+// and status.json for scripts. Sites are grouped by host. Each one gets its
+// current state, a bar per day for the last 90 days, a latency chart for the
+// last 24 hours and its recent incidents. All times are in UTC.
+
+const DAYS: i64 = 90;
+// Latency is averaged over windows of this many seconds
+const LATENCY_BUCKET_SECS: i64 = 15 * 60;
+const MAX_INCIDENTS: usize = 50;
+// How often the daemon rebuilds the page
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Where the daemon writes the page when statuspage_dir isn't set
+pub fn default_dir() -> PathBuf {
+    get_data_dir().join("statuspage")
+}
+
+struct SiteReport {
+    site: Site,
+    // Uptime percentage for each of the last DAYS days, oldest first
+    days: Vec<Option<f64>>,
+    uptime: Option<f64>,
+    latency: Vec<(i64, f64)>,
+    incidents: Vec<Incident>,
+}
+
+impl SiteReport {
+    fn state(&self) -> &'static str {
+        self.site.state()
+    }
+
+    fn group(&self) -> String {
+        reqwest::Url::parse(&self.site.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.site.url.clone())
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn collect(now: i64) -> Result<Vec<SiteReport>> {
+    let today = now / 86400;
+    let first_day = today - DAYS + 1;
+
+    let mut reports = Vec::new();
+    for site in get_all_sites()? {
+        let id = site.id.unwrap_or_default();
+
+        let counts = get_daily_uptime(id, first_day * 86400)?;
+        let by_day: BTreeMap<i64, (i64, i64)> = counts
+            .iter()
+            .map(|(day, total, up)| (*day, (*total, *up)))
+            .collect();
+        let days = (first_day..=today)
+            .map(|day| {
+                by_day
+                    .get(&day)
+                    .filter(|(total, _)| *total > 0)
+                    .map(|(total, up)| *up as f64 * 100.0 / *total as f64)
+            })
+            .collect();
+        let (total, up) = counts
+            .iter()
+            .fold((0, 0), |(total, up), (_, t, u)| (total + t, up + u));
+
+        reports.push(SiteReport {
+            days,
+            uptime: (total > 0).then(|| up as f64 * 100.0 / total as f64),
+            latency: get_latency_buckets(id, now - 86400, LATENCY_BUCKET_SECS)?,
+            incidents: get_incidents(id, first_day * 86400)?,
+            site,
+        });
+    }
+    Ok(reports)
+}
+
+// Build the page into `out`, returning how many sites it covers
+pub fn build(out: &Path, title: &str) -> Result<usize> {
+    let now = now_secs();
+    let reports = collect(now)?;
+
+    std::fs::create_dir_all(out).with_context(|| format!("failed to create {}", out.display()))?;
+    write_atomic(&out.join("index.html"), &render_html(&reports, title, now))?;
+    write_atomic(
+        &out.join("status.json"),
+        &format!("{:#}\n", render_json(&reports, title, now)),
+    )?;
+
+    Ok(reports.len())
+}
+
+// Write through a temporary file, so a server never hands out half a page
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, contents)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    std::fs::rename(&temp, path).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render_json(reports: &[SiteReport], title: &str, now: i64) -> Value {
+    let sites: Vec<Value> = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "url": report.site.url,
+                "group": report.group(),
+                "state": report.state(),
+                "status_code": report.site.last_status_code,
+                "latency_ms": report.site.last_latency_ms,
+                "last_checked": report.site.last_checked,
+                "uptime_90d": report.uptime,
+                "daily_uptime": report.days,
+                "incidents": report.incidents.iter().map(|incident| incident.to_json(now)).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({ "title": title, "generated_at": now, "sites": sites })
+}
+
+fn render_html(reports: &[SiteReport], title: &str, now: i64) -> String {
+    let count = |state: &str| {
+        reports
+            .iter()
+            .filter(|report| report.state() == state)
+            .count()
+    };
+    let (banner_class, banner) = match (count("down"), count("degraded")) {
+        (0, 0) => ("up", "All systems operational".to_string()),
+        (0, 1) => ("degraded", "1 site is degraded".to_string()),
+        (0, n) => ("degraded", format!("{} sites are degraded", n)),
+        (1, _) => ("down", "1 site is down".to_string()),
+        (n, _) => ("down", format!("{} sites are down", n)),
+    };
+
+    let mut groups: BTreeMap<String, Vec<&SiteReport>> = BTreeMap::new();
+    for report in reports {
+        groups.entry(report.group()).or_default().push(report);
+    }
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!doctype html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<main>
+<h1>{title}</h1>
+<div class=\"banner {banner_class}\">{banner}</div>
+",
+        title = escape(title),
+    );
+
+    for (group, reports) in &groups {
+        let _ = writeln!(
+            html,
+            "<section class=\"group\">\n<h2>{}</h2>",
+            escape(group)
+        );
+        for report in reports {
+            render_site(&mut html, report, now);
+        }
+        html.push_str("</section>\n");
+    }
+
+    let mut incidents: Vec<(&Site, &Incident)> = reports
+        .iter()
+        .flat_map(|report| {
+            report
+                .incidents
+                .iter()
+                .map(move |incident| (&report.site, incident))
+        })
+        .collect();
+    incidents.sort_by_key(|(_, incident)| std::cmp::Reverse(incident.started));
+
+    html.push_str("<section>\n<h2>Incident history</h2>\n");
+    if incidents.is_empty() {
+        let _ = writeln!(
+            html,
+            "<p class=\"muted\">No incidents in the last {} days.</p>",
+            DAYS
+        );
+    } else {
+        html.push_str(
+            "<table>\n<tr><th>Site</th><th>Started</th><th>Duration</th><th>Status</th></tr>\n",
+        );
+        for (site, incident) in incidents.iter().take(MAX_INCIDENTS) {
+            let duration = match incident.ended {
+                Some(ended) => format_duration(ended - incident.started),
+                None => format!("ongoing, {}", format_duration(now - incident.started)),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&site.url),
+                format_time(incident.started),
+                duration,
+                escape(incident.status.as_deref().unwrap_or("-")),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</section>\n");
+
+    let _ = write!(
+        html,
+        "<footer>Updated {} &middot; generated by isup</footer>\n</main>\n</body>\n</html>\n",
+        format_time(now)
+    );
+    html
+}
+
+fn render_site(html: &mut String, report: &SiteReport, now: i64) {
+    let state = report.state();
+    let uptime = report
+        .uptime
+        .map(|pct| format!("{:.2}%", pct))
+        .unwrap_or_else(|| "-".to_string());
+    let detail = match (report.site.last_status_code, report.site.last_latency_ms) {
+        (Some(code), Some(ms)) => format!("{} &middot; {}ms", code, ms),
+        (Some(code), None) => code.to_string(),
+        _ => escape(report.site.last_status.as_deref().unwrap_or("")),
+    };
+
+    let _ = write!(
+        html,
+        "<div class=\"site\">
+<div class=\"row\"><span class=\"dot {state}\"></span><span class=\"url\">{url}</span><span class=\"muted\">{detail}</span><span class=\"uptime\">{uptime} uptime</span></div>
+",
+        url = escape(&report.site.url),
+    );
+
+    // One bar per day, coloured by that day's uptime
+    let first_day = now / 86400 - DAYS + 1;
+    let _ = write!(
+        html,
+        "<svg class=\"bars\" viewBox=\"0 0 {} 24\" preserveAspectRatio=\"none\">",
+        DAYS * 4
+    );
+    for (index, day) in report.days.iter().enumerate() {
+        let class = match day {
+            None => "none",
+            Some(pct) if *pct >= 99.9 => "up",
+            Some(pct) if *pct >= 95.0 => "degraded",
+            Some(_) => "down",
+        };
+        let date = chrono::DateTime::from_timestamp((first_day + index as i64) * 86400, 0)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let label = day
+            .map(|pct| format!("{:.2}%", pct))
+            .unwrap_or_else(|| "no data".to_string());
+        let _ = write!(
+            html,
+            "<rect class=\"{}\" x=\"{}\" width=\"3\" height=\"24\"><title>{}: {}</title></rect>",
+            class,
+            index * 4,
+            date,
+            label
+        );
+    }
+    html.push_str("</svg>\n");
+    let _ = writeln!(
+        html,
+        "<div class=\"axis muted\"><span>{} days ago</span><span>today</span></div>",
+        DAYS
+    );
+
+    render_latency(html, &report.latency, now);
+    html.push_str("</div>\n");
+}
+
+// Line chart of the average latency over the last 24 hours
+fn render_latency(html: &mut String, latency: &[(i64, f64)], now: i64) {
+    if latency.is_empty() {
+        html.push_str("<div class=\"muted small\">No response times in the last 24 hours</div>\n");
+        return;
+    }
+
+    let (width, height) = (360.0, 48.0);
+    let start = now - 86400;
+    let max = latency.iter().map(|(_, ms)| *ms).fold(1.0, f64::max);
+    let points: Vec<String> = latency
+        .iter()
+        .map(|(at, ms)| {
+            let x = (at - start).max(0) as f64 / 86400.0 * width;
+            let y = height - ms / max * (height - 4.0) - 2.0;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+    let average = latency.iter().map(|(_, ms)| ms).sum::<f64>() / latency.len() as f64;
+
+    let _ = writeln!(
+        html,
+        "<svg class=\"latency\" viewBox=\"0 0 {} {}\" preserveAspectRatio=\"none\"><polyline points=\"{}\"/></svg>
+<div class=\"axis muted small\"><span>response time, last 24h</span><span>avg {:.0}ms &middot; max {:.0}ms</span></div>",
+        width,
+        height,
+        points.join(" "),
+        average,
+        max
+    );
+}
+
+const STYLE: &str = "
+body { margin: 0; background: #f6f7f9; color: #1d2330; font: 15px/1.5 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; }
+main { max-width: 760px; margin: 0 auto; padding: 32px 16px; }
+h1 { font-size: 26px; margin: 0 0 16px; }
+h2 { font-size: 16px; margin: 28px 0 8px; }
+.banner { padding: 14px 18px; border-radius: 8px; color: #fff; font-weight: 600; }
+.banner.up { background: #1f9d55; }
+.banner.degraded { background: #b7791f; }
+.banner.down { background: #cc1f1a; }
+.site { background: #fff; border: 1px solid #e3e6eb; border-radius: 8px; padding: 12px 16px; margin-bottom: 10px; }
+.row { display: flex; align-items: center; gap: 10px; }
+.url { font-weight: 600; flex: 1; overflow-wrap: anywhere; }
+.uptime { font-variant-numeric: tabular-nums; }
+.dot { width: 10px; height: 10px; border-radius: 50%; background: #9aa3b0; flex: none; }
+.dot.up { background: #1f9d55; }
+.dot.degraded { background: #f6c343; }
+.dot.down { background: #cc1f1a; }
+.dot.paused { background: #b7791f; }
+svg.bars { width: 100%; height: 28px; margin-top: 10px; }
+svg.bars .up { fill: #38c172; }
+svg.bars .degraded { fill: #f6c343; }
+svg.bars .down { fill: #e3342f; }
+svg.bars .none { fill: #dfe3e8; }
+svg.latency { width: 100%; height: 48px; margin-top: 8px; }
+svg.latency polyline { fill: none; stroke: #3490dc; stroke-width: 1.5; vector-effect: non-scaling-stroke; }
+.axis { display: flex; justify-content: space-between; font-size: 12px; }
+.muted { color: #6b7482; }
+.small { font-size: 12px; }
+table { width: 100%; border-collapse: collapse; background: #fff; border: 1px solid #e3e6eb; border-radius: 8px; font-size: 14px; }
+th, td { text-align: left; padding: 8px 12px; border-bottom: 1px solid #eef0f3; overflow-wrap: anywhere; }
+footer { margin-top: 32px; font-size: 12px; color: #6b7482; text-align: center; }
+";
+
+// The directory the daemon keeps the page in, if it should keep one at all
+pub fn daemon_dir() -> Option<PathBuf> {
+    let config = config::current();
+    match (&config.statuspage_dir, config.statuspage_addr) {
+        (Some(dir), _) => Some(dir.clone()),
+        (None, Some(_)) => Some(default_dir()),
+        (None, None) => None,
+    }
+}
+
+// Rebuild the page every REFRESH_INTERVAL while statuspage_dir or statuspage_addr is set
+pub async fn refresh_periodically() {
+    loop {
+        if let Some(dir) = daemon_dir() {
+            let title = config::current().statuspage_title.clone();
+            let result = tokio::task::spawn_blocking(move || build(&dir, &title)).await;
+            if let Some(e) = match result {
+                Ok(result) => result.err(),
+                Err(e) => Some(e.into()),
+            } {
+                log::event(
+                    LogLevel::Error,
+                    "statuspage_failed",
+                    json!({ "error": format!("{:#}", e) }),
+                );
+            }
+        }
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
+
+// Serve the generated page on statuspage_addr
+pub async fn handle(request: Request<Body>) -> Response<Body> {
+    let (file, content_type) = match request.uri().path() {
+        "/" | "/index.html" => ("index.html", "text/html; charset=utf-8"),
+        "/status.json" => ("status.json", "application/json"),
+        _ => return not_found(),
+    };
+    if request.method() != Method::GET {
+        return not_found();
+    }
+
+    let Some(dir) = daemon_dir() else {
+        return not_found();
+    };
+    match tokio::fs::read(dir.join(file)).await {
+        Ok(contents) => text_response(StatusCode::OK, content_type, contents),
+        Err(_) => text_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "text/plain; charset=utf-8",
+            "the status page hasn't been generated yet\n",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_760_000_000;
+
+    fn report(url: &str, is_up: Option<bool>, incidents: Vec<Incident>) -> SiteReport {
+        SiteReport {
+            site: Site {
+                url: url.to_string(),
+                is_up,
+                ..Default::default()
+            },
+            days: vec![None; DAYS as usize],
+            uptime: None,
+            latency: Vec::new(),
+            incidents,
+        }
+    }
+
+    #[test]
+    fn sites_are_grouped_by_host() {
+        let reports = [
+            report("https://b.test/health", Some(true), Vec::new()),
+            report("https://a.test/", Some(true), Vec::new()),
+            report("https://b.test/api", Some(false), Vec::new()),
+        ];
+        let html = render_html(&reports, "Status", NOW);
+
+        let a = html.find("<h2>a.test</h2>").expect("a.test group");
+        let b = html.find("<h2>b.test</h2>").expect("b.test group");
+        assert!(a < b, "groups are sorted by host");
+        assert_eq!(html.matches("<h2>b.test</h2>").count(), 1);
+        let (health, api) = (
+            html.find("https://b.test/health").unwrap(),
+            html.find("https://b.test/api").unwrap(),
+        );
+        assert!(b < health && b < api);
+        assert!(html.contains("1 site is down"));
+
+        let json = render_json(&reports, "Status", NOW);
+        assert_eq!(json["sites"][0]["group"], "b.test");
+        assert_eq!(json["sites"][2]["state"], "down");
+    }
+
+    #[test]
+    fn names_and_urls_are_escaped() {
+        let incident = Incident {
+            started: NOW - 600,
+            ended: None,
+            status: Some("<b>bad</b> gateway".to_string()),
+            failed_checks: 3,
+        };
+        let reports = [report(
+            "https://x.test/?a=1&b=\"<script>\"",
+            Some(false),
+            vec![incident],
+        )];
+        let html = render_html(&reports, "Tom & Jerry's <Status>", NOW);
+
+        assert!(html.contains("<title>Tom &amp; Jerry&#39;s &lt;Status&gt;</title>"));
+        assert!(html.contains("https://x.test/?a=1&amp;b=&quot;&lt;script&gt;&quot;"));
+        assert!(html.contains("&lt;b&gt;bad&lt;/b&gt; gateway"));
+        assert!(html.contains("ongoing, 10m"));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>bad</b>"));
+    }
+}
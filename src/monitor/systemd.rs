@@ -0,0 +1,313 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::monitor::config;
+use crate::monitor::lock;
+use crate::monitor::service::{is_daemon_running, stop_monitoring_service};
+use crate::output::{emit_record, is_structured};
+
+const UNIT_NAME: &str = "isup.service";
+
+// How often systemd expects to hear from the daemon before restarting it
+const WATCHDOG_SECS: u64 = 60;
+
+// sd_notify: send a state update such as "READY=1" to the service manager.
+// Does nothing unless we were started by systemd with Type=notify.
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy().into_owned();
+
+    // A leading '@' means a socket in the abstract namespace
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(&path),
+    };
+
+    if let (Ok(socket), Ok(addr)) = (UnixDatagram::unbound(), addr) {
+        let _ = socket.send_to_addr(state.as_bytes(), &addr);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) {}
+
+// How often to ping the watchdog: half the interval systemd asked for
+pub fn watchdog_interval() -> Option<Duration> {
+    watchdog_interval_from(
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn watchdog_interval_from(pid: Option<&str>, usec: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // WATCHDOG_PID, when set, says which process the watchdog is meant for
+    if let Some(pid) = pid.and_then(|pid| pid.parse::<u32>().ok()) {
+        if pid != own_pid {
+            return None;
+        }
+    }
+
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+// Whether stdout goes straight to the journal
+pub fn logging_to_journal() -> bool {
+    static JOURNAL: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *JOURNAL.get_or_init(|| std::env::var_os("JOURNAL_STREAM").is_some())
+}
+
+fn get_unit_path() -> Result<PathBuf> {
+    let config_dir =
+        dirs::config_dir().ok_or_else(|| anyhow!("could not find the user config directory"))?;
+    Ok(config_dir.join("systemd").join("user").join(UNIT_NAME))
+}
+
+// Whether the user unit file exists, so the daemon should be started through systemd
+pub fn unit_installed() -> bool {
+    cfg!(target_os = "linux") && get_unit_path().map(|path| path.exists()).unwrap_or(false)
+}
+
+fn systemctl(args: &[&str]) -> Result<String> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context("failed to run systemctl")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(anyhow!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            stderr
+        ))
+    }
+}
+
+// Query commands like is-active exit non-zero for "inactive"; we only want the word
+fn systemctl_query(args: &[&str]) -> String {
+    Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .ok()
+        .filter(|state| !state.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn unit_file() -> Result<String> {
+    let exe = std::env::current_exe().context("failed to find the isup executable")?;
+    Ok(render_unit(&exe, config::current().shutdown_grace))
+}
+
+fn render_unit(exe: &Path, shutdown_grace: f64) -> String {
+    // Leave room for in-flight checks to drain before systemd kills us
+    let stop_timeout = shutdown_grace.ceil() as u64 + 10;
+
+    format!(
+        "[Unit]
+Description=isup site monitoring daemon
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart=\"{}\" daemon --foreground
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=5
+WatchdogSec={}
+TimeoutStopSec={}
+
+[Install]
+WantedBy=default.target
+",
+        exe.display(),
+        WATCHDOG_SECS,
+        stop_timeout
+    )
+}
+
+// Write and enable the user unit, then start the daemon under systemd
+pub fn install_service() -> Result<()> {
+    ensure_supported()?;
+
+    let path = get_unit_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, unit_file()?)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+
+    // A daemon started outside systemd holds the lock and would make the unit fail
+    if is_daemon_running() {
+        say!("stopping the monitoring service so systemd can take over...");
+        stop_monitoring_service()?;
+    }
+
+    // Don't leave a unit behind that auto-start would then try to use
+    if let Err(e) =
+        systemctl(&["daemon-reload"]).and_then(|_| systemctl(&["enable", "--now", UNIT_NAME]))
+    {
+        let _ = std::fs::remove_file(&path);
+        return Err(e.context("could not enable the user unit; is a systemd user session running?"));
+    }
+
+    say!("{} {}", "installed".green().bold(), path.display());
+    say!("{} {} is enabled and running", "✓".green(), UNIT_NAME);
+    say!("  logs: journalctl --user -u isup");
+
+    if is_structured() {
+        emit_record(
+            json!({ "unit": UNIT_NAME, "path": path.display().to_string(), "action": "installed" }),
+            &["unit", "path", "action"],
+        );
+    }
+    Ok(())
+}
+
+// Stop and disable the user unit and remove its file
+pub fn uninstall_service() -> Result<()> {
+    ensure_supported()?;
+
+    let path = get_unit_path()?;
+    if !path.exists() {
+        say!("{} is not installed", UNIT_NAME);
+        return Ok(());
+    }
+
+    // Not fatal: the unit may already be stopped or disabled
+    if let Err(e) = systemctl(&["disable", "--now", UNIT_NAME]) {
+        say!("⚠️ {}", e);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    if let Err(e) = systemctl(&["daemon-reload"]) {
+        say!("⚠️ {}", e);
+    }
+
+    say!("{} {}", "removed".green().bold(), path.display());
+
+    if is_structured() {
+        emit_record(
+            json!({ "unit": UNIT_NAME, "path": path.display().to_string(), "action": "uninstalled" }),
+            &["unit", "path", "action"],
+        );
+    }
+    Ok(())
+}
+
+pub fn service_status() -> Result<()> {
+    ensure_supported()?;
+
+    let path = get_unit_path()?;
+    let installed = path.exists();
+    let (enabled, active) = if installed {
+        (
+            systemctl_query(&["is-enabled", UNIT_NAME]),
+            systemctl_query(&["is-active", UNIT_NAME]),
+        )
+    } else {
+        ("not-installed".to_string(), "inactive".to_string())
+    };
+    let pid = lock::daemon_pid();
+
+    if is_structured() {
+        emit_record(
+            json!({
+                "unit": UNIT_NAME,
+                "path": path.display().to_string(),
+                "installed": installed,
+                "enabled": enabled,
+                "active": active,
+                "pid": pid,
+            }),
+            &["unit", "path", "installed", "enabled", "active", "pid"],
+        );
+        return Ok(());
+    }
+
+    if !installed {
+        say!(
+            "{} is not installed. run 'isup service install' to set it up",
+            UNIT_NAME
+        );
+    } else {
+        let active_label = if active == "active" {
+            active.green().bold()
+        } else {
+            active.yellow().bold()
+        };
+        say!("{}  {}", "unit".bold(), path.display());
+        say!("{}  {}", "enabled".bold(), enabled);
+        say!("{}  {}", "active".bold(), active_label);
+    }
+    match pid {
+        Some(pid) => say!("{}  daemon running with PID {}", "pid".bold(), pid),
+        None => say!("{}  no daemon is running", "pid".bold()),
+    }
+    Ok(())
+}
+
+fn ensure_supported() -> Result<()> {
+    if cfg!(target_os = "linux") {
+        Ok(())
+    } else {
+        Err(anyhow!("systemd services are only available on Linux"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval_is_half_of_what_systemd_asks_for() {
+        assert_eq!(
+            watchdog_interval_from(None, Some("60000000"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_interval_from(Some("42"), Some("3"), 42),
+            Some(Duration::from_micros(1))
+        );
+        assert_eq!(watchdog_interval_from(None, None, 42), None);
+        assert_eq!(watchdog_interval_from(None, Some("0"), 42), None);
+        assert_eq!(watchdog_interval_from(None, Some("soon"), 42), None);
+    }
+
+    #[test]
+    fn watchdog_meant_for_another_process_is_ignored() {
+        assert_eq!(
+            watchdog_interval_from(Some("41"), Some("60000000"), 42),
+            None
+        );
+    }
+
+    #[test]
+    fn stop_timeout_follows_the_shutdown_grace() {
+        let exe = Path::new("/usr/local/bin/isup");
+        let unit = render_unit(exe, 10.0);
+        assert!(unit.contains("ExecStart=\"/usr/local/bin/isup\" daemon --foreground\n"));
+        assert!(unit.contains("\nTimeoutStopSec=20\n"));
+        assert!(unit.contains("\nWatchdogSec=60\n"));
+
+        assert!(render_unit(exe, 0.0).contains("\nTimeoutStopSec=10\n"));
+        assert!(render_unit(exe, 2.5).contains("\nTimeoutStopSec=13\n"));
+    }
+}
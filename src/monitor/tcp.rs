@@ -0,0 +1,655 @@
+use anyhow::{anyhow, Context, Result};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::monitor::probe::{reject_credentials, Outcome};
+
+// Protocol probes for services behind a TCP port. An open port only proves
+// something is listening, so each probe speaks just enough of the protocol to
+// see the service answer:
+//
+//     redis://host[:6379]       PING gets PONG
+//     smtp://host[:25]          a 220 banner, then 250 for EHLO
+//     ssh://host[:22]           an SSH-2.0 version banner
+//     postgres://host[:5432]    an answer to an SSL request
+//     mysql://host[:3306]       a protocol 10 handshake
+//     mqtt://host[:1883]        CONNACK for a CONNECT
+//
+// Probes never log in. A server that asks for credentials has answered in
+// its protocol, so it counts as up.
+
+const SCHEMES_AND_PORTS: &[(&str, u16)] = &[
+    ("redis", 6379),
+    ("smtp", 25),
+    ("ssh", 22),
+    ("postgres", 5432),
+    ("postgresql", 5432),
+    ("mysql", 3306),
+    ("mqtt", 1883),
+];
+
+// Longest line read from a server
+const MAX_LINE: usize = 1024;
+
+// Whether `url` is one of the protocols above
+pub fn handles(url: &str) -> bool {
+    url.split_once("://")
+        .is_some_and(|(scheme, _)| SCHEMES_AND_PORTS.iter().any(|(known, _)| *known == scheme))
+}
+
+fn target(url: &str) -> Result<(String, String, u16)> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid url {}", url))?;
+    reject_credentials(&parsed)?;
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(anyhow!("{}:// monitors take no options", parsed.scheme()));
+    }
+    let scheme = parsed.scheme().to_string();
+    let default_port = SCHEMES_AND_PORTS
+        .iter()
+        .find(|(known, _)| *known == scheme)
+        .map(|(_, port)| *port)
+        .ok_or_else(|| anyhow!("unsupported scheme '{}://'", scheme))?;
+    let host = parsed
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| anyhow!("{} needs a host", url))?
+        .trim_matches(|c| c == '[' || c == ']')
+        .to_string();
+    Ok((scheme, host, parsed.port().unwrap_or(default_port)))
+}
+
+// Check that a protocol monitor's URL makes sense before it is stored
+pub fn validate(url: &str) -> Result<()> {
+    target(url).map(|_| ())
+}
+
+// What a protocol exchange found: up, or the protocol error the server gave
+enum Answer {
+    Up(String),
+    Degraded(String),
+    Down(String),
+}
+
+pub async fn check(url: &str, timeout: Duration) -> Outcome {
+    let (scheme, host, port) = match target(url) {
+        Ok(target) => target,
+        Err(e) => return Outcome::down(format!("{:#}", e), "request"),
+    };
+
+    let started = Instant::now();
+    let exchange = async {
+        let mut stream = connect(&host, port).await?;
+        let answer = match scheme.as_str() {
+            "redis" => redis(&mut stream).await,
+            "smtp" => smtp(&mut stream).await,
+            "ssh" => ssh(&mut stream).await,
+            "postgres" | "postgresql" => postgres(&mut stream).await,
+            "mysql" => mysql(&mut stream).await,
+            _ => mqtt(&mut stream).await,
+        };
+        answer.map_err(|e| (e, "protocol"))
+    };
+
+    let answer = match tokio::time::timeout(timeout, exchange).await {
+        Ok(Ok(answer)) => answer,
+        Ok(Err((e, failure))) => {
+            return Outcome {
+                error: Some(format!("{:#}", e)),
+                ..Outcome::down(format!("{:#}", e), failure)
+            }
+        }
+        Err(_) => return Outcome::down("no answer before the timeout", "timeout"),
+    };
+    let latency_ms = Some(started.elapsed().as_millis() as i64);
+
+    let (degraded, status) = match answer {
+        Answer::Up(status) => (false, status),
+        Answer::Degraded(status) => (true, status),
+        Answer::Down(status) => {
+            return Outcome {
+                latency_ms,
+                ..Outcome::down(status, "protocol")
+            }
+        }
+    };
+    Outcome {
+        is_up: true,
+        degraded,
+        status_code: None,
+        status,
+        latency_ms,
+        error: None,
+        failure: None,
+    }
+}
+
+async fn connect(host: &str, port: u16) -> Result<TcpStream, (anyhow::Error, &'static str)> {
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .map(|addrs| addrs.collect())
+        .unwrap_or_default();
+    if addrs.is_empty() {
+        return Err((anyhow!("could not resolve {}", host), "dns"));
+    }
+    TcpStream::connect(&addrs[..])
+        .await
+        .map_err(|e| (anyhow!("cannot connect: {}", e), "connect"))
+}
+
+// One line from the server, without its line ending
+async fn read_line(reader: &mut (impl AsyncBufReadExt + Unpin)) -> Result<String> {
+    let mut line = Vec::new();
+    let read = reader
+        .take(MAX_LINE as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Err(anyhow!("the server closed the connection"));
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+async fn redis(stream: &mut TcpStream) -> Result<Answer> {
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await?;
+    let reply = read_line(&mut BufReader::new(stream)).await?;
+    Ok(
+        match reply
+            .split_once(' ')
+            .map_or(reply.as_str(), |(kind, _)| kind)
+        {
+            "+PONG" => Answer::Up("PONG".to_string()),
+            "-NOAUTH" => Answer::Up("answered, requires authentication".to_string()),
+            // Loading its dataset, or a replica that lost its master
+            "-LOADING" | "-MASTERDOWN" => {
+                Answer::Degraded(reply.trim_start_matches('-').to_string())
+            }
+            _ if reply.starts_with('-') => Answer::Down(reply.trim_start_matches('-').to_string()),
+            _ => Answer::Down(format!("unexpected reply to PING: {}", reply)),
+        },
+    )
+}
+
+// An SMTP reply, which may span lines like "250-first" ... "250 last".
+// Returns the code and the first line's text.
+async fn smtp_reply(reader: &mut (impl AsyncBufReadExt + Unpin)) -> Result<(u16, String)> {
+    let first = read_line(reader).await?;
+    let code = first
+        .get(..3)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("not an SMTP reply: {}", first))?;
+    let mut line = first.clone();
+    while line.as_bytes().get(3) == Some(&b'-') {
+        line = read_line(reader).await?;
+    }
+    Ok((code, first.get(4..).unwrap_or_default().to_string()))
+}
+
+async fn smtp(stream: &mut TcpStream) -> Result<Answer> {
+    let mut reader = BufReader::new(stream);
+    let (code, banner) = smtp_reply(&mut reader).await?;
+    if code != 220 {
+        return Ok(Answer::Down(format!("{} {}", code, banner)));
+    }
+
+    reader.get_mut().write_all(b"EHLO isup\r\n").await?;
+    let (code, text) = smtp_reply(&mut reader).await?;
+    let _ = reader.get_mut().write_all(b"QUIT\r\n").await;
+    Ok(match code {
+        250 => Answer::Up(banner),
+        400..=499 => Answer::Degraded(format!("EHLO: {} {}", code, text)),
+        _ => Answer::Down(format!("EHLO: {} {}", code, text)),
+    })
+}
+
+async fn ssh(stream: &mut TcpStream) -> Result<Answer> {
+    let mut reader = BufReader::new(stream);
+    // Servers may send other lines before their version
+    for _ in 0..10 {
+        let line = read_line(&mut reader).await?;
+        if line.starts_with("SSH-2.0-") || line.starts_with("SSH-1.99-") {
+            return Ok(Answer::Up(line));
+        }
+        if line.starts_with("SSH-") {
+            return Ok(Answer::Down(format!(
+                "unsupported protocol version: {}",
+                line
+            )));
+        }
+    }
+    Ok(Answer::Down("no SSH version banner".to_string()))
+}
+
+// The SSLRequest message. Postgres answers it with one byte before asking
+// for any credentials.
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+async fn postgres(stream: &mut TcpStream) -> Result<Answer> {
+    stream.write_all(&POSTGRES_SSL_REQUEST).await?;
+    let mut reply = [0u8; 1];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .context("no answer to the SSL request")?;
+    Ok(match reply[0] {
+        b'S' => Answer::Up("accepting connections, SSL supported".to_string()),
+        b'N' => Answer::Up("accepting connections, SSL not supported".to_string()),
+        // An ErrorResponse: its fields follow, after a 4 byte length
+        b'E' => {
+            let mut body = vec![0u8; MAX_LINE];
+            let read = stream.read(&mut body).await.unwrap_or(0);
+            Answer::Down(postgres_error(body.get(4..read).unwrap_or_default()))
+        }
+        other => Answer::Down(format!(
+            "unexpected answer to the SSL request: {:#04x}",
+            other
+        )),
+    })
+}
+
+// The message field ('M') of a Postgres ErrorResponse
+fn postgres_error(fields: &[u8]) -> String {
+    fields
+        .split(|b| *b == 0)
+        .find_map(|field| field.strip_prefix(b"M"))
+        .map(|message| String::from_utf8_lossy(message).into_owned())
+        .unwrap_or_else(|| "the server sent an error".to_string())
+}
+
+async fn mysql(stream: &mut TcpStream) -> Result<Answer> {
+    // A packet: 3 byte little-endian length, a sequence number, then the payload
+    let mut header = [0u8; 4];
+    stream
+        .read_exact(&mut header)
+        .await
+        .context("no handshake from the server")?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let mut payload = vec![0u8; len.min(MAX_LINE)];
+    stream
+        .read_exact(&mut payload)
+        .await
+        .context("short handshake packet")?;
+
+    Ok(match payload.first() {
+        Some(10) => {
+            let version = payload[1..].split(|b| *b == 0).next().unwrap_or_default();
+            Answer::Up(format!("MySQL {}", String::from_utf8_lossy(version)))
+        }
+        // An error packet: 0xff, a 2 byte code and the message, e.g. when the
+        // host is blocked or there are too many connections
+        Some(0xff) if payload.len() >= 3 => {
+            let code = u16::from_le_bytes([payload[1], payload[2]]);
+            Answer::Down(format!(
+                "error {}: {}",
+                code,
+                String::from_utf8_lossy(&payload[3..])
+            ))
+        }
+        Some(version) => Answer::Down(format!("unsupported protocol version {}", version)),
+        None => Answer::Down("empty handshake packet".to_string()),
+    })
+}
+
+async fn mqtt(stream: &mut TcpStream) -> Result<Answer> {
+    // CONNECT for MQTT 3.1.1 with a clean session, a 60s keep alive and a
+    // client id unique to this process
+    let client_id = format!("isup-{}", std::process::id());
+    let mut body = vec![0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3c];
+    body.extend_from_slice(&(client_id.len() as u16).to_be_bytes());
+    body.extend_from_slice(client_id.as_bytes());
+    let mut packet = vec![0x10, body.len() as u8];
+    packet.extend_from_slice(&body);
+    stream.write_all(&packet).await?;
+
+    let mut connack = [0u8; 4];
+    stream
+        .read_exact(&mut connack)
+        .await
+        .context("no CONNACK from the broker")?;
+    if connack[0] != 0x20 || connack[1] != 0x02 {
+        return Ok(Answer::Down(format!(
+            "unexpected reply to CONNECT: {:#04x}",
+            connack[0]
+        )));
+    }
+    // DISCONNECT, so the broker doesn't wait out the keep alive
+    let _ = stream.write_all(&[0xe0, 0x00]).await;
+
+    Ok(match connack[3] {
+        0 => Answer::Up("connection accepted".to_string()),
+        4 | 5 => Answer::Up("answered, requires authentication".to_string()),
+        1 => Answer::Down("unacceptable protocol version".to_string()),
+        2 => Answer::Down("client identifier rejected".to_string()),
+        3 => Answer::Down("server unavailable".to_string()),
+        code => Answer::Down(format!("connection refused, return code {}", code)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    // A server on localhost that sends `script` to the first client as soon as
+    // it connects, then reads whatever the client says until it hangs up.
+    // With `hang_up` it closes the connection right after the script instead.
+    async fn serve(script: Vec<u8>, hang_up: bool) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.write_all(&script).await;
+            if hang_up {
+                return;
+            }
+            let mut sink = [0u8; 256];
+            while matches!(stream.read(&mut sink).await, Ok(n) if n > 0) {}
+        });
+        port
+    }
+
+    async fn check_script(scheme: &str, script: impl Into<Vec<u8>>) -> Outcome {
+        let port = serve(script.into(), false).await;
+        check(&format!("{}://127.0.0.1:{}", scheme, port), TIMEOUT).await
+    }
+
+    async fn check_and_hang_up(scheme: &str, script: impl Into<Vec<u8>>) -> Outcome {
+        let port = serve(script.into(), true).await;
+        check(&format!("{}://127.0.0.1:{}", scheme, port), TIMEOUT).await
+    }
+
+    fn assert_up(outcome: &Outcome, status: &str) {
+        assert!(outcome.is_up && !outcome.degraded, "{}", outcome.status);
+        assert_eq!(outcome.status, status);
+        assert!(outcome.latency_ms.is_some());
+    }
+
+    fn assert_degraded(outcome: &Outcome, status: &str) {
+        assert!(outcome.is_up && outcome.degraded, "{}", outcome.status);
+        assert_eq!(outcome.status, status);
+    }
+
+    fn assert_down(outcome: &Outcome, status: &str) {
+        assert!(!outcome.is_up, "{}", outcome.status);
+        assert_eq!(outcome.status, status);
+        assert_eq!(outcome.failure, Some("protocol"));
+    }
+
+    #[tokio::test]
+    async fn redis() {
+        assert_up(&check_script("redis", "+PONG\r\n").await, "PONG");
+        assert_up(
+            &check_script("redis", "-NOAUTH Authentication required.\r\n").await,
+            "answered, requires authentication",
+        );
+        assert_degraded(
+            &check_script(
+                "redis",
+                "-LOADING Redis is loading the dataset in memory\r\n",
+            )
+            .await,
+            "LOADING Redis is loading the dataset in memory",
+        );
+        assert_down(
+            &check_script("redis", "-ERR max number of clients reached\r\n").await,
+            "ERR max number of clients reached",
+        );
+        assert_down(
+            &check_script("redis", "HTTP/1.1 400 Bad Request\r\n").await,
+            "unexpected reply to PING: HTTP/1.1 400 Bad Request",
+        );
+    }
+
+    #[tokio::test]
+    async fn smtp() {
+        let multi_line = concat!(
+            "220 mail.test ESMTP ready\r\n",
+            "250-mail.test\r\n250-PIPELINING\r\n250-SIZE 10240000\r\n250 HELP\r\n",
+        );
+        assert_up(
+            &check_script("smtp", multi_line).await,
+            "mail.test ESMTP ready",
+        );
+
+        let banner = "220-mail.test ESMTP\r\n220 second greeting line\r\n250 mail.test\r\n";
+        assert_up(&check_script("smtp", banner).await, "mail.test ESMTP");
+
+        assert_degraded(
+            &check_script("smtp", "220 mail.test\r\n421 4.7.0 try again later\r\n").await,
+            "EHLO: 421 4.7.0 try again later",
+        );
+        assert_down(
+            &check_script("smtp", "554 no service here\r\n").await,
+            "554 no service here",
+        );
+        assert_down(
+            &check_script("smtp", "220 mail.test\r\n502 not implemented\r\n").await,
+            "EHLO: 502 not implemented",
+        );
+
+        let outcome = check_script("smtp", "* OK IMAP4rev1\r\n").await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "not an SMTP reply: * OK IMAP4rev1");
+    }
+
+    #[tokio::test]
+    async fn ssh() {
+        assert_up(
+            &check_script("ssh", "SSH-2.0-OpenSSH_9.6\r\n").await,
+            "SSH-2.0-OpenSSH_9.6",
+        );
+        assert_up(
+            &check_script(
+                "ssh",
+                "Welcome to the bastion\r\nauthorized use only\r\nSSH-2.0-OpenSSH_9.6\r\n",
+            )
+            .await,
+            "SSH-2.0-OpenSSH_9.6",
+        );
+        assert_up(
+            &check_script("ssh", "SSH-1.99-Cisco-1.25\r\n").await,
+            "SSH-1.99-Cisco-1.25",
+        );
+        assert_down(
+            &check_script("ssh", "SSH-1.5-old\r\n").await,
+            "unsupported protocol version: SSH-1.5-old",
+        );
+        assert_down(
+            &check_script("ssh", "hello\n".repeat(10)).await,
+            "no SSH version banner",
+        );
+    }
+
+    #[tokio::test]
+    async fn postgres() {
+        assert_up(
+            &check_script("postgres", "S").await,
+            "accepting connections, SSL supported",
+        );
+        assert_up(
+            &check_script("postgresql", "N").await,
+            "accepting connections, SSL not supported",
+        );
+
+        let fields = b"SFATAL\0VFATAL\0C53300\0Msorry, too many clients already\0\0";
+        let mut error = vec![b'E'];
+        error.extend(((fields.len() + 4) as u32).to_be_bytes());
+        error.extend(fields);
+        assert_down(
+            &check_script("postgres", error).await,
+            "sorry, too many clients already",
+        );
+
+        assert_down(
+            &check_script("postgres", "H").await,
+            "unexpected answer to the SSL request: 0x48",
+        );
+    }
+
+    #[test]
+    fn postgres_errors() {
+        assert_eq!(postgres_error(b"SERROR\0Mboom\0\0"), "boom");
+        assert_eq!(postgres_error(b"Mfirst\0Msecond\0"), "first");
+        assert_eq!(
+            postgres_error(b"SERROR\0C28000\0"),
+            "the server sent an error"
+        );
+        assert_eq!(
+            postgres_error(b"Mcut off without a termin"),
+            "cut off without a termin"
+        );
+        assert_eq!(postgres_error(b""), "the server sent an error");
+    }
+
+    fn mysql_packet(payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(0);
+        packet.extend(payload);
+        packet
+    }
+
+    #[tokio::test]
+    async fn mysql() {
+        let mut handshake = vec![10];
+        handshake.extend(b"8.0.36\0");
+        handshake.extend([0x2a, 0, 0, 0]);
+        handshake.extend(b"abcdefgh\0");
+        assert_up(
+            &check_script("mysql", mysql_packet(&handshake)).await,
+            "MySQL 8.0.36",
+        );
+
+        let mut error = vec![0xff];
+        error.extend(1040u16.to_le_bytes());
+        error.extend(b"Too many connections");
+        assert_down(
+            &check_script("mysql", mysql_packet(&error)).await,
+            "error 1040: Too many connections",
+        );
+
+        assert_down(
+            &check_script("mysql", mysql_packet(&[9, b'x', 0])).await,
+            "unsupported protocol version 9",
+        );
+        assert_down(
+            &check_script("mysql", mysql_packet(&[])).await,
+            "empty handshake packet",
+        );
+
+        let outcome = check_and_hang_up("mysql", mysql_packet(&handshake)[..10].to_vec()).await;
+        assert!(!outcome.is_up);
+        assert!(
+            outcome.status.starts_with("short handshake packet"),
+            "{}",
+            outcome.status
+        );
+    }
+
+    #[tokio::test]
+    async fn mqtt() {
+        let cases = [
+            (0, Some("connection accepted")),
+            (1, None),
+            (2, None),
+            (3, None),
+            (4, Some("answered, requires authentication")),
+            (5, Some("answered, requires authentication")),
+            (6, None),
+        ];
+        let down = [
+            "unacceptable protocol version",
+            "client identifier rejected",
+            "server unavailable",
+        ];
+        for (code, up) in cases {
+            let outcome = check_script("mqtt", vec![0x20, 0x02, 0x00, code]).await;
+            match (up, code) {
+                (Some(status), _) => assert_up(&outcome, status),
+                (None, 1..=3) => assert_down(&outcome, down[code as usize - 1]),
+                (None, _) => assert_down(
+                    &outcome,
+                    &format!("connection refused, return code {}", code),
+                ),
+            }
+        }
+        assert_down(
+            &check_script("mqtt", vec![0x30, 0x02, 0x00, 0x00]).await,
+            "unexpected reply to CONNECT: 0x30",
+        );
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_cut_at_max_line() {
+        let long = format!("{}\r\nSSH-2.0-next\r\n", "x".repeat(MAX_LINE * 2 + 10));
+        let mut reader = long.as_bytes();
+        assert_eq!(read_line(&mut reader).await.unwrap().len(), MAX_LINE);
+        assert_eq!(read_line(&mut reader).await.unwrap().len(), MAX_LINE);
+        assert_eq!(read_line(&mut reader).await.unwrap(), "x".repeat(10));
+        assert_eq!(read_line(&mut reader).await.unwrap(), "SSH-2.0-next");
+        assert!(read_line(&mut reader).await.is_err());
+
+        // A banner after a long preamble is still found
+        assert_up(&check_script("ssh", long).await, "SSH-2.0-next");
+    }
+
+    #[tokio::test]
+    async fn silent_closed_and_missing_servers_are_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let outcome = check(
+            &format!("ssh://127.0.0.1:{}", port),
+            Duration::from_millis(200),
+        )
+        .await;
+        assert_eq!(
+            (outcome.status.as_str(), outcome.failure),
+            ("no answer before the timeout", Some("timeout"))
+        );
+
+        let outcome = check_and_hang_up("ssh", "").await;
+        assert_eq!(outcome.status, "the server closed the connection");
+        assert_eq!(outcome.failure, Some("protocol"));
+
+        drop(listener);
+        let outcome = check(&format!("redis://127.0.0.1:{}", port), TIMEOUT).await;
+        assert!(
+            outcome.status.starts_with("cannot connect"),
+            "{}",
+            outcome.status
+        );
+        assert_eq!(outcome.failure, Some("connect"));
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            target("redis://cache").unwrap(),
+            ("redis".to_string(), "cache".to_string(), 6379)
+        );
+        assert_eq!(
+            target("postgresql://[::1]:6432").unwrap(),
+            ("postgresql".to_string(), "::1".to_string(), 6432)
+        );
+        assert!(
+            handles("mqtt://broker") && !handles("https://mqtt.test") && !handles("amqp://broker")
+        );
+
+        for (url, error) in [
+            ("redis://:secret@cache", "user name or password"),
+            ("postgres://user:pw@db", "user name or password"),
+            ("mysql://root@db", "user name or password"),
+            ("redis://cache?password=secret", "looks like a secret"),
+            ("smtp://mail?starttls=1", "take no options"),
+            ("ssh://host#frag", "take no options"),
+            ("ssh://", "needs a host"),
+        ] {
+            let message = validate(url)
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            assert!(message.contains(error), "{}: {:?}", url, message);
+        }
+    }
+}
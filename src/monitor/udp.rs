@@ -0,0 +1,725 @@
+use anyhow::{anyhow, Context, Result};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+use crate::monitor::probe::{reject_credentials, Outcome};
+
+// UDP monitors. There is no connection to prove a service is there, so each
+// probe sends a datagram and waits for an answer:
+//
+//     udp://host:port?send=hello&expect=hello   any service; the reply must contain `expect`
+//     ntp://host[:123]                          an NTP server answers a client request
+//     dns://host[:53]?name=example.com&type=A   a DNS server answers a query
+//
+// Lost datagrams are retried: `attempts=N` (default 3) sends up to N of them,
+// splitting the timeout between them. With `loss=degrade`, a site that only
+// answered after a retry is degraded instead of up. A udp:// service that
+// never replies, like a syslog collector, can use `reply=optional`: it is up
+// unless the host reports the port closed.
+
+pub const UDP_SCHEME: &str = "udp://";
+pub const NTP_SCHEME: &str = "ntp://";
+pub const DNS_SCHEME: &str = "dns://";
+pub const SCHEMES: &[&str] = &[UDP_SCHEME, NTP_SCHEME, DNS_SCHEME];
+
+const DEFAULT_ATTEMPTS: u32 = 3;
+// Largest datagram we read; anything longer is cut off
+const MAX_REPLY: usize = 4096;
+
+// DNS record types a query may ask for
+const DNS_TYPES: &[(&str, u16)] = &[
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Kind {
+    Udp,
+    Ntp,
+    Dns,
+}
+
+struct Probe {
+    kind: Kind,
+    host: String,
+    port: u16,
+    send: Vec<u8>,
+    expect: Option<String>,
+    attempts: u32,
+    degrade_on_loss: bool,
+    reply_optional: bool,
+    dns_name: String,
+    dns_type: u16,
+}
+
+impl Probe {
+    fn parse(url: &str) -> Result<Self> {
+        let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid url {}", url))?;
+        reject_credentials(&parsed)?;
+        let (kind, default_port) = match parsed.scheme() {
+            "udp" => (Kind::Udp, None),
+            "ntp" => (Kind::Ntp, Some(123)),
+            "dns" => (Kind::Dns, Some(53)),
+            other => return Err(anyhow!("unsupported scheme '{}://'", other)),
+        };
+        let host = parsed
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| anyhow!("{} needs a host", url))?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string();
+        let port = parsed
+            .port()
+            .or(default_port)
+            .ok_or_else(|| anyhow!("{} needs a port, e.g. udp://{}:514", url, host))?;
+
+        let mut probe = Probe {
+            kind,
+            host,
+            port,
+            send: Vec::new(),
+            expect: None,
+            attempts: DEFAULT_ATTEMPTS,
+            degrade_on_loss: false,
+            reply_optional: false,
+            dns_name: ".".to_string(),
+            dns_type: 2,
+        };
+        for (key, value) in parsed.query_pairs() {
+            match (kind, key.as_ref()) {
+                (_, "attempts") => {
+                    probe.attempts = value
+                        .parse()
+                        .ok()
+                        .filter(|attempts| (1..=10).contains(attempts))
+                        .ok_or_else(|| anyhow!("invalid attempts '{}', expected 1 to 10", value))?;
+                }
+                (_, "loss") => {
+                    probe.degrade_on_loss = match value.as_ref() {
+                        "ignore" => false,
+                        "degrade" => true,
+                        _ => {
+                            return Err(anyhow!(
+                                "invalid loss '{}', expected ignore or degrade",
+                                value
+                            ))
+                        }
+                    };
+                }
+                (Kind::Udp, "send") => probe.send = value.as_bytes().to_vec(),
+                (Kind::Udp, "expect") => probe.expect = Some(value.into_owned()),
+                (Kind::Udp, "reply") => {
+                    probe.reply_optional = match value.as_ref() {
+                        "required" => false,
+                        "optional" => true,
+                        _ => {
+                            return Err(anyhow!(
+                                "invalid reply '{}', expected required or optional",
+                                value
+                            ))
+                        }
+                    };
+                }
+                (Kind::Dns, "name") => probe.dns_name = value.into_owned(),
+                (Kind::Dns, "type") => {
+                    probe.dns_type = DNS_TYPES
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(&value))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| anyhow!("unsupported DNS type '{}'", value))?;
+                }
+                (_, other) => return Err(anyhow!("unknown option '{}' for {}", other, url)),
+            }
+        }
+        if probe.reply_optional && probe.expect.is_some() {
+            return Err(anyhow!("reply=optional can't be combined with expect"));
+        }
+
+        Ok(probe)
+    }
+}
+
+// Check that a UDP monitor's URL makes sense before it is stored
+pub fn validate(url: &str) -> Result<()> {
+    Probe::parse(url).map(|_| ())
+}
+
+pub async fn check(url: &str, timeout: Duration) -> Outcome {
+    let probe = match Probe::parse(url) {
+        Ok(probe) => probe,
+        Err(e) => return Outcome::down(format!("{:#}", e), "request"),
+    };
+
+    let socket = match connect(&probe.host, probe.port).await {
+        Ok(socket) => socket,
+        Err((e, failure)) => {
+            return Outcome {
+                error: Some(format!("{:#}", e)),
+                ..Outcome::down(format!("{:#}", e), failure)
+            }
+        }
+    };
+
+    let dns_id = random_id();
+    let request = match probe.kind {
+        Kind::Udp => probe.send.clone(),
+        Kind::Ntp => ntp_request(),
+        Kind::Dns => match dns_query(dns_id, &probe.dns_name, probe.dns_type) {
+            Ok(query) => query,
+            Err(e) => return Outcome::down(format!("{:#}", e), "request"),
+        },
+    };
+
+    // Send, and send again when nothing comes back in time
+    let per_attempt = timeout / probe.attempts;
+    let mut buf = vec![0u8; MAX_REPLY];
+    let mut reply = None;
+    for attempt in 1..=probe.attempts {
+        let started = Instant::now();
+        if let Err(e) = socket.send(&request).await {
+            return socket_error(e);
+        }
+        let deadline = tokio::time::Instant::now() + per_attempt;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            match received {
+                Ok(len) if probe.kind != Kind::Dns || dns_id_matches(&buf[..len], dns_id) => {
+                    reply = Some((buf[..len].to_vec(), attempt, started.elapsed()));
+                    break;
+                }
+                // Not an answer to our query
+                Ok(_) => continue,
+                Err(e) => return socket_error(e),
+            }
+        }
+        if reply.is_some() {
+            break;
+        }
+    }
+
+    let Some((reply, attempt, rtt)) = reply else {
+        if probe.reply_optional {
+            return Outcome {
+                is_up: true,
+                degraded: false,
+                status_code: None,
+                status: "no reply, port not refused".to_string(),
+                latency_ms: None,
+                error: None,
+                failure: None,
+            };
+        }
+        let sent = if probe.attempts == 1 {
+            "1 datagram".to_string()
+        } else {
+            format!("{} datagrams", probe.attempts)
+        };
+        return Outcome::down(format!("no reply to {}", sent), "timeout");
+    };
+
+    let mut outcome = match probe.kind {
+        Kind::Udp => udp_reply(&reply, probe.expect.as_deref()),
+        Kind::Ntp => ntp_reply(&reply),
+        Kind::Dns => dns_reply(&reply),
+    };
+    outcome.latency_ms = Some(rtt.as_millis() as i64);
+    if attempt > 1 && outcome.is_up {
+        outcome.status = format!("{} (after {} lost)", outcome.status, attempt - 1);
+        outcome.degraded |= probe.degrade_on_loss;
+    }
+    outcome
+}
+
+// A socket connected to the first address `host` resolves to, so the kernel
+// drops datagrams from anyone else and reports ICMP errors
+async fn connect(host: &str, port: u16) -> Result<UdpSocket, (anyhow::Error, &'static str)> {
+    let addr = tokio::net::lookup_host((host, port))
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| (anyhow!("could not resolve {}", host), "dns"))?;
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .map_err(|e| (anyhow!("could not open a socket: {}", e), "connect"))?;
+    socket
+        .connect(addr)
+        .await
+        .map_err(|e| (anyhow!("could not reach {}: {}", addr, e), "connect"))?;
+    Ok(socket)
+}
+
+// ICMP port unreachable comes back as a refused send or receive
+fn socket_error(e: std::io::Error) -> Outcome {
+    let status = if e.kind() == std::io::ErrorKind::ConnectionRefused {
+        "port unreachable".to_string()
+    } else {
+        e.to_string()
+    };
+    Outcome {
+        error: Some(e.to_string()),
+        ..Outcome::down(status, "connect")
+    }
+}
+
+fn up(status: String) -> Outcome {
+    Outcome {
+        is_up: true,
+        degraded: false,
+        status_code: None,
+        status,
+        latency_ms: None,
+        error: None,
+        failure: None,
+    }
+}
+
+fn udp_reply(reply: &[u8], expect: Option<&str>) -> Outcome {
+    let text = String::from_utf8_lossy(reply);
+    match expect {
+        Some(expect) if !text.contains(expect) => {
+            Outcome::down(format!("reply did not contain {:?}", expect), "status")
+        }
+        _ => up(format!("{} byte reply", reply.len())),
+    }
+}
+
+// An NTP v3 client request: leap 0, version 3, mode 3
+fn ntp_request() -> Vec<u8> {
+    let mut packet = vec![0u8; 48];
+    packet[0] = 0x1b;
+    packet
+}
+
+// Seconds between the NTP epoch (1900) and the Unix epoch
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+
+fn ntp_reply(reply: &[u8]) -> Outcome {
+    if reply.len() < 48 || reply[0] & 0x07 != 4 {
+        return Outcome::down("not an NTP server reply", "status");
+    }
+    let leap = reply[0] >> 6;
+    let stratum = reply[1];
+    // Stratum 0 is a kiss-o'-death: the server tells us to go away
+    if stratum == 0 {
+        let code = String::from_utf8_lossy(&reply[12..16])
+            .trim_matches('\0')
+            .to_string();
+        return Outcome::down(format!("kiss-o'-death {}", code), "status");
+    }
+
+    let seconds = u32::from_be_bytes([reply[40], reply[41], reply[42], reply[43]]) as f64;
+    let fraction =
+        u32::from_be_bytes([reply[44], reply[45], reply[46], reply[47]]) as f64 / 4_294_967_296.0;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    let offset_ms = ((seconds + fraction - NTP_UNIX_OFFSET - now) * 1000.0).round() as i64;
+
+    let status = format!("stratum {}, offset {}ms", stratum, offset_ms);
+    // Leap 3 and stratum 16 both mean the server's own clock isn't synchronized
+    if leap == 3 || stratum >= 16 {
+        return Outcome {
+            degraded: true,
+            ..up(format!("{}, clock not synchronized", status))
+        };
+    }
+    up(status)
+}
+
+fn random_id() -> u16 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish() as u16
+}
+
+// A recursive query for `name`
+fn dns_query(id: u16, name: &str, record_type: u16) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(32 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired; one question
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|label| !label.is_empty())
+    {
+        if label.len() > 63 {
+            return Err(anyhow!(
+                "DNS name label '{}' is longer than 63 bytes",
+                label
+            ));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    // Class IN
+    query.extend_from_slice(&[0x00, 0x01]);
+    Ok(query)
+}
+
+fn dns_id_matches(reply: &[u8], id: u16) -> bool {
+    reply.len() >= 2 && u16::from_be_bytes([reply[0], reply[1]]) == id
+}
+
+fn dns_reply(reply: &[u8]) -> Outcome {
+    // Header: id, flags, then question, answer, authority and additional counts
+    if reply.len() < 12 || reply[2] & 0x80 == 0 {
+        return Outcome::down("not a DNS response", "status");
+    }
+    let answers = u16::from_be_bytes([reply[6], reply[7]]);
+    match reply[3] & 0x0f {
+        0 => up(format!(
+            "NOERROR, {} answer{}",
+            answers,
+            if answers == 1 { "" } else { "s" }
+        )),
+        // The server works, but the name it was asked about doesn't exist
+        3 => Outcome {
+            degraded: true,
+            ..up("NXDOMAIN".to_string())
+        },
+        code => {
+            let name = match code {
+                1 => "FORMERR",
+                2 => "SERVFAIL",
+                4 => "NOTIMP",
+                5 => "REFUSED",
+                _ => "error",
+            };
+            Outcome::down(format!("{} (rcode {})", name, code), "status")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // A DNS response header with the given id, RCODE and answer count, after the query's question
+    fn dns_response(query: &[u8], id: u16, rcode: u8, answers: u16) -> Vec<u8> {
+        let mut reply = query.to_vec();
+        reply[..2].copy_from_slice(&id.to_be_bytes());
+        reply[2] = 0x81;
+        reply[3] = 0x80 | rcode;
+        reply[6..8].copy_from_slice(&answers.to_be_bytes());
+        reply
+    }
+
+    // An NTP server reply: mode 4, with `stratum` and a transmit time of `unix` seconds
+    fn ntp_response(leap: u8, stratum: u8, unix: f64) -> Vec<u8> {
+        let mut reply = vec![0u8; 48];
+        reply[0] = (leap << 6) | (4 << 3) | 4;
+        reply[1] = stratum;
+        let ntp = unix + NTP_UNIX_OFFSET;
+        reply[40..44].copy_from_slice(&(ntp as u32).to_be_bytes());
+        reply[44..48].copy_from_slice(&((ntp.fract() * 4_294_967_296.0) as u32).to_be_bytes());
+        reply
+    }
+
+    fn unix_now() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+    }
+
+    #[test]
+    fn dns_queries_are_encoded() {
+        let query = dns_query(0x1234, "example.com.", 1).unwrap();
+        let mut expected = vec![
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        expected.extend(b"\x07example\x03com\x00");
+        expected.extend([0x00, 0x01, 0x00, 0x01]);
+        assert_eq!(query, expected);
+
+        // The root is a single empty label
+        assert_eq!(
+            &dns_query(1, ".", 2).unwrap()[12..],
+            [0x00, 0x00, 0x02, 0x00, 0x01]
+        );
+        assert!(dns_query(1, &format!("{}.com", "a".repeat(64)), 1).is_err());
+    }
+
+    #[test]
+    fn dns_replies_are_matched_by_id() {
+        let reply = dns_response(&dns_query(0xbeef, "example.com", 1).unwrap(), 0xbeef, 0, 1);
+        assert!(dns_id_matches(&reply, 0xbeef));
+        assert!(!dns_id_matches(&reply, 0xbeee));
+        assert!(!dns_id_matches(&[0xbe], 0xbeef));
+        assert!(!dns_id_matches(&[], 0xbeef));
+    }
+
+    #[test]
+    fn dns_rcodes() {
+        let query = dns_query(7, "example.com", 1).unwrap();
+
+        let outcome = dns_reply(&dns_response(&query, 7, 0, 2));
+        assert!(outcome.is_up && !outcome.degraded);
+        assert_eq!(outcome.status, "NOERROR, 2 answers");
+        assert_eq!(
+            dns_reply(&dns_response(&query, 7, 0, 1)).status,
+            "NOERROR, 1 answer"
+        );
+
+        let outcome = dns_reply(&dns_response(&query, 7, 3, 0));
+        assert!(outcome.is_up && outcome.degraded);
+        assert_eq!(outcome.status, "NXDOMAIN");
+
+        for (rcode, status) in [
+            (1, "FORMERR (rcode 1)"),
+            (2, "SERVFAIL (rcode 2)"),
+            (5, "REFUSED (rcode 5)"),
+            (9, "error (rcode 9)"),
+        ] {
+            let outcome = dns_reply(&dns_response(&query, 7, rcode, 0));
+            assert!(!outcome.is_up);
+            assert_eq!(outcome.status, status);
+        }
+    }
+
+    #[test]
+    fn malformed_dns_replies_are_down() {
+        let query = dns_query(7, "example.com", 1).unwrap();
+        // Our own query echoed back isn't a response
+        assert_eq!(dns_reply(&query).status, "not a DNS response");
+        let reply = dns_response(&query, 7, 0, 1);
+        for len in 0..12 {
+            assert!(!dns_reply(&reply[..len]).is_up, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn ntp_replies() {
+        let outcome = ntp_reply(&ntp_response(0, 2, unix_now()));
+        assert!(outcome.is_up && !outcome.degraded, "{}", outcome.status);
+        let offset: i64 = outcome
+            .status
+            .strip_prefix("stratum 2, offset ")
+            .and_then(|rest| rest.strip_suffix("ms"))
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or_else(|| panic!("{}", outcome.status));
+        assert!(offset.abs() < 1000, "{}", offset);
+
+        let outcome = ntp_reply(&ntp_response(0, 2, unix_now() - 90.0));
+        assert!(
+            outcome.status.starts_with("stratum 2, offset -9"),
+            "{}",
+            outcome.status
+        );
+
+        // Unsynchronized clocks are degraded
+        assert!(ntp_reply(&ntp_response(3, 2, unix_now())).degraded);
+        assert!(ntp_reply(&ntp_response(0, 16, unix_now())).degraded);
+
+        let mut kiss = ntp_response(0, 0, 0.0);
+        kiss[12..16].copy_from_slice(b"RATE");
+        let outcome = ntp_reply(&kiss);
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "kiss-o'-death RATE");
+    }
+
+    #[test]
+    fn malformed_ntp_replies_are_down() {
+        let reply = ntp_response(0, 2, unix_now());
+        for len in 0..48 {
+            assert!(!ntp_reply(&reply[..len]).is_up, "{} bytes", len);
+        }
+        // Mode 3 is a client request, not a server reply
+        assert_eq!(ntp_reply(&ntp_request()).status, "not an NTP server reply");
+    }
+
+    #[test]
+    fn udp_replies() {
+        assert!(udp_reply(b"", None).is_up);
+        assert_eq!(udp_reply(b"+pong\n", Some("pong")).status, "6 byte reply");
+        assert!(!udp_reply(b"nope", Some("pong")).is_up);
+    }
+
+    #[test]
+    fn urls_are_parsed() {
+        let probe =
+            Probe::parse("dns://127.0.0.1?name=example.com&type=aaaa&attempts=2&loss=degrade")
+                .unwrap();
+        assert!(
+            probe.kind == Kind::Dns
+                && probe.port == 53
+                && probe.dns_type == 28
+                && probe.attempts == 2
+        );
+        assert!(probe.degrade_on_loss);
+        assert_eq!(probe.dns_name, "example.com");
+        assert_eq!(Probe::parse("ntp://[::1]").unwrap().host, "::1");
+
+        for (url, error) in [
+            ("udp://host", "needs a port"),
+            ("udp://host:514?attempts=0", "invalid attempts"),
+            ("udp://host:514?attempts=11", "invalid attempts"),
+            ("udp://host:514?loss=maybe", "invalid loss"),
+            (
+                "udp://host:514?reply=optional&expect=x",
+                "can't be combined",
+            ),
+            ("ntp://host?send=x", "unknown option 'send'"),
+            ("dns://host?type=ANY", "unsupported DNS type"),
+            ("udp://user:pw@host:514", "user name or password"),
+            ("dns://admin@host", "user name or password"),
+            (
+                "udp://host:514?send=x&token=abc",
+                "'token' looks like a secret",
+            ),
+            ("ntp://host?Api_Key=abc", "'Api_Key' looks like a secret"),
+            ("dns://host?password=", "'password' looks like a secret"),
+        ] {
+            let message = Probe::parse(url)
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            assert!(message.contains(error), "{}: {:?}", url, message);
+        }
+    }
+
+    // A UDP server on localhost that ignores the first `lose` datagrams and
+    // answers the rest with whatever `answer` makes of them
+    async fn server(lose: usize, answer: fn(&[u8]) -> Vec<Vec<u8>>) -> (u16, Arc<AtomicUsize>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let received = Arc::new(AtomicUsize::new(0));
+        let count = received.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                if count.fetch_add(1, Ordering::SeqCst) < lose {
+                    continue;
+                }
+                for reply in answer(&buf[..len]) {
+                    let _ = socket.send_to(&reply, from).await;
+                }
+            }
+        });
+        (port, received)
+    }
+
+    fn pong(_: &[u8]) -> Vec<Vec<u8>> {
+        vec![b"pong".to_vec()]
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(900);
+
+    #[tokio::test]
+    async fn first_reply_is_up() {
+        let (port, received) = server(0, pong).await;
+        let outcome = check(
+            &format!("udp://127.0.0.1:{}?send=ping&expect=pong", port),
+            TIMEOUT,
+        )
+        .await;
+        assert!(outcome.is_up && !outcome.degraded, "{}", outcome.status);
+        assert_eq!(outcome.status, "4 byte reply");
+        assert!(outcome.latency_ms.is_some());
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn lost_datagrams_are_retried() {
+        let (port, received) = server(2, pong).await;
+        let outcome = check(&format!("udp://127.0.0.1:{}?send=ping", port), TIMEOUT).await;
+        assert!(outcome.is_up && !outcome.degraded, "{}", outcome.status);
+        assert_eq!(outcome.status, "4 byte reply (after 2 lost)");
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn loss_can_degrade() {
+        let (port, _) = server(1, pong).await;
+        let outcome = check(&format!("udp://127.0.0.1:{}?loss=degrade", port), TIMEOUT).await;
+        assert!(outcome.is_up && outcome.degraded, "{}", outcome.status);
+        assert_eq!(outcome.status, "4 byte reply (after 1 lost)");
+    }
+
+    #[tokio::test]
+    async fn every_datagram_lost_is_down() {
+        let (port, received) = server(usize::MAX, pong).await;
+        let outcome = check(&format!("udp://127.0.0.1:{}?attempts=3", port), TIMEOUT).await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "no reply to 3 datagrams");
+        assert_eq!(outcome.failure, Some("timeout"));
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+
+        let outcome = check(&format!("udp://127.0.0.1:{}?attempts=1", port), TIMEOUT).await;
+        assert_eq!(outcome.status, "no reply to 1 datagram");
+
+        let outcome = check(
+            &format!("udp://127.0.0.1:{}?reply=optional&attempts=1", port),
+            TIMEOUT,
+        )
+        .await;
+        assert!(outcome.is_up, "{}", outcome.status);
+    }
+
+    #[tokio::test]
+    async fn closed_ports_are_down_even_when_replies_are_optional() {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let outcome = check(&format!("udp://127.0.0.1:{}?reply=optional", port), TIMEOUT).await;
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, "port unreachable");
+        assert_eq!(outcome.failure, Some("connect"));
+    }
+
+    #[tokio::test]
+    async fn dns_replies_to_other_queries_are_ignored() {
+        // A stray answer with the wrong id comes first
+        fn answer(query: &[u8]) -> Vec<Vec<u8>> {
+            let id = u16::from_be_bytes([query[0], query[1]]);
+            vec![
+                dns_response(query, id.wrapping_add(1), 2, 0),
+                dns_response(query, id, 0, 1),
+            ]
+        }
+        let (port, _) = server(0, answer).await;
+        let outcome = check(
+            &format!("dns://127.0.0.1:{}?name=example.com", port),
+            TIMEOUT,
+        )
+        .await;
+        assert!(outcome.is_up, "{}", outcome.status);
+        assert_eq!(outcome.status, "NOERROR, 1 answer");
+    }
+
+    #[tokio::test]
+    async fn ntp_servers_are_checked() {
+        fn answer(_: &[u8]) -> Vec<Vec<u8>> {
+            vec![ntp_response(0, 1, unix_now())]
+        }
+        let (port, _) = server(0, answer).await;
+        let outcome = check(&format!("ntp://127.0.0.1:{}", port), TIMEOUT).await;
+        assert!(outcome.is_up, "{}", outcome.status);
+        assert!(
+            outcome.status.starts_with("stratum 1, offset "),
+            "{}",
+            outcome.status
+        );
+    }
+}
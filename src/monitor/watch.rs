@@ -0,0 +1,651 @@
+use anyhow::Result;
+use colored::*;
+use serde_json::json;
+use std::io::Write;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use terminal_size::terminal_size;
+
+use crate::monitor::config;
+use crate::monitor::control::{notify_sites_changed, send_command};
+use crate::monitor::db::{
+    get_all_sites, get_check_counts, get_site_history, set_site_paused, CheckRecord, Site,
+};
+use crate::monitor::service::{build_client, check_and_record, is_daemon_running, SiteCheck};
+use crate::utils::format_duration;
+
+// Number of recent checks drawn in the latency sparkline
+const SPARKLINE_LEN: usize = 24;
+// Number of checks listed in the history view
+const HISTORY_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Pause,
+    Check,
+    History,
+    Back,
+    Quit,
+}
+
+enum Event {
+    Key(Key),
+    CheckDone(SiteCheck),
+}
+
+#[derive(PartialEq, Eq)]
+enum View {
+    Sites,
+    History,
+}
+
+// Everything the dashboard shows for one site, loaded on each refresh
+struct SiteRow {
+    site: Site,
+    recent: Vec<CheckRecord>,
+    uptime_24h: Option<f64>,
+    failures_24h: i64,
+}
+
+struct Dashboard {
+    rows: Vec<SiteRow>,
+    selected: usize,
+    view: View,
+    history: Vec<CheckRecord>,
+    message: String,
+    daemon_running: bool,
+}
+
+// Full-screen dashboard that refreshes from the database until 'q' is pressed
+pub fn watch_sites(refresh: Duration) -> Result<()> {
+    let (tx, rx) = channel();
+
+    // Check and daemon messages would scribble over the screen
+    crate::output::set_quiet(true);
+
+    let _terminal = TerminalGuard::enter()?;
+    spawn_input_reader(tx.clone());
+
+    let mut dashboard = Dashboard {
+        rows: Vec::new(),
+        selected: 0,
+        view: View::Sites,
+        history: Vec::new(),
+        message: String::new(),
+        daemon_running: false,
+    };
+
+    loop {
+        dashboard.reload()?;
+        dashboard.draw();
+
+        match rx.recv_timeout(refresh) {
+            Ok(Event::Key(Key::Quit)) => break,
+            Ok(Event::Key(key)) => dashboard.handle_key(key, &tx)?,
+            Ok(Event::CheckDone(check)) => {
+                dashboard.message = format!(
+                    "{} is {} ({}{}{}){}",
+                    check.url,
+                    match (check.is_up, check.degraded) {
+                        (true, false) => "UP",
+                        (true, true) => "DEGRADED",
+                        (false, _) => "DOWN",
+                    },
+                    check
+                        .status_code
+                        .map(|code| format!("{} ", code))
+                        .unwrap_or_default(),
+                    check.status_desc,
+                    check
+                        .latency_ms
+                        .map(|ms| format!(", {}ms", ms))
+                        .unwrap_or_default(),
+                    if check.state_changed {
+                        ", state changed"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(())
+}
+
+impl Dashboard {
+    fn reload(&mut self) -> Result<()> {
+        let now = now_secs();
+        let mut rows = Vec::new();
+
+        for site in get_all_sites()? {
+            let site_id = site.id.unwrap_or_default();
+            let recent = get_site_history(site_id, 0, SPARKLINE_LEN)?;
+            let (total, up) = get_check_counts(site_id, now - 86400)?;
+            rows.push(SiteRow {
+                site,
+                recent,
+                uptime_24h: (total > 0).then(|| up as f64 * 100.0 / total as f64),
+                failures_24h: total - up,
+            });
+        }
+
+        self.rows = rows;
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+        self.daemon_running = is_daemon_running();
+
+        if self.view == View::History {
+            self.history = match self.rows.get(self.selected) {
+                Some(row) => get_site_history(row.site.id.unwrap_or_default(), 0, HISTORY_LEN)?,
+                None => Vec::new(),
+            };
+        }
+
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: Key, tx: &Sender<Event>) -> Result<()> {
+        let Some(row) = self.rows.get(self.selected) else {
+            return Ok(());
+        };
+        let url = row.site.url.clone();
+
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.rows.len() - 1),
+            Key::Pause => {
+                let paused = !row.site.paused;
+                set_site_paused(&url, paused)?;
+                notify_sites_changed();
+                self.message = format!("{} {}", if paused { "paused" } else { "resumed" }, url);
+            }
+            Key::Check => {
+                self.message = format!("checking {}...", url);
+                spawn_check(url, tx.clone());
+            }
+            Key::History => {
+                self.view = if self.view == View::History {
+                    View::Sites
+                } else {
+                    View::History
+                };
+            }
+            Key::Back => self.view = View::Sites,
+            Key::Quit => {}
+        }
+
+        Ok(())
+    }
+
+    fn draw(&self) {
+        let (width, height) = terminal_size()
+            .map(|(w, h)| (w.0 as usize, h.0 as usize))
+            .unwrap_or((80, 24));
+
+        let mut lines = Vec::new();
+        let now = now_secs();
+
+        let daemon = if self.daemon_running {
+            "● daemon running".green()
+        } else {
+            "● daemon not running".red()
+        };
+        lines.push(format!(
+            " {}  {} sites  {}  {}",
+            "isup watch".bold(),
+            self.rows.len(),
+            daemon,
+            chrono::Local::now().format("%H:%M:%S").to_string().dimmed()
+        ));
+        lines.push("─".repeat(width));
+
+        match self.view {
+            View::Sites => self.draw_sites(&mut lines, width, now),
+            View::History => self.draw_history(&mut lines),
+        }
+
+        // Pad the body so the footer sits on the last lines of the screen
+        let footer_lines = 3;
+        let body_height = height.saturating_sub(footer_lines);
+        lines.truncate(body_height);
+        while lines.len() < body_height {
+            lines.push(String::new());
+        }
+        lines.push("─".repeat(width));
+        lines.push(format!(" {}", self.message.italic()));
+        lines.push(format!(
+            " {}",
+            "↑/↓ select · p pause/resume · c check now · h history · q quit".dimmed()
+        ));
+
+        let mut out = std::io::stdout().lock();
+        let _ = write!(out, "\x1b[H");
+        for (i, line) in lines.iter().enumerate() {
+            let _ = write!(out, "{}\x1b[K", line);
+            if i + 1 < lines.len() {
+                let _ = write!(out, "\r\n");
+            }
+        }
+        let _ = out.flush();
+    }
+
+    fn draw_sites(&self, lines: &mut Vec<String>, width: usize, now: i64) {
+        if self.rows.is_empty() {
+            lines.push(format!(
+                "  {}",
+                "no sites are currently being monitored".yellow().italic()
+            ));
+            return;
+        }
+
+        let url_width = (width.saturating_sub(70)).clamp(20, 40);
+        lines.push(format!(
+            "   {:<url_width$} {:<10} {:<8} {:<width_spark$} {:>7} {:>6} {:>10}",
+            "URL".bold(),
+            "STATE".bold(),
+            "LATENCY".bold(),
+            "RECENT".bold(),
+            "UP 24H".bold(),
+            "FAILS".bold(),
+            "DOWNTIME".bold(),
+            width_spark = SPARKLINE_LEN
+        ));
+
+        for (i, row) in self.rows.iter().enumerate() {
+            lines.push(site_line(row, i == self.selected, url_width, now));
+        }
+    }
+
+    fn draw_history(&self, lines: &mut Vec<String>) {
+        let Some(row) = self.rows.get(self.selected) else {
+            return;
+        };
+
+        lines.push(format!(
+            " history for {} {}",
+            row.site.url.cyan(),
+            "(h/esc to go back)".dimmed()
+        ));
+        lines.push(format!(
+            " {:<20} {:<8} {:<6} {:<9} {}",
+            "TIME".bold(),
+            "STATE".bold(),
+            "CODE".bold(),
+            "LATENCY".bold(),
+            "STATUS".bold()
+        ));
+
+        if self.history.is_empty() {
+            lines.push(format!("  {}", "no checks recorded yet".yellow().italic()));
+        }
+
+        for record in &self.history {
+            let time = chrono::DateTime::from_timestamp(record.checked_at, 0)
+                .map(|t| {
+                    t.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let state = if record.is_up {
+                "● UP".green()
+            } else {
+                "● DOWN".red()
+            };
+            lines.push(format!(
+                " {:<20} {:<8} {:<6} {:<9} {}",
+                time,
+                state,
+                record
+                    .status_code
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                record
+                    .latency_ms
+                    .map(|ms| format!("{}ms", ms))
+                    .unwrap_or_else(|| "-".to_string()),
+                record.status.clone().unwrap_or_default()
+            ));
+        }
+    }
+}
+
+// One row of the sites table
+fn site_line(row: &SiteRow, selected: bool, url_width: usize, now: i64) -> String {
+    let site = &row.site;
+    let marker = if selected {
+        "▶".cyan().bold()
+    } else {
+        " ".normal()
+    };
+    let state = match site.is_up {
+        _ if site.paused => "⏸ PAUSED".blue().bold(),
+        Some(true) if site.degraded => "▲ DEGRADED".yellow().bold(),
+        Some(true) => "● UP".green().bold(),
+        Some(false) => "● DOWN".red().bold(),
+        None => "○ UNKNOWN".yellow().bold(),
+    };
+    let latency = site
+        .last_latency_ms
+        .map(|ms| format!("{}ms", ms))
+        .unwrap_or_else(|| "-".to_string());
+    let uptime = row
+        .uptime_24h
+        .map(|pct| format!("{:.1}%", pct))
+        .unwrap_or_else(|| "-".to_string());
+    let downtime = match (site.is_up, site.downtime_started) {
+        (Some(false), Some(start)) => format_duration(now - start).red().to_string(),
+        _ => "-".to_string(),
+    };
+
+    format!(
+        " {} {:<url_width$} {:<10} {:<8} {} {:>7} {:>6} {:>10}",
+        marker,
+        truncate(&site.url, url_width).cyan(),
+        state,
+        latency,
+        sparkline(&row.recent),
+        uptime,
+        row.failures_24h,
+        downtime
+    )
+}
+
+// Latency of recent checks as block characters, oldest first; failed checks are a red cross
+fn sparkline(recent: &[CheckRecord]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = recent
+        .iter()
+        .filter_map(|record| record.latency_ms)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut line = String::new();
+    for record in recent.iter().rev() {
+        match (record.is_up, record.latency_ms) {
+            (true, Some(ms)) => {
+                let level = (ms * (BLOCKS.len() as i64 - 1) / max) as usize;
+                line.push_str(&BLOCKS[level].to_string().green().to_string());
+            }
+            _ => line.push_str(&"×".red().to_string()),
+        }
+    }
+
+    // Pad by hand: the colour codes would throw off format width
+    line.push_str(&" ".repeat(SPARKLINE_LEN.saturating_sub(recent.len())));
+    line
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut short: String = text.chars().take(width.saturating_sub(1)).collect();
+        short.push('…');
+        short
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Have the daemon check the site, or run the same check ourselves when it isn't running.
+// Either way this happens off the UI thread.
+fn spawn_check(url: String, tx: Sender<Event>) {
+    std::thread::spawn(move || {
+        if let Ok(response) = send_command(json!({ "cmd": "check", "url": url })) {
+            let check = response["results"]
+                .as_array()
+                .and_then(|results| results.first())
+                .and_then(SiteCheck::from_json);
+            if let Some(check) = check {
+                let _ = tx.send(Event::CheckDone(check));
+            }
+            return;
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build();
+        let client = build_client(&config::current());
+
+        if let (Ok(runtime), Ok(client)) = (runtime, client) {
+            let check = runtime.block_on(check_and_record(&client, &url));
+            let _ = tx.send(Event::CheckDone(check));
+        }
+    });
+}
+
+// Read keys from stdin on a background thread
+fn spawn_input_reader(tx: Sender<Event>) {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 16];
+        loop {
+            let n = match read_stdin(&mut buf) {
+                Some(n) if n > 0 => n,
+                _ => return,
+            };
+
+            if let Some(key) = parse_key(&buf[..n]) {
+                if tx.send(Event::Key(key)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn parse_key(bytes: &[u8]) -> Option<Key> {
+    match bytes {
+        [27, b'[', b'A', ..] => Some(Key::Up),
+        [27, b'[', b'B', ..] => Some(Key::Down),
+        [27] => Some(Key::Back),
+        [b'k', ..] => Some(Key::Up),
+        [b'j', ..] => Some(Key::Down),
+        [b'p', ..] | [b' ', ..] => Some(Key::Pause),
+        [b'c', ..] => Some(Key::Check),
+        [b'h', ..] | [b'\r', ..] | [b'\n', ..] => Some(Key::History),
+        // Ctrl+C arrives as a byte in raw mode
+        [b'q', ..] | [3, ..] => Some(Key::Quit),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn read_stdin(buf: &mut [u8]) -> Option<usize> {
+    // Read straight from the fd so escape sequences arrive in one piece
+    let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+    (n >= 0).then_some(n as usize)
+}
+
+#[cfg(not(unix))]
+fn read_stdin(buf: &mut [u8]) -> Option<usize> {
+    use std::io::Read;
+    std::io::stdin().read(buf).ok()
+}
+
+// Switches to the alternate screen (and raw mode on unix), restoring the terminal on drop
+struct TerminalGuard {
+    #[cfg(unix)]
+    original: Option<libc::termios>,
+}
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        #[cfg(unix)]
+        let original = unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+                let original = termios;
+                libc::cfmakeraw(&mut termios);
+                // Keep output processing so the rest of isup's output behaves
+                termios.c_oflag |= libc::OPOST;
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+                Some(original)
+            } else {
+                None
+            }
+        };
+
+        // Alternate screen, hidden cursor, cleared
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        let _ = std::io::stdout().flush();
+
+        Ok(TerminalGuard {
+            #[cfg(unix)]
+            original,
+        })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+
+        #[cfg(unix)]
+        if let Some(original) = self.original {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Drop colour codes so rows can be compared as plain text
+    fn plain(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    fn check(is_up: bool, latency_ms: Option<i64>) -> CheckRecord {
+        CheckRecord {
+            checked_at: 0,
+            is_up,
+            status_code: None,
+            latency_ms,
+            status: None,
+        }
+    }
+
+    fn row(url: &str, is_up: Option<bool>, paused: bool) -> SiteRow {
+        SiteRow {
+            site: Site {
+                id: Some(1),
+                url: url.to_string(),
+                interval: 60.0,
+                is_up,
+                paused,
+                ..Default::default()
+            },
+            recent: Vec::new(),
+            uptime_24h: None,
+            failures_24h: 0,
+        }
+    }
+
+    #[test]
+    fn down_rows_show_latency_uptime_and_downtime() {
+        let now = 1_000_000;
+        let mut down = row("https://example.com", Some(false), false);
+        down.site.last_latency_ms = Some(120);
+        down.site.downtime_started = Some(now - 3600 - 5 * 60 - 7);
+        down.uptime_24h = Some(97.54);
+        down.failures_24h = 3;
+
+        let line = plain(&site_line(&down, true, 20, now));
+        let cells: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(
+            cells,
+            [
+                "▶",
+                "https://example.com",
+                "●",
+                "DOWN",
+                "120ms",
+                "97.5%",
+                "3",
+                "1h",
+                "5m"
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_without_data_show_dashes() {
+        let line = plain(&site_line(
+            &row("https://a.test", None, false),
+            false,
+            20,
+            0,
+        ));
+        let cells: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(
+            cells,
+            ["https://a.test", "○", "UNKNOWN", "-", "-", "0", "-"]
+        );
+
+        // Paused wins over the last known state, and downtime isn't shown while up
+        let mut paused = row("https://b.test", Some(true), true);
+        paused.site.downtime_started = Some(0);
+        let line = plain(&site_line(&paused, false, 20, 100));
+        assert!(line.contains("⏸ PAUSED"));
+        assert!(line.trim_end().ends_with(" -"));
+
+        // A warning from a command monitor shows as degraded, not up
+        let mut degraded = row("cmd://disk", Some(true), false);
+        degraded.site.degraded = true;
+        assert!(plain(&site_line(&degraded, false, 20, 0)).contains("▲ DEGRADED"));
+    }
+
+    #[test]
+    fn long_urls_are_truncated_to_the_column() {
+        assert_eq!(truncate("https://example.com", 30), "https://example.com");
+        assert_eq!(truncate("https://example.com", 10), "https://e…");
+        let line = plain(&site_line(
+            &row(
+                "https://a-very-long-host-name.example.com/health",
+                Some(true),
+                false,
+            ),
+            false,
+            20,
+            0,
+        ));
+        assert!(line.contains("https://a-very-long…"));
+    }
+
+    #[test]
+    fn sparklines_are_oldest_first_and_padded() {
+        // History comes newest first
+        let recent = [
+            check(true, Some(100)),
+            check(false, None),
+            check(true, Some(10)),
+        ];
+        let line = plain(&sparkline(&recent));
+        assert_eq!(line.chars().count(), SPARKLINE_LEN);
+        assert!(line.starts_with("▁×█"));
+    }
+}
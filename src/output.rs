@@ -0,0 +1,180 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+// Output format selected with the global --output flag
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+// Set by --quiet: nothing is printed and only the exit code reports results
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> OutputFormat {
+    *FORMAT.get().unwrap_or(&OutputFormat::Text)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+// True when stdout is reserved for machine-readable records
+pub fn is_structured() -> bool {
+    format() != OutputFormat::Text
+}
+
+// Human-readable messages go to stdout in text mode and to stderr otherwise,
+// so piping structured output into jq never picks up prose.
+macro_rules! say {
+    () => {
+        if $crate::output::is_quiet() {
+        } else if $crate::output::is_structured() { eprintln!() } else { println!() }
+    };
+    ($($arg:tt)*) => {
+        if $crate::output::is_quiet() {
+        } else if $crate::output::is_structured() { eprintln!($($arg)*) } else { println!($($arg)*) }
+    };
+}
+
+// Print a list of records in the selected structured format.
+// `columns` fixes the field order for csv output.
+pub fn emit_records(records: &[Value], columns: &[&str]) {
+    if is_quiet() {
+        return;
+    }
+    print!("{}", render_records(format(), records, columns));
+}
+
+fn render_records(format: OutputFormat, records: &[Value], columns: &[&str]) -> String {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            let array = Value::Array(records.to_vec());
+            out.push_str(&serde_json::to_string_pretty(&array).unwrap_or_default());
+            out.push('\n');
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                out.push_str(&format!("{}\n", record));
+            }
+        }
+        OutputFormat::Csv => {
+            out.push_str(&format!("{}\n", columns.join(",")));
+            for record in records {
+                let row: Vec<String> = columns
+                    .iter()
+                    .map(|column| csv_field(record.get(*column).unwrap_or(&Value::Null)))
+                    .collect();
+                out.push_str(&format!("{}\n", row.join(",")));
+            }
+        }
+    }
+    out
+}
+
+// Print a single record, e.g. the result of `isup add`
+pub fn emit_record(record: Value, columns: &[&str]) {
+    if is_quiet() {
+        return;
+    }
+
+    match format() {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&record).unwrap_or_default()
+            );
+        }
+        _ => emit_records(&[record], columns),
+    }
+}
+
+fn csv_field(value: &Value) -> String {
+    let raw = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    if raw.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(
+            csv_field(&json!("https://example.com")),
+            "https://example.com"
+        );
+        assert_eq!(csv_field(&json!("http://a,b")), "\"http://a,b\"");
+        assert_eq!(csv_field(&json!("say \"hi\"")), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&json!("two\nlines")), "\"two\nlines\"");
+        assert_eq!(csv_field(&json!("crlf\r\n")), "\"crlf\r\n\"");
+        assert_eq!(csv_field(&json!("")), "");
+        assert_eq!(csv_field(&Value::Null), "");
+        assert_eq!(csv_field(&json!(200)), "200");
+        assert_eq!(csv_field(&json!(true)), "true");
+        assert_eq!(csv_field(&json!(1.5)), "1.5");
+    }
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({ "url": "http://a,b", "status": "up", "code": 200 }),
+            json!({ "url": "https://c", "status": "down" }),
+        ]
+    }
+
+    #[test]
+    fn csv_follows_the_column_order() {
+        let csv = render_records(OutputFormat::Csv, &records(), &["url", "code", "status"]);
+        assert_eq!(
+            csv,
+            "url,code,status\n\"http://a,b\",200,up\nhttps://c,,down\n"
+        );
+
+        // A header is printed even with no records
+        assert_eq!(render_records(OutputFormat::Csv, &[], &["url"]), "url\n");
+    }
+
+    #[test]
+    fn json_and_ndjson_keep_every_field() {
+        let json = render_records(OutputFormat::Json, &records(), &["url"]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            Value::Array(records())
+        );
+        assert_eq!(render_records(OutputFormat::Json, &[], &["url"]), "[]\n");
+
+        let ndjson = render_records(OutputFormat::Ndjson, &records(), &["url"]);
+        let lines: Vec<Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, records());
+
+        assert_eq!(render_records(OutputFormat::Text, &records(), &["url"]), "");
+    }
+}
@@ -1,19 +1,78 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::OnceLock;
+use std::time::Duration;
+
+// Directory holding the database and other isup state (~/.isup)
+pub fn get_data_dir() -> PathBuf {
+    // Tests get a scratch directory instead of the user's real state
+    #[cfg(test)]
+    let data_dir = std::env::temp_dir().join(format!("isup-test-{}", std::process::id()));
+
+    // Get user's home directory for data storage
+    #[cfg(not(test))]
+    let data_dir = dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".isup");
+
+    // Create the directory if it doesn't exist
+    if !data_dir.exists() {
+        let _ = fs::create_dir_all(&data_dir);
+    }
+
+    data_dir
+}
+
+// Per-user directory for the daemon's control socket:
+// $XDG_RUNTIME_DIR/isup when there is one, otherwise ~/.isup
+pub fn get_runtime_dir() -> PathBuf {
+    // Tests keep the socket next to their scratch data
+    if cfg!(test) {
+        return get_data_dir();
+    }
+
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        let dir = runtime_dir.join("isup");
+        if runtime_dir.is_dir() && fs::create_dir_all(&dir).is_ok() {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = fs::set_permissions(&dir, fs::Permissions::from_mode(0o700));
+            }
+            return dir;
+        }
+    }
+
+    get_data_dir()
+}
+
+// Rough length of a span of seconds, e.g. 45s, 12m, 3h 5m or 2d 4h
+pub fn format_duration(seconds: i64) -> String {
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 3600 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 86400 {
+        format!("{}h {}m", seconds / 3600, (seconds % 3600) / 60)
+    } else {
+        format!("{}d {}h", seconds / 86400, (seconds % 86400) / 3600)
+    }
+}
 
 // Get a description for HTTP status codes
 pub fn get_status_description(code: u16) -> String {
     static STATUS_DESCRIPTIONS: OnceLock<HashMap<u16, &'static str>> = OnceLock::new();
-    
+
     let descriptions = STATUS_DESCRIPTIONS.get_or_init(|| {
         let mut map = HashMap::new();
-        
+
         // 1xx: Informational
         map.insert(100, "continue");
         map.insert(101, "switching protocols");
         map.insert(102, "processing");
         map.insert(103, "early hints");
-        
+
         // 2xx: Success
         map.insert(200, "ok");
         map.insert(201, "created");
@@ -25,7 +84,7 @@ pub fn get_status_description(code: u16) -> String {
         map.insert(207, "multi-status");
         map.insert(208, "reported");
         map.insert(226, "im used");
-        
+
         // 3xx: Redirection
         map.insert(300, "multiple choices");
         map.insert(301, "moved");
@@ -36,7 +95,7 @@ pub fn get_status_description(code: u16) -> String {
         map.insert(306, "switch proxy");
         map.insert(307, "temp redirect");
         map.insert(308, "perm redirect");
-        
+
         // 4xx: Client Error
         map.insert(400, "bad request");
         map.insert(401, "unauthorized");
@@ -67,7 +126,7 @@ pub fn get_status_description(code: u16) -> String {
         map.insert(429, "too many requests");
         map.insert(431, "headers too large");
         map.insert(451, "legal reasons");
-        
+
         // 5xx: Server Error
         map.insert(500, "server error");
         map.insert(501, "not implemented");
@@ -80,11 +139,104 @@ pub fn get_status_description(code: u16) -> String {
         map.insert(508, "loop detected");
         map.insert(510, "not extended");
         map.insert(511, "network auth");
-        
+
         map
     });
-    
-    descriptions.get(&code)
+
+    descriptions
+        .get(&code)
         .map(|desc| desc.to_string())
         .unwrap_or_else(|| format!("Unknown Status Code {}", code))
 }
+
+// Parse a human duration such as "90", "2s", "500ms", "5m", "1h30m" or "1d".
+// A bare number is taken as seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let invalid = || format!("invalid duration '{}', expected e.g. 30s, 5m or 1h", input);
+
+    if input.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = input.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let unit_len = rest[number_len..]
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len() - number_len);
+
+        let value: f64 = rest[..number_len].parse().map_err(|_| invalid())?;
+        let unit_seconds = match &rest[number_len..number_len + unit_len] {
+            "ms" => 0.001,
+            "s" | "sec" | "secs" => 1.0,
+            "m" | "min" | "mins" => 60.0,
+            "h" | "hr" | "hrs" => 3600.0,
+            "d" => 86400.0,
+            _ => return Err(invalid()),
+        };
+
+        let part = Duration::try_from_secs_f64(value * unit_seconds).map_err(|_| invalid())?;
+        total = total.checked_add(part).ok_or_else(invalid)?;
+        rest = &rest[number_len + unit_len..];
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration(" 5m "), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        assert_eq!(parse_duration("1hr 5mins").ok(), None);
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        for invalid in [
+            "",
+            "m",
+            "5x",
+            "5 m",
+            "-1",
+            "-5s",
+            "1e400",
+            "NaN",
+            "inf",
+            "s5",
+            "1..5s",
+            // Each part fits in a Duration, the sum doesn't
+            "10000000000000000000s10000000000000000000s",
+        ] {
+            assert!(
+                parse_duration(invalid).is_err(),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn durations_format() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(60), "1m");
+        assert_eq!(format_duration(3599), "59m");
+        assert_eq!(format_duration(3600 + 5 * 60 + 7), "1h 5m");
+        assert_eq!(format_duration(2 * 86400 + 4 * 3600 + 59), "2d 4h");
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use colored::*;
+use reqwest::Client;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::check::{check_request, normalize_url, CheckResult, StatusPattern, EXIT_DOWN, EXIT_OK};
+use crate::output::{emit_record, is_structured};
+use crate::utils::parse_duration;
+
+// Shortest --interval; anything less would hammer the site
+pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct WaitOptions {
+    pub timeout: Duration,
+    pub interval: Duration,
+    pub request_timeout: Duration,
+    pub until_down: bool,
+    pub fail_on: Vec<StatusPattern>,
+}
+
+// Parse --interval, which must leave the site some room between checks
+pub fn parse_interval(input: &str) -> Result<Duration, String> {
+    let interval = parse_duration(input)?;
+    if interval < MIN_INTERVAL {
+        return Err(format!(
+            "interval '{}' is too short, the minimum is {}ms",
+            input.trim(),
+            MIN_INTERVAL.as_millis()
+        ));
+    }
+    Ok(interval)
+}
+
+// Time until the deadline, or forever if there is none
+fn time_left(deadline: Option<Instant>, now: Instant) -> Duration {
+    deadline.map_or(Duration::MAX, |deadline| {
+        deadline.saturating_duration_since(now)
+    })
+}
+
+// Poll a URL until it is healthy (or down, with --until-down) and return the exit code:
+// 0 once the target state is reached, 1 if the timeout expires first.
+pub fn wait_for(url: &str, options: &WaitOptions) -> Result<i32> {
+    let url = normalize_url(url);
+    let target = if options.until_down { "down" } else { "up" };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    say!("waiting for {} to be {}...", url.cyan(), target.bold());
+
+    let client = Client::builder()
+        .user_agent("isup/2.0.3")
+        .build()
+        .context("failed to create HTTP client")?;
+
+    let started = Instant::now();
+    // A timeout too large to add to the clock never expires
+    let deadline = started.checked_add(options.timeout);
+    let mut attempts = 0;
+
+    let (last, reached): (CheckResult, bool) = loop {
+        attempts += 1;
+
+        // Never let a single request run past the overall deadline
+        let remaining = time_left(deadline, Instant::now());
+        let request = client.get(&url).timeout(
+            options
+                .request_timeout
+                .min(remaining)
+                .max(Duration::from_millis(100)),
+        );
+
+        let result = runtime.block_on(check_request(&url, request));
+        let healthy = !result.is_failure(&options.fail_on);
+        let reached = healthy != options.until_down;
+
+        say!(
+            "  attempt {}: {} ({})",
+            attempts,
+            if healthy {
+                result.state.as_str().green()
+            } else {
+                result.state.as_str().red()
+            },
+            result
+                .status_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| result.description.clone())
+        );
+
+        if reached {
+            say!(
+                "✅ {} is {} after {:.1}s",
+                url.cyan(),
+                target.bold(),
+                started.elapsed().as_secs_f64()
+            );
+            break (result, true);
+        }
+
+        let remaining = time_left(deadline, Instant::now());
+        if remaining.is_zero() {
+            say!(
+                "❌ timed out after {:.1}s waiting for {} to be {}",
+                started.elapsed().as_secs_f64(),
+                url.cyan(),
+                target.bold()
+            );
+            break (result, false);
+        }
+        std::thread::sleep(options.interval.max(MIN_INTERVAL).min(remaining));
+    };
+
+    if is_structured() {
+        emit_record(
+            json!({
+                "url": url,
+                "target": target,
+                "reached": reached,
+                "attempts": attempts,
+                "elapsed_ms": started.elapsed().as_millis() as u64,
+                "state": last.state.as_str(),
+                "status_code": last.status_code,
+            }),
+            &[
+                "url",
+                "target",
+                "reached",
+                "attempts",
+                "elapsed_ms",
+                "state",
+                "status_code",
+            ],
+        );
+    }
+
+    Ok(if reached { EXIT_OK } else { EXIT_DOWN })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_below_the_minimum_are_rejected() {
+        assert_eq!(parse_interval("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_interval("100ms"), Ok(MIN_INTERVAL));
+        for too_short in ["0", "0s", "99ms", "0.01", "1ms"] {
+            assert!(
+                parse_interval(too_short).is_err(),
+                "{:?} should be rejected",
+                too_short
+            );
+        }
+        assert!(parse_interval("soon").is_err());
+    }
+
+    #[test]
+    fn huge_timeouts_never_expire() {
+        let timeout = parse_duration("18000000000000000000").unwrap();
+        let now = Instant::now();
+        let deadline = now.checked_add(timeout);
+        assert_eq!(deadline, None);
+        assert_eq!(time_left(deadline, now), Duration::MAX);
+
+        let deadline = now.checked_add(Duration::from_secs(5));
+        assert_eq!(time_left(deadline, now), Duration::from_secs(5));
+        assert_eq!(
+            time_left(deadline, now + Duration::from_secs(6)),
+            Duration::ZERO
+        );
+    }
+}